@@ -79,16 +79,9 @@ pub fn sequentially_rationalizable_choice(
         q
     };
 
-    if cfg!(test) {
-        assert!(p.is_transitive());
-        assert!(p.is_reflexive());
-        assert!(p.is_strict());
-
-        assert!(q.is_transitive());
-        assert!(q.is_reflexive());
-        assert!(q.is_strict());
-    }
-
+    // On data that are not sequentially rationalizable, P and Q need not be
+    // partial orders. `exact_estimation` calls this on arbitrary data as a warm start,
+    // so we check instead of asserting (see the test `src_warm_start` there).
     if p.is_transitive() && p.is_reflexive() && p.is_strict()
         && q.is_transitive() && q.is_reflexive() && q.is_strict()
     {
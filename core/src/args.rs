@@ -1,11 +1,14 @@
 use argparse;
+use rpc::Transport;
 
 pub struct Args {
     pub fname_precomputed_preorders : Option<String>,
+    pub transport : Transport,
 }
 
 pub fn parse() -> Args {
     let mut fname_precomputed_preorders = String::new();
+    let mut transport = Transport::Binary;
 
     {
         let mut ap = argparse::ArgumentParser::new();
@@ -14,6 +17,9 @@ pub fn parse() -> Args {
         ap.refer(&mut fname_precomputed_preorders)
             .add_option(&["--precomputed-preorders"], argparse::Store, "Path to precomputed preorders");
 
+        ap.refer(&mut transport)
+            .add_option(&["--transport"], argparse::Store, "Request/response encoding: binary (default) or json");
+
         ap.parse_args_or_exit();
     }
 
@@ -24,5 +30,6 @@ pub fn parse() -> Args {
             } else {
                 Some(fname_precomputed_preorders)
             },
+        transport,
     }
 }
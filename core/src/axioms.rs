@@ -0,0 +1,323 @@
+use std::result;
+use std::fmt;
+use std::io::{Read,Write};
+use std::collections::BTreeSet;
+
+use simulation::Menu;
+use codec::{self,Packed,Encode,Decode};
+use rpc_common::{ChoiceRow,Subject,CancelFlag};
+use json::{self,Json,ToJson,FromJson};
+
+/* Axioms of choice correspondences tested on every pair of nested menus A ⊊ B
+ * with choices C(A) and C(B):
+ *
+ *  - Sen's α (contraction): C(B) ∩ A ⊆ C(A)
+ *  - Sen's β (expansion): if C(A) ∩ C(B) is nonempty, then C(A) ⊆ C(B)
+ *  - Congruence (Arrow): if C(B) ∩ A is nonempty, then C(A) = C(B) ∩ A
+ *  - Nash's IIA: if C(B) ⊆ A, then C(A) = C(B)
+ *
+ * Deferrals (empty choices) are not choices in this sense and are left out of these,
+ * but they are the subject of two more axioms, tested over the raw data:
+ *
+ *  - desirability: if C(B) ∩ A is nonempty, then C(A) is nonempty,
+ *    i.e. the subject does not defer at a menu with an alternative desirable enough
+ *    to be chosen from a larger menu
+ *  - deferral monotonicity: if C(A) is empty, then C(B) is empty,
+ *    as with undominated choice, where more alternatives make it harder
+ *    to find one that beats all others
+ *
+ * Repeated menus are compared only with other menus.
+ */
+
+#[derive(Debug)]
+pub struct Request {
+    pub subject : Packed<Subject>,
+}
+
+impl Decode for Request {
+    fn decode<R : Read>(f : &mut R) -> codec::Result<Request> {
+        Ok(Request {
+            subject: Decode::decode(f)?,
+        })
+    }
+}
+
+impl FromJson for Request {
+    fn from_json(js : &Json) -> json::Result<Request> {
+        Ok(Request {
+            subject: json::field(js, "subject")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axiom {
+    Alpha,
+    Beta,
+    Congruence,
+    NashIia,
+    Desirability,
+    DeferralMonotonicity,
+}
+
+impl Encode for Axiom {
+    fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+        match *self {
+            Axiom::Alpha => 0u8.encode(f),
+            Axiom::Beta => 1u8.encode(f),
+            Axiom::Congruence => 2u8.encode(f),
+            Axiom::NashIia => 3u8.encode(f),
+            Axiom::Desirability => 4u8.encode(f),
+            Axiom::DeferralMonotonicity => 5u8.encode(f),
+        }
+    }
+}
+
+impl ToJson for Axiom {
+    fn to_json(&self) -> Json {
+        String::from(match *self {
+            Axiom::Alpha => "alpha",
+            Axiom::Beta => "beta",
+            Axiom::Congruence => "congruence",
+            Axiom::NashIia => "nash-iia",
+            Axiom::Desirability => "desirability",
+            Axiom::DeferralMonotonicity => "deferral-monotonicity",
+        }).to_json()
+    }
+}
+
+impl Axiom {
+    pub fn all() -> Vec<Axiom> {
+        vec![
+            Axiom::Alpha, Axiom::Beta, Axiom::Congruence, Axiom::NashIia,
+            Axiom::Desirability, Axiom::DeferralMonotonicity,
+        ]
+    }
+
+    // is the axiom violated by the choices at the nested menus small ⊊ large?
+    fn is_violated_by(self, small : &ChoiceRow, large : &ChoiceRow) -> bool {
+        let mut chosen_in_small = large.choice.clone();
+        chosen_in_small &= &small.menu;
+
+        let small_deferred = small.choice.view().is_empty();
+        let large_deferred = large.choice.view().is_empty();
+
+        match self {
+            Axiom::Desirability => small_deferred && chosen_in_small.view().is_nonempty(),
+
+            Axiom::DeferralMonotonicity => small_deferred && !large_deferred,
+
+            // the remaining axioms are about active choices
+            _ if small_deferred || large_deferred => false,
+
+            Axiom::Alpha => !chosen_in_small.view().is_subseteq_of(small.choice.view()),
+
+            Axiom::Beta => {
+                let mut chosen_in_both = small.choice.clone();
+                chosen_in_both &= &large.choice;
+                chosen_in_both.view().is_nonempty()
+                    && !small.choice.view().is_subseteq_of(large.choice.view())
+            }
+
+            Axiom::Congruence => chosen_in_small.view().is_nonempty()
+                && chosen_in_small != small.choice,
+
+            Axiom::NashIia => large.choice.view().is_subseteq_of(small.menu.view())
+                && large.choice != small.choice,
+        }
+    }
+}
+
+pub struct Row {
+    axiom : Axiom,
+    menu_pairs : BTreeSet<(Menu, Menu)>,  // (smaller, larger) menus violating the axiom
+}
+
+impl Encode for Row {
+    fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+        (self.axiom, &self.menu_pairs).encode(f)
+    }
+}
+
+impl Row {
+    fn json_with(&self, alternatives : Option<&[String]>) -> Json {
+        json::object(vec![
+            ("axiom", self.axiom.to_json()),
+            ("violations", self.menu_pairs.len().to_json()),
+            ("menu_pairs", Json::Array(self.menu_pairs.iter().map(
+                |(smaller, larger)| json::object(vec![
+                    ("smaller", json::alt_set(smaller, alternatives)),
+                    ("larger", json::alt_set(larger, alternatives)),
+                ])
+            ).collect())),
+        ])
+    }
+}
+
+pub struct Response {
+    subject_name : String,
+    rows : Vec<Row>,  // one for every axiom
+}
+
+impl Encode for Response {
+    fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+        (&self.subject_name, &self.rows).encode(f)
+    }
+}
+
+impl Response {
+    /// With the alternatives by name rather than by index.
+    pub fn to_named_json(&self, alternatives : &[String]) -> Json {
+        self.json_with(Some(alternatives))
+    }
+
+    fn json_with(&self, alternatives : Option<&[String]>) -> Json {
+        json::object(vec![
+            ("subject", self.subject_name.to_json()),
+            ("axioms", Json::Array(self.rows.iter().map(
+                |row| row.json_with(alternatives)
+            ).collect())),
+        ])
+    }
+}
+
+impl ToJson for Response {
+    fn to_json(&self) -> Json {
+        self.json_with(None)
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Cancelled,
+}
+
+impl Encode for Error {
+    fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+        match self {
+            Error::Cancelled => 0u8.encode(f),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+pub fn run(request : &Request, cancel : &CancelFlag) -> Result<Response> {
+    let subject = request.subject.unpack();
+    let choices = &subject.choices;
+
+    let mut rows : Vec<Row> = Axiom::all().into_iter().map(
+        |axiom| Row{axiom, menu_pairs: BTreeSet::new()}
+    ).collect();
+
+    for small in choices {
+        if cancel.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+
+        for large in choices {
+            if !small.menu.view().is_strict_subset_of(large.menu.view()) {
+                continue;
+            }
+
+            for row in &mut rows {
+                if row.axiom.is_violated_by(small, large) {
+                    row.menu_pairs.insert((small.menu.clone(), large.menu.clone()));
+                }
+            }
+        }
+    }
+
+    Ok(Response {
+        subject_name: subject.name.clone(),
+        rows,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alt::Alt;
+    use alt_set::AltSet;
+    use std::iter::FromIterator;
+
+    fn violations(choices : Vec<ChoiceRow>) -> Vec<(Axiom, Vec<(Menu, Menu)>)> {
+        let request = Request{subject: Packed(Subject{
+            name: String::from("subject"),
+            alternatives: (0..4).map(|s| s.to_string()).collect(),
+            choices,
+        })};
+
+        run(&request, &CancelFlag::new()).unwrap().rows.into_iter().map(
+            |row| (row.axiom, row.menu_pairs.into_iter().collect())
+        ).collect()
+    }
+
+    #[test]
+    fn nested_menus() {
+        let m01 = alts![0, 1];
+        let m012 = alts![0, 1, 2];
+
+        // 0 is chosen from the larger menu but not from the smaller one
+        assert_eq!(violations(choices![[0,1] -> [1], [0,1,2] -> [0]]), vec![
+            (Axiom::Alpha, vec![(m01.clone(), m012.clone())]),
+            (Axiom::Beta, vec![]),
+            (Axiom::Congruence, vec![(m01.clone(), m012.clone())]),
+            (Axiom::NashIia, vec![(m01.clone(), m012.clone())]),
+            (Axiom::Desirability, vec![]),
+            (Axiom::DeferralMonotonicity, vec![]),
+        ]);
+
+        // indifference between 0 and 1 is broken in the larger menu
+        assert_eq!(violations(choices![[0,1] -> [0,1], [0,1,2] -> [0]]), vec![
+            (Axiom::Alpha, vec![]),
+            (Axiom::Beta, vec![(m01.clone(), m012.clone())]),
+            (Axiom::Congruence, vec![(m01.clone(), m012.clone())]),
+            (Axiom::NashIia, vec![(m01.clone(), m012.clone())]),
+            (Axiom::Desirability, vec![]),
+            (Axiom::DeferralMonotonicity, vec![]),
+        ]);
+
+        // consistent, with unrelated menus
+        assert!(violations(choices![
+            [0,1] -> [0],
+            [0,1,2] -> [2],
+            [2,3] -> [3]
+        ]).into_iter().all(|(_axiom, pairs)| pairs.is_empty()));
+    }
+
+    #[test]
+    fn deferrals() {
+        let m0 = alts![0];
+        let m01 = alts![0, 1];
+        let m012 = alts![0, 1, 2];
+
+        // deferral at {0} although 0 is chosen from {0,1},
+        // deferral at {0,1} but not at {0,1,2}
+        assert_eq!(violations(choices![[0] -> [], [0,1] -> [], [0,1] -> [0], [0,1,2] -> [2]]), vec![
+            (Axiom::Alpha, vec![]),
+            (Axiom::Beta, vec![]),
+            (Axiom::Congruence, vec![]),
+            (Axiom::NashIia, vec![]),
+            (Axiom::Desirability, vec![(m0.clone(), m01.clone())]),
+            (Axiom::DeferralMonotonicity, vec![
+                (m0.clone(), m01.clone()),
+                (m0.clone(), m012.clone()),
+                (m01.clone(), m012.clone()),
+            ]),
+        ]);
+
+        // deferring more often at larger menus is consistent
+        assert!(violations(choices![[0] -> [0], [0,1] -> [0], [0,1,2] -> []]).into_iter().all(
+            |(_axiom, pairs)| pairs.is_empty()
+        ));
+    }
+}
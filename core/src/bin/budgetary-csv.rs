@@ -0,0 +1,35 @@
+extern crate argparse;
+extern crate prest;
+
+use std::process;
+use prest::budgetary::reader;
+
+// Validate a budgetary CSV on stdin and write it to stdout in the wide format.
+fn main() {
+    let mut wide = false;
+
+    {
+        let mut ap = argparse::ArgumentParser::new();
+        ap.set_description("Convert a budgetary CSV (long format by default) to the wide format");
+
+        ap.refer(&mut wide)
+            .add_option(&["--wide"], argparse::StoreTrue, "The input is in the wide format");
+
+        ap.parse_args_or_exit();
+    }
+
+    let response = if wide {
+        reader::read_wide(std::io::stdin())
+    } else {
+        reader::read_long(std::io::stdin())
+    };
+
+    let result = response.and_then(
+        |response| reader::write_wide(std::io::stdout(), &response)
+    );
+
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
@@ -135,5 +135,27 @@ fn main() {
             println!("P = {}", fmt_digraph(p, &alt_names, &[]));
             println!("Q = {}", fmt_digraph(q, &alt_names, &[]));
         }
+
+        Instance::LimitedAttention{ref p, ref filter} => {
+            assert_eq!(p.size, alt_names.len() as u32, "preorder size ({}) does not match the number of alternatives ({})", p.size, alt_names.len());
+
+            println!("preference = {}", fmt_digraph(p, &alt_names, &[]));
+            for &(ref menu, choice) in filter {
+                let considered : Vec<&str> = menu.view().iter()
+                    .filter(|&a| !p.lt(choice, a))
+                    .map(|a| alt_names[a.index() as usize].as_str())
+                    .collect();
+                println!("considered in {}: {}", menu, considered.join(", "));
+            }
+        }
+
+        Instance::RandomConsideration{ref p, ref attention} => {
+            assert_eq!(p.size, alt_names.len() as u32, "preorder size ({}) does not match the number of alternatives ({})", p.size, alt_names.len());
+
+            println!("{}", fmt_digraph(p, &alt_names, &[]));
+            for (name, level) in alt_names.iter().zip(attention) {
+                println!("attention to {}: {}/{}", name, level, model::ATTENTION_DENOMINATOR);
+            }
+        }
     }
 }
@@ -3,8 +3,8 @@ extern crate rand;
 
 use rand::SeedableRng;
 use rand::rngs::SmallRng;
-use prest::{rpc,precomputed,estimation,args,consistency,simulation,instviz};
-use prest::{experiment_stats,budgetary,integrity};
+use prest::{rpc,precomputed,estimation,args,consistency,axioms,simulation,instviz,stochastic,significance};
+use prest::{experiment_stats,budgetary,integrity,handshake};
 use precomputed::Precomputed;
 
 fn rpc_loop(args : &args::Args) {
@@ -12,23 +12,33 @@ fn rpc_loop(args : &args::Args) {
 
     // core state
     let mut rng : SmallRng = SeedableRng::from_seed([0;32]);
-    let mut rpc = IO::from_stdio();
+    let mut rpc = IO::from_stdio(args.transport);
+    let cancel = rpc.cancel.clone();
     let mut precomp = Precomputed::new(
         args.fname_precomputed_preorders.as_ref().map(String::as_str)
     );
 
     loop {
-        let request : ActionRequest = rpc.read().unwrap();
+        let request : ActionRequest = rpc.read_request().unwrap();
 
         match request {
             ActionRequest::Quit => {
                 break;
             }
 
+            ActionRequest::Hello(req) => {
+                rpc.write_result(handshake::run(&req)).unwrap();
+            }
+
             ActionRequest::Echo(msg) => {
                 rpc.write_result(Ok::<String, bool>(msg)).unwrap();
             }
 
+            ActionRequest::Cancel => {
+                // the reader thread sets the cancel flag and never forwards cancellations,
+                // but if one gets here, there is nothing running to cancel
+            }
+
             ActionRequest::Crash(msg) => {
                 panic!("{}", msg);
             }
@@ -38,19 +48,41 @@ fn rpc_loop(args : &args::Args) {
             }
 
             ActionRequest::Estimation(req) => {
-                rpc.write_result(estimation::run(&mut precomp, &req)).unwrap();
+                rpc.write_result(estimation::run(&mut precomp, &req, &cancel)).unwrap();
+            }
+
+            ActionRequest::EstimationStream(req) => {
+                let resp = estimation::run_streaming(Logger::new(&mut rpc), &mut precomp, &req, &cancel);
+                rpc.write_result(resp).unwrap();
+            }
+
+            ActionRequest::StochasticEstimation(req) => {
+                rpc.write_result(stochastic::run(&req)).unwrap();
+            }
+
+            ActionRequest::StochasticAxioms(req) => {
+                rpc.write_result(stochastic::axioms::run(&req)).unwrap();
+            }
+
+            ActionRequest::Significance(req) => {
+                let resp = significance::run(Logger::new(&mut rpc), &mut rng, &mut precomp, &req, &cancel);
+                rpc.write_result(resp).unwrap();
             }
 
             ActionRequest::Consistency(req) => {
-                rpc.write_result(consistency::run(&req)).unwrap();
+                rpc.write_result(consistency::run(&req, &cancel)).unwrap();
             }
 
             ActionRequest::TupleIntransMenus(req) => {
-                rpc.write_result(consistency::tuple_intrans::run_menus(&req)).unwrap();
+                rpc.write_result(consistency::tuple_intrans::run_menus(&req, &cancel)).unwrap();
             }
 
             ActionRequest::TupleIntransAlts(req) => {
-                rpc.write_result(consistency::tuple_intrans::run_alts(&req)).unwrap();
+                rpc.write_result(consistency::tuple_intrans::run_alts(&req, &cancel)).unwrap();
+            }
+
+            ActionRequest::Axioms(req) => {
+                rpc.write_result(axioms::run(&req, &cancel)).unwrap();
             }
 
             ActionRequest::SetRngSeed(seed) => {
@@ -78,10 +110,22 @@ fn rpc_loop(args : &args::Args) {
             }
 
             ActionRequest::BudgetaryConsistency(req) => {
-                let resp = budgetary::consistency::run(Logger::new(&mut rpc), req);
+                let resp = budgetary::consistency::run(Logger::new(&mut rpc), req, &cancel);
                 rpc.write_result(resp).unwrap();
             }
 
+            ActionRequest::BudgetaryUtility(req) => {
+                rpc.write_result(budgetary::afriat::run(req)).unwrap();
+            }
+
+            ActionRequest::BudgetarySupport(req) => {
+                rpc.write_result(budgetary::support::run(&req, &cancel)).unwrap();
+            }
+
+            ActionRequest::BudgetaryCsv(req) => {
+                rpc.write_result(budgetary::reader::run(&req)).unwrap();
+            }
+
             ActionRequest::IntegrityCheck(req) => {
                 rpc.write_result(integrity::run(req)).unwrap();
             }
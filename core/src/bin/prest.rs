@@ -0,0 +1,543 @@
+extern crate argparse;
+extern crate csv;
+extern crate rand;
+extern crate base64;
+extern crate prest;
+
+use std::fs::File;
+use std::io::{self,Read,Write};
+use std::process;
+use std::str::FromStr;
+use argparse::{ArgumentParser,Store,StoreOption,StoreTrue,Collect,List};
+use rand::SeedableRng;
+use rand::rngs::SmallRng;
+use base64::prelude::BASE64_STANDARD;
+use base64::engine::Engine;
+
+use prest::{estimation,consistency,axioms,stochastic,integrity,experiment_stats,simulation,codec};
+use prest::budgetary::{self,reader};
+use prest::precomputed::Precomputed;
+use prest::rpc::DummyLogger;
+use prest::rpc_common::{Subject,ChoiceRow,CancelFlag};
+use prest::csv::{ToCell,read_subjects};
+use prest::codec::Packed;
+use prest::model::Model;
+use prest::json::{self,Json,ToJson};
+
+/* Batch interface to the analyses of the GUI.
+ *
+ * Choice data are read from a CSV file (or stdin) with the columns
+ * `subject`, `menu`, `choice` and optionally `default`,
+ * where menus and choices are comma-separated alternatives.
+ * Budgetary data are read in the long or wide format of `budgetary::reader`.
+ *
+ * Every subcommand writes one record per subject (or per simulated choice),
+ * either as CSV with nested fields flattened into dotted column names,
+ * or as JSON, one object per line.
+ */
+
+type Result<T> = std::result::Result<T, String>;
+
+#[derive(Debug, Clone, Copy)]
+enum Command {
+    Estimate,
+    Consistency,
+    TupleIntrans,
+    Axioms,
+    StochasticAxioms,
+    Integrity,
+    Summary,
+    Simulate,
+    Budgetary,
+}
+
+impl FromStr for Command {
+    type Err = String;
+    fn from_str(s : &str) -> Result<Command> {
+        match s {
+            "estimate" => Ok(Command::Estimate),
+            "consistency" => Ok(Command::Consistency),
+            "tuple-intrans" => Ok(Command::TupleIntrans),
+            "axioms" => Ok(Command::Axioms),
+            "stochastic-axioms" => Ok(Command::StochasticAxioms),
+            "integrity" => Ok(Command::Integrity),
+            "summary" => Ok(Command::Summary),
+            "simulate" => Ok(Command::Simulate),
+            "budgetary" => Ok(Command::Budgetary),
+            _ => Err(format!("unknown command: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Csv,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+    fn from_str(s : &str) -> Result<Format> {
+        match s {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("unknown output format: {}", s)),
+        }
+    }
+}
+
+// options shared by all subcommands
+struct Common {
+    input : String,
+    format : Format,
+}
+
+impl Common {
+    fn new() -> Common {
+        Common {
+            input: String::new(),
+            format: Format::Csv,
+        }
+    }
+
+    fn register<'a>(&'a mut self, ap : &mut ArgumentParser<'a>) {
+        ap.refer(&mut self.input)
+            .add_argument("input", Store, "Input CSV file (default: stdin)");
+        ap.refer(&mut self.format)
+            .add_option(&["--format"], Store, "Output format: csv (default) or json");
+    }
+
+    fn open(&self) -> Result<Box<dyn Read>> {
+        if self.input.is_empty() || self.input == "-" {
+            Ok(Box::new(io::stdin()))
+        } else {
+            File::open(&self.input).map(
+                |f| Box::new(f) as Box<dyn Read>
+            ).map_err(|e| format!("{}: {}", self.input, e))
+        }
+    }
+}
+
+fn parse_args(ap : ArgumentParser, args : Vec<String>) {
+    if let Err(code) = ap.parse(args, &mut io::stdout(), &mut io::stderr()) {
+        process::exit(code);
+    }
+}
+
+fn read_choices(common : &Common) -> Result<Vec<Subject>> {
+    let subjects = read_subjects::<_, (), ChoiceRow>(common.open()?, "subject").map_err(|e| e.to_string())?;
+    let subjects : Vec<Subject> = subjects.map(|subject| subject.map(
+        |s| Subject {
+            name: s.name,
+            alternatives: s.alternatives,
+            choices: s.rows,
+        }
+    ).map_err(|e| e.to_string())).collect::<Result<_>>()?;
+
+    Ok(with_all_alternatives(subjects))
+}
+
+/// Alternatives accumulate while reading so earlier subjects know only a prefix of them.
+/// Give every subject the full list, like the GUI does, so that alternative indices
+/// and instance codes mean the same for all subjects.
+fn with_all_alternatives(mut subjects : Vec<Subject>) -> Vec<Subject> {
+    let alternatives = subjects.last().map(|s| s.alternatives.clone()).unwrap_or_default();
+    for subject in &mut subjects {
+        subject.alternatives = alternatives.clone();
+    }
+    subjects
+}
+
+fn write_output(format : Format, records : &[Json]) -> Result<()> {
+    let stdout = io::stdout();
+    match format {
+        Format::Json => {
+            let mut out = stdout.lock();
+            for record in records {
+                writeln!(out, "{}", record).map_err(|e| e.to_string())?;
+            }
+            Ok(())
+        }
+
+        Format::Csv => {
+            let rows : Vec<Vec<(String, String)>> = records.iter().map(Json::flatten).collect();
+            let header : Vec<String> = match rows.first() {
+                None => return Ok(()),
+                Some(row) => row.iter().map(|(col, _)| col.clone()).collect(),
+            };
+
+            let mut out = csv::Writer::from_writer(stdout.lock());
+            out.write_record(&header).map_err(|e| e.to_string())?;
+            for row in &rows {
+                out.write_record(header.iter().map(
+                    |col| row.iter().find(|(c, _)| c == col).map_or("", |(_, cell)| cell.as_str())
+                )).map_err(|e| e.to_string())?;
+            }
+            out.flush().map_err(|e| e.to_string())
+        }
+    }
+}
+
+fn estimate(args : Vec<String>) -> Result<()> {
+    let mut common = Common::new();
+    let mut models : Vec<String> = Vec::new();
+    let mut stochastic_models : Vec<String> = Vec::new();
+    let mut disregard_deferrals = false;
+    let mut diagnostics = false;
+    let mut disable_parallelism = false;
+    let mut fname_precomputed_preorders = String::new();
+
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Estimate models of choice for every subject");
+        common.register(&mut ap);
+        ap.refer(&mut models).required()
+            .add_option(&["-m", "--model"], Collect,
+                "Model to estimate, e.g. preorder-maximization:strict,total (repeatable)");
+        ap.refer(&mut stochastic_models)
+            .add_option(&["-s", "--stochastic-model"], Collect,
+                "Stochastic model to fit as well, luce or random-preference (repeatable)");
+        ap.refer(&mut disregard_deferrals)
+            .add_option(&["--disregard-deferrals"], StoreTrue, "Drop deferrals before estimation");
+        ap.refer(&mut diagnostics)
+            .add_option(&["--diagnostics"], StoreTrue, "List the observations each best instance misses");
+        ap.refer(&mut disable_parallelism)
+            .add_option(&["--disable-parallelism"], StoreTrue, "Estimate the subjects one by one");
+        ap.refer(&mut fname_precomputed_preorders)
+            .add_option(&["--precomputed-preorders"], Store, "Path to precomputed preorders");
+        parse_args(ap, args);
+    }
+
+    let models = models.iter().map(|m| m.parse::<Model>()).collect::<Result<Vec<Model>>>()?;
+    let stochastic_models = stochastic_models.iter().map(
+        |m| m.parse::<stochastic::StochasticModel>()
+    ).collect::<Result<Vec<_>>>()?;
+    let subjects = read_choices(&common)?;
+    let mut precomputed = Precomputed::new(
+        if fname_precomputed_preorders.is_empty() { None } else { Some(fname_precomputed_preorders.as_str()) }
+    );
+
+    let request = estimation::Request {
+        subjects: subjects.iter().cloned().map(Packed).collect(),
+        models,
+        disable_parallelism,
+        disregard_deferrals,
+        diagnostics,
+        stochastic_models,
+    };
+
+    let responses = estimation::run(&mut precomputed, &request, &CancelFlag::new()).map_err(|e| e.to_string())?;
+    write_output(common.format, &responses.iter().zip(&subjects).map(
+        |(Packed(response), subject)| response.to_named_json(&subject.alternatives)
+    ).collect::<Vec<Json>>())
+}
+
+fn consistency(args : Vec<String>) -> Result<()> {
+    let mut common = Common::new();
+    let mut cycle_limit : Option<u32> = None;
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Test GARP, SARP and WARP for every subject");
+        common.register(&mut ap);
+        ap.refer(&mut cycle_limit)
+            .add_option(&["--cycle-limit"], StoreOption,
+                "Also count the violations by cycle length, enumerating at most this many cycles");
+        parse_args(ap, args);
+    }
+
+    let mut records = Vec::new();
+    for subject in read_choices(&common)? {
+        let names = subject.alternatives.clone();
+        let request = consistency::Request{subject: Packed(subject), cycle_limit};
+        let response = consistency::run(&request, &CancelFlag::new()).map_err(|e| e.to_string())?;
+        records.push(response.to_named_json(&names));
+    }
+
+    write_output(common.format, &records)
+}
+
+fn tuple_intrans(args : Vec<String>) -> Result<()> {
+    let mut common = Common::new();
+    let mut alternatives = false;
+    let mut cycle_limit : Option<u32> = None;
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("List the tuples of menus involved in GARP violations");
+        common.register(&mut ap);
+        ap.refer(&mut alternatives)
+            .add_option(&["--alternatives"], StoreTrue, "List tuples of alternatives instead of menus");
+        ap.refer(&mut cycle_limit)
+            .add_option(&["--cycle-limit"], StoreOption, "Enumerate at most this many cycles");
+        parse_args(ap, args);
+    }
+
+    let mut records = Vec::new();
+    for subject in read_choices(&common)? {
+        let names = subject.alternatives.clone();
+        let request = consistency::Request{subject: Packed(subject), cycle_limit};
+        records.push(if alternatives {
+            consistency::tuple_intrans::run_alts(&request, &CancelFlag::new())
+                .map_err(|e| e.to_string())?.to_named_json(&names)
+        } else {
+            consistency::tuple_intrans::run_menus(&request, &CancelFlag::new())
+                .map_err(|e| e.to_string())?.to_named_json(&names)
+        });
+    }
+
+    write_output(common.format, &records)
+}
+
+fn axioms(args : Vec<String>) -> Result<()> {
+    let mut common = Common::new();
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("List the nested menus violating Sen's alpha and beta, Congruence, Nash's IIA and the deferral axioms");
+        common.register(&mut ap);
+        parse_args(ap, args);
+    }
+
+    let mut records = Vec::new();
+    for subject in read_choices(&common)? {
+        let names = subject.alternatives.clone();
+        let request = axioms::Request{subject: Packed(subject)};
+        let response = axioms::run(&request, &CancelFlag::new()).map_err(|e| e.to_string())?;
+        records.push(response.to_named_json(&names));
+    }
+
+    write_output(common.format, &records)
+}
+
+fn stochastic_axioms(args : Vec<String>) -> Result<()> {
+    let mut common = Common::new();
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Test regularity, stochastic transitivity and the triangle inequality \
+            on the choice frequencies of every subject and of all subjects pooled");
+        common.register(&mut ap);
+        parse_args(ap, args);
+    }
+
+    let subjects = read_choices(&common)?;
+    let names = subjects.first().map(|s| s.alternatives.clone()).unwrap_or_default();
+    let request = stochastic::axioms::Request{subjects: subjects.into_iter().map(Packed).collect()};
+    let response = stochastic::axioms::run(&request).map_err(|e| e.to_string())?;
+
+    let mut records : Vec<Json> = response.subjects.iter().map(|r| r.to_named_json(&names)).collect();
+    records.push(response.pooled.to_named_json(&names));
+    write_output(common.format, &records)
+}
+
+fn integrity(args : Vec<String>) -> Result<()> {
+    let mut common = Common::new();
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Check the data for repeated menus and choices outside the menu");
+        common.register(&mut ap);
+        parse_args(ap, args);
+    }
+
+    let mut records = Vec::new();
+    for subject in read_choices(&common)? {
+        let names = subject.alternatives.clone();
+        let response = integrity::run(integrity::Request{subject: Packed(subject)}).map_err(|e| e.to_string())?;
+        records.push(response.to_named_json(&names));
+    }
+
+    write_output(common.format, &records)
+}
+
+fn summary(args : Vec<String>) -> Result<()> {
+    let mut common = Common::new();
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Count the observations, active choices and deferrals of every subject");
+        common.register(&mut ap);
+        parse_args(ap, args);
+    }
+
+    let mut records = Vec::new();
+    for subject in read_choices(&common)? {
+        let response = experiment_stats::run(experiment_stats::Request{subject: Packed(subject)}).map_err(
+            |e| -> String { match e {} }
+        )?;
+        records.push(response.to_json());
+    }
+
+    write_output(common.format, &records)
+}
+
+fn parse_menus(s : &str, copycat : &[Subject]) -> Result<Vec<simulation::MenuGenerator>> {
+    use simulation::MenuGenerator;
+
+    match s {
+        "exhaustive" => Ok(vec![MenuGenerator::Exhaustive]),
+        "binary" => Ok(vec![MenuGenerator::Binary]),
+        "copycat" => Ok(copycat.iter().cloned().map(|s| MenuGenerator::Copycat(Packed(s))).collect()),
+        _ if s.starts_with("sample:") => s["sample:".len()..].parse::<u32>().map(
+            |n| vec![MenuGenerator::SampleWithReplacement(n)]
+        ).map_err(|_| format!("bad number of menus: {}", s)),
+        _ => Err(format!("unknown menu generator: {}", s)),
+    }
+}
+
+fn simulate(args : Vec<String>) -> Result<()> {
+    let mut common = Common::new();
+    let mut alternatives = String::new();
+    let mut menus = String::from("exhaustive");
+    let mut defaults = false;
+    let mut instance = String::new();
+    let mut forced_choice = false;
+    let mut multiple_choice = false;
+    let mut preserve_deferrals = false;
+    let mut subjects = 1u32;
+    let mut name = String::from("random");
+    let mut seed = 0u64;
+
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Simulate choice data; the input is read only for copycat menus");
+        common.register(&mut ap);
+        ap.refer(&mut alternatives)
+            .add_option(&["--alternatives"], Store, "Comma-separated names of the alternatives");
+        ap.refer(&mut menus)
+            .add_option(&["--menus"], Store, "exhaustive (default), binary, sample:N or copycat");
+        ap.refer(&mut defaults)
+            .add_option(&["--defaults"], StoreTrue, "Give every menu a random default");
+        ap.refer(&mut instance)
+            .add_option(&["--instance"], Store, "Choose by this instance code (default: uniformly at random)");
+        ap.refer(&mut forced_choice)
+            .add_option(&["--forced-choice"], StoreTrue, "Uniform choices never defer");
+        ap.refer(&mut multiple_choice)
+            .add_option(&["--multiple-choice"], StoreTrue, "Uniform choices may pick several alternatives");
+        ap.refer(&mut preserve_deferrals)
+            .add_option(&["--preserve-deferrals"], StoreTrue, "Keep the deferrals of copycat subjects");
+        ap.refer(&mut subjects)
+            .add_option(&["--subjects"], Store, "Number of subjects (per copycat subject)");
+        ap.refer(&mut name)
+            .add_option(&["--name"], Store, "Name (prefix) of the simulated subjects");
+        ap.refer(&mut seed)
+            .add_option(&["--seed"], Store, "Random seed");
+        parse_args(ap, args);
+    }
+
+    let copycat = if menus == "copycat" { read_choices(&common)? } else { Vec::new() };
+    let generators = parse_menus(&menus, &copycat)?;
+    let names : Vec<String> = if generators.iter().any(|g| matches!(g, simulation::MenuGenerator::Copycat(_))) {
+        copycat.first().map_or(Vec::new(), |s| s.alternatives.clone())
+    } else {
+        alternatives.split(',').map(|s| String::from(s.trim())).filter(|s| !s.is_empty()).collect()
+    };
+
+    if names.is_empty() {
+        return Err(String::from("no alternatives given"));
+    }
+
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let mut records = Vec::new();
+    for generator in generators {
+        for i in 0..subjects {
+            let gen_choices = if instance.is_empty() {
+                simulation::GenChoices::Uniform{forced_choice, multiple_choice}
+            } else {
+                let bytes = BASE64_STANDARD.decode(&instance).map_err(|e| e.to_string())?;
+                simulation::GenChoices::Instance(codec::decode_from_memory(&bytes).map_err(
+                    |e| format!("could not decode instance: {:?}", e)
+                )?)
+            };
+
+            let request = simulation::Request {
+                name: format!("{}{}", name, i + 1),
+                alternatives: names.clone(),
+                gen_menus: simulation::GenMenus{generator: clone_generator(&generator), defaults},
+                gen_choices,
+                preserve_deferrals,
+            };
+
+            let Packed(subject) = simulation::run(&mut rng, request).map_err(|e| -> String { match e {} })?.subject;
+            for cr in &subject.choices {
+                records.push(json::object(vec![
+                    ("subject", subject.name.to_json()),
+                    ("menu", cr.menu.to_cell(&subject.alternatives).to_json()),
+                    ("default", cr.default.map_or(String::new(), |alt| subject.alternatives[alt.index() as usize].clone()).to_json()),
+                    ("choice", cr.choice.to_cell(&subject.alternatives).to_json()),
+                ]));
+            }
+        }
+    }
+
+    write_output(common.format, &records)
+}
+
+fn clone_generator(generator : &simulation::MenuGenerator) -> simulation::MenuGenerator {
+    use simulation::MenuGenerator::*;
+    match *generator {
+        Exhaustive => Exhaustive,
+        SampleWithReplacement(n) => SampleWithReplacement(n),
+        Copycat(Packed(ref subject)) => Copycat(Packed(subject.clone())),
+        Binary => Binary,
+    }
+}
+
+fn budgetary(args : Vec<String>) -> Result<()> {
+    let mut common = Common::new();
+    let mut wide = false;
+    let mut cycle_limit : Option<u32> = None;
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Test the consistency of budgetary data");
+        common.register(&mut ap);
+        ap.refer(&mut wide)
+            .add_option(&["--wide"], StoreTrue, "The input is in the wide format");
+        ap.refer(&mut cycle_limit)
+            .add_option(&["--cycle-limit"], StoreOption,
+                "Also count the HARP and quasilinearity violations by cycle length, enumerating at most this many cycles");
+        parse_args(ap, args);
+    }
+
+    let input = common.open()?;
+    let data = if wide { reader::read_wide(input) } else { reader::read_long(input) }.map_err(|e| e.to_string())?;
+
+    let mut records = Vec::new();
+    for subject in data.subjects {
+        let request = budgetary::consistency::Request{subject, cycle_limit};
+        let response = budgetary::consistency::run(DummyLogger, request, &CancelFlag::new()).map_err(|e| e.to_string())?;
+        records.push(response.to_json());
+    }
+
+    write_output(common.format, &records)
+}
+
+fn main() {
+    let mut command = String::new();
+    let mut args : Vec<String> = Vec::new();
+
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Prest batch interface");
+        ap.refer(&mut command).required()
+            .add_argument("command", Store,
+                "estimate, consistency, tuple-intrans, axioms, stochastic-axioms, integrity, summary, simulate or budgetary");
+        ap.refer(&mut args)
+            .add_argument("arguments", List, "Arguments of the command");
+        ap.stop_on_first_argument(true);
+        ap.parse_args_or_exit();
+    }
+
+    args.insert(0, format!("prest {}", command));
+    let result = command.parse::<Command>().and_then(|command| match command {
+        Command::Estimate => estimate(args),
+        Command::Consistency => consistency(args),
+        Command::TupleIntrans => tuple_intrans(args),
+        Command::Axioms => axioms(args),
+        Command::StochasticAxioms => stochastic_axioms(args),
+        Command::Integrity => integrity(args),
+        Command::Summary => summary(args),
+        Command::Simulate => simulate(args),
+        Command::Budgetary => budgetary(args),
+    });
+
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,296 @@
+use std::fmt;
+use std::result;
+use std::io::{Read,Write};
+use budgetary::{self,Subject};
+use matrix::{Matrix,dot_product};
+use codec::{self,Encode,Decode};
+use json::{self,Json,ToJson,FromJson};
+
+/* Afriat's theorem: the data satisfy GARP iff there are
+ * utility levels U_i and marginal utilities L_i > 0 such that
+ *
+ *   U_j <= U_i + L_i p^i (x^j - x^i)  for all i, j
+ *
+ * and then u(x) = min_i U_i + L_i p^i (x - x^i)
+ * is a concave, monotone utility function that rationalises the data.
+ *
+ * We solve the inequalities constructively (Fostel, Scarf and Todd, 2004),
+ * starting from the most preferred observations. At each step, we take
+ * a group of observations that none of the remaining observations
+ * is revealed preferred to, give them the highest utility permitted
+ * by the observations processed so far, and then the smallest marginal utility
+ * that keeps them from undercutting those observations.
+ */
+
+#[derive(Debug, Clone)]
+pub enum Error {
+    GarpViolated,
+    BundleSize{expected : usize, got : usize},
+}
+
+impl Encode for Error {
+    fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+        match *self {
+            Error::GarpViolated => 0u8.encode(f),
+            Error::BundleSize{expected, got} => (1u8, expected, got).encode(f),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::GarpViolated => write!(f, "the data violate GARP so there is no rationalising utility"),
+            Error::BundleSize{expected, got} => write!(
+                f, "bundles to evaluate have {} goods but the subject has {} goods", got, expected
+            ),
+        }
+    }
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+#[derive(Debug)]
+pub struct Request {
+    subject : Subject,
+    bundles : Matrix<f32>,  // rows ~ bundles to evaluate, columns ~ goods
+}
+
+impl Decode for Request {
+    fn decode<R : Read>(f : &mut R) -> codec::Result<Request> {
+        Ok(Request {
+            subject: Decode::decode(f)?,
+            bundles: Decode::decode(f)?,
+        })
+    }
+}
+
+impl FromJson for Request {
+    fn from_json(js : &Json) -> json::Result<Request> {
+        Ok(Request {
+            subject: json::field(js, "subject")?,
+            bundles: json::field(js, "bundles")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub name : String,
+    pub utilities : Vec<f64>,  // U_i, for each observation
+    pub marginal_utilities : Vec<f64>,  // L_i, for each observation
+    pub evaluated : Vec<f64>,  // u(x), for each requested bundle
+}
+
+impl Encode for Response {
+    fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+        (&self.name, &self.utilities, &self.marginal_utilities, &self.evaluated).encode(f)
+    }
+}
+
+impl ToJson for Response {
+    fn to_json(&self) -> Json {
+        json::object(vec![
+            ("name", self.name.to_json()),
+            ("utilities", self.utilities.to_json()),
+            ("marginal_utilities", self.marginal_utilities.to_json()),
+            ("evaluated", self.evaluated.to_json()),
+        ])
+    }
+}
+
+/// The piecewise-linear utility rationalising the data.
+#[derive(Debug, Clone)]
+pub struct AfriatUtility {
+    pub prices : Matrix<f32>,
+    pub expenditures : Vec<f64>,  // p^i x^i
+    pub utilities : Vec<f64>,
+    pub marginal_utilities : Vec<f64>,
+}
+
+impl AfriatUtility {
+    /// Solve Afriat's inequalities, failing if GARP does not hold.
+    pub fn new(subject : &Subject) -> Result<AfriatUtility> {
+        let px = budgetary::px_matrix(subject);
+        let n = px.nrows;
+
+        // a(i,j) = p^i (x^j - x^i)
+        let a = |i : usize, j : usize| px.get(i, j) as f64 - px.get(i, i) as f64;
+
+        let revealed = Matrix::from_indices(n, n, |i, j| a(i, j) <= 0.0);
+        let closure = budgetary::transitive_closure(&revealed);
+        let same_group = |i : usize, j : usize| closure.get(i, j) && closure.get(j, i);
+
+        // GARP: no strict revelation within a strongly connected component
+        if (0..n).any(|i| (0..n).any(|j| same_group(i, j) && a(i, j) < 0.0)) {
+            return Err(Error::GarpViolated);
+        }
+
+        let mut utilities = vec![0.0; n];
+        let mut marginal_utilities = vec![0.0; n];
+        let mut processed = vec![false; n];
+        let mut processed_list : Vec<usize> = Vec::new();
+
+        while processed_list.len() < n {
+            // a remaining group that no other remaining observation is revealed preferred to
+            let top = (0..n).find(|&i| !processed[i] && (0..n).all(
+                |k| processed[k] || same_group(k, i) || !closure.get(k, i)
+            )).unwrap();  // the remaining part of the closure is acyclic on groups
+            let group : Vec<usize> = (0..n).filter(|&i| !processed[i] && same_group(top, i)).collect();
+
+            let utility = group.iter().flat_map(|&i|
+                processed_list.iter().map(
+                    move |&j| (i, j)
+                )
+            ).map(
+                |(i, j)| utilities[j] + marginal_utilities[j] * a(j, i)
+            ).fold(f64::INFINITY, f64::min);
+            let utility = if utility.is_finite() { utility } else { 0.0 };
+
+            for &i in &group {
+                // nothing in the group is revealed preferred to the processed observations,
+                // so a(i, j) > 0 here
+                let lower_bound = processed_list.iter().map(
+                    |&j| (utilities[j] - utility) / a(i, j)
+                ).fold(0.0, f64::max);
+
+                utilities[i] = utility;
+                marginal_utilities[i] = if lower_bound > 0.0 { lower_bound } else { 1.0 };
+            }
+
+            for &i in &group {
+                processed[i] = true;
+                processed_list.push(i);
+            }
+        }
+
+        Ok(AfriatUtility {
+            prices: subject.prices.clone(),
+            expenditures: (0..n).map(|i| px.get(i, i) as f64).collect(),
+            utilities,
+            marginal_utilities,
+        })
+    }
+
+    pub fn evaluate(&self, bundle : &[f32]) -> f64 {
+        (0..self.utilities.len()).map(|i|
+            self.utilities[i] + self.marginal_utilities[i] * (
+                dot_product(self.prices.row(i), bundle) as f64 - self.expenditures[i]
+            )
+        ).fold(f64::INFINITY, f64::min)
+    }
+}
+
+pub fn run(request : Request) -> Result<Response> {
+    let n_goods = request.subject.prices.ncols;
+    if request.bundles.nrows > 0 && request.bundles.ncols != n_goods {
+        return Err(Error::BundleSize{expected: n_goods, got: request.bundles.ncols});
+    }
+
+    let utility = AfriatUtility::new(&request.subject)?;
+    let evaluated = request.bundles.iter_rows().map(|x| utility.evaluate(x)).collect();
+
+    Ok(Response {
+        name: request.subject.name,
+        utilities: utility.utilities,
+        marginal_utilities: utility.marginal_utilities,
+        evaluated,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{Rng,SeedableRng};
+    use rand::rngs::SmallRng;
+    use budgetary::efficiency;
+
+    fn subject(n_obs : usize, prices : &[f32], amounts : &[f32]) -> Subject {
+        Subject {
+            name: String::from("subject"),
+            prices: Matrix::from_slice(n_obs, prices.len() / n_obs, prices),
+            amounts: Matrix::from_slice(n_obs, amounts.len() / n_obs, amounts),
+        }
+    }
+
+    fn check_afriat(subject : &Subject, utility : &AfriatUtility) {
+        let n = subject.prices.nrows;
+        for i in 0..n {
+            assert!(utility.marginal_utilities[i] > 0.0);
+
+            // the utility function goes through the observed levels
+            let u = utility.evaluate(subject.amounts.row(i));
+            assert!((u - utility.utilities[i]).abs() < 1e-6, "{} != {}", u, utility.utilities[i]);
+        }
+    }
+
+    #[test]
+    fn basic() {
+        // x^0 is revealed preferred to x^1, which is revealed preferred to x^2
+        let subject = subject(3, &[
+            1.0, 1.0,
+            1.0, 2.0,
+            2.0, 1.0,
+        ], &[
+            2.0, 2.0,
+            3.0, 0.0,
+            0.0, 1.0,
+        ]);
+
+        let utility = AfriatUtility::new(&subject).unwrap();
+        check_afriat(&subject, &utility);
+        assert!(utility.utilities[0] > utility.utilities[1]);
+        assert!(utility.utilities[1] > utility.utilities[2]);
+
+        // monotone in every good
+        assert!(utility.evaluate(&[2.0, 3.0]) > utility.evaluate(&[2.0, 2.0]));
+        assert!(utility.evaluate(&[3.0, 2.0]) > utility.evaluate(&[2.0, 2.0]));
+
+        let response = run(Request {
+            subject,
+            bundles: Matrix::from_slice(2, 2, &[2.0, 2.0, 0.0, 0.0]),
+        }).unwrap();
+        assert_eq!(response.evaluated[0], response.utilities[0]);
+        assert!(response.evaluated[1] < response.utilities[2]);
+    }
+
+    #[test]
+    fn violation() {
+        let subject = subject(2, &[1.0, 1.0, 3.0, 1.0], &[1.0, 3.0, 2.0, 1.0]);
+        assert!(matches!(AfriatUtility::new(&subject), Err(Error::GarpViolated)));
+    }
+
+    #[test]
+    fn random_consistent() {
+        // Cobb-Douglas demand is rationalisable, ties and corners included
+        let mut rng = SmallRng::from_seed([3; 32]);
+        for _ in 0..200 {
+            let n_obs = rng.gen_range(1..8);
+            let alpha : f32 = rng.gen_range(0..5) as f32 / 4.0;
+            let mut prices = Vec::new();
+            let mut amounts = Vec::new();
+            for _ in 0..n_obs {
+                let (p1, p2, m) = (rng.gen_range(1..4) as f32, rng.gen_range(1..4) as f32, rng.gen_range(1..4) as f32);
+                prices.extend_from_slice(&[p1, p2]);
+                amounts.extend_from_slice(&[alpha * m / p1, (1.0 - alpha) * m / p2]);
+            }
+
+            let subject = subject(n_obs, &prices, &amounts);
+            let px = budgetary::px_matrix(&subject);
+            assert!(efficiency::garp_holds(&px, |_| 1.0));
+
+            let utility = AfriatUtility::new(&subject).unwrap();
+            check_afriat(&subject, &utility);
+
+            // Afriat's inequalities
+            for i in 0..n_obs {
+                for j in 0..n_obs {
+                    let a = px.get(i, j) as f64 - px.get(i, i) as f64;
+                    assert!(
+                        utility.utilities[j] <= utility.utilities[i] + utility.marginal_utilities[i] * a + 1e-6
+                    );
+                }
+            }
+        }
+    }
+}
@@ -1,33 +1,67 @@
 use std::result;
 use std::fmt;
-use std::io::Write;
+use std::io::{Read,Write};
 use std::iter::FromIterator;
-use budgetary::Subject;
+use budgetary::{self,Subject,efficiency,cyclical};
+use budgetary::cyclical::CyclicalTest;
 use rpc::Log;
-use matrix::{Matrix,dot_product};
-use codec::{self,Encode};
+use rpc_common::CancelFlag;
+use matrix::Matrix;
+use codec::{self,Encode,Decode};
 use std::collections::{HashSet,BTreeMap};
 use set_cover;
+use json::{self,Json,ToJson,FromJson};
 
 #[derive(Debug, Clone)]
 pub enum Error {
+    Cancelled,
 }
 
 impl Encode for Error {
-    fn encode<W : Write>(&self, _f : &mut W) -> codec::Result<()> {
-        match *self { }
+    fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+        match *self {
+            Error::Cancelled => 0u8.encode(f),
+        }
     }
 }
 
 impl fmt::Display for Error {
-    fn fmt(&self, _f : &mut fmt::Formatter) -> fmt::Result {
-        match *self { }
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Cancelled => write!(f, "cancelled"),
+        }
     }
 }
 
 pub type Result<T> = result::Result<T, Error>;
 
-pub type Request = Subject;
+#[derive(Debug)]
+pub struct Request {
+    pub subject : Subject,
+
+    /// Count the HARP and quasilinearity violations by cycle length,
+    /// enumerating at most this many cycles per test.
+    /// Without a limit, only whether the tests pass is reported.
+    pub cycle_limit : Option<u32>,
+}
+
+impl Decode for Request {
+    fn decode<R : Read>(f : &mut R) -> codec::Result<Request> {
+        Ok(Request {
+            subject: Decode::decode(f)?,
+            cycle_limit: Decode::decode(f)?,
+        })
+    }
+}
+
+impl FromJson for Request {
+    fn from_json(js : &Json) -> json::Result<Request> {
+        Ok(Request {
+            subject: json::field(js, "subject")?,
+            cycle_limit: json::field(js, "cycle_limit")?,
+        })
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Violations {
@@ -41,15 +75,25 @@ impl Encode for Violations {
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct BoundEstimate<T> {
-    lower : T,
-    upper : T,
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HoutmanMaks {
+    count : usize,
+    removed : Vec<usize>,  // observations, ascending
 }
 
-impl<T : Encode> Encode for BoundEstimate<T> {
+impl Encode for HoutmanMaks {
     fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
-        (&self.lower, &self.upper).encode(f)
+        (&self.count, &self.removed).encode(f)
+    }
+}
+
+// observations numbered from 1, like in the GUI
+impl ToJson for HoutmanMaks {
+    fn to_json(&self) -> Json {
+        json::object(vec![
+            ("count", self.count.to_json()),
+            ("removed", self.removed.iter().map(|&i| i + 1).collect::<Vec<usize>>().to_json()),
+        ])
     }
 }
 
@@ -59,10 +103,17 @@ pub struct Response {
     violations : Vec<(usize, Violations)>, // (cycle_length, counts)
     warp_strict : usize,
     warp_nonstrict : usize,
-    hm_garp : BoundEstimate<usize>,
-    hm_sarp : BoundEstimate<usize>,
-    hm_warp_strict : BoundEstimate<usize>,
-    hm_warp_nonstrict : BoundEstimate<usize>,
+    hm_garp : HoutmanMaks,
+    hm_sarp : HoutmanMaks,
+    hm_warp_strict : HoutmanMaks,
+    hm_warp_nonstrict : HoutmanMaks,
+    ccei : f64,
+    varian : Vec<f64>,  // for each observation
+    mpi_mean : f64,
+    mpi_median : f64,
+    mci : f64,
+    harp : CyclicalTest,
+    quasilinear : CyclicalTest,
 }
 
 impl Encode for Response {
@@ -71,13 +122,42 @@ impl Encode for Response {
          &self.violations,
          &self.warp_strict,
          &self.warp_nonstrict,
-         &self.hm_garp, &self.hm_sarp,
-         &self.hm_warp_strict,
-         &self.hm_warp_nonstrict,
+         (&self.hm_garp, &self.hm_sarp, &self.hm_warp_strict, &self.hm_warp_nonstrict),
+         (self.ccei, &self.varian),
+         (self.mpi_mean, self.mpi_median, self.mci),
+         (&self.harp, &self.quasilinear),
         ).encode(f)
     }
 }
 
+impl ToJson for Response {
+    fn to_json(&self) -> Json {
+        json::object(vec![
+            ("subject", self.name.to_json()),
+            ("violations", Json::Array(self.violations.iter().map(
+                |&(cycle_length, ref counts)| json::object(vec![
+                    ("cycle_length", cycle_length.to_json()),
+                    ("garp", counts.garp.to_json()),
+                    ("sarp", counts.sarp.to_json()),
+                ])
+            ).collect())),
+            ("warp_strict", self.warp_strict.to_json()),
+            ("warp_nonstrict", self.warp_nonstrict.to_json()),
+            ("hm_garp", self.hm_garp.to_json()),
+            ("hm_sarp", self.hm_sarp.to_json()),
+            ("hm_warp_strict", self.hm_warp_strict.to_json()),
+            ("hm_warp_nonstrict", self.hm_warp_nonstrict.to_json()),
+            ("ccei", self.ccei.to_json()),
+            ("varian", self.varian.to_json()),
+            ("mpi_mean", self.mpi_mean.to_json()),
+            ("mpi_median", self.mpi_median.to_json()),
+            ("mci", self.mci.to_json()),
+            ("harp", self.harp.to_json()),
+            ("quasilinear", self.quasilinear.to_json()),
+        ])
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 struct Vertex {
     pub number : usize
@@ -208,12 +288,16 @@ fn find_cycles_from(
     result
 }
 
-fn find_cycles(edges : &Matrix<bool>) -> HashSet<Cycle> {
+fn find_cycles(edges : &Matrix<bool>, cancel : &CancelFlag) -> Result<HashSet<Cycle>> {
     let mut untouched = HashSet::from_iter(Vertex::all(edges.nrows));
     let mut result = HashSet::new();
 
     // pop a vertex
     while let Some(&root) = untouched.iter().next() {
+        if cancel.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+
         // create history for this run
         let mut history = Vec::new();
 
@@ -223,81 +307,114 @@ fn find_cycles(edges : &Matrix<bool>) -> HashSet<Cycle> {
         );
     }
 
-    result
+    Ok(result)
 }
 
-fn find_intersect(components : &[HashSet<Vertex>]) -> Option<(usize, usize)> {
-    for i in 0..components.len() {
-        for j in i+1..components.len() {
-            if !components[i].is_disjoint(&components[j]) {
-                return Some((i, j));
+/// The smallest set of observations whose removal breaks all the given cycles,
+/// i.e. a minimum feedback vertex set of the cycles.
+fn houtman_maks(vertex_count : usize, cycles : &[Cycle]) -> HoutmanMaks {
+    let cycles_per_vertex : Vec<HashSet<usize>> = {
+        let mut groups = vec![HashSet::new(); vertex_count];
+        for (cycle_nr, cycle) in cycles.iter().enumerate() {
+            for vertex in &cycle.vertices {
+                groups[vertex.number].insert(cycle_nr);
             }
         }
-    }
+        groups
+    };
 
-    None
+    let mut removed = Vec::from_iter(set_cover::exact(&cycles_per_vertex));
+    removed.sort();
+
+    HoutmanMaks{
+        count: removed.len(),
+        removed,
+    }
 }
 
-fn connected_components(cycles : &[Cycle]) -> Vec<HashSet<Vertex>> {
-    let mut components : Vec<HashSet<Vertex>> = cycles.iter().map(
-        |c| HashSet::from_iter(c.vertices.iter().cloned())
-    ).collect();
+/// Echenique, Lee and Shum: the fraction of the expenditure along the cycle
+/// that an arbitrageur could extract from the subject.
+fn money_pump(px : &Matrix<f32>, cycle : &Cycle) -> f64 {
+    let savings : f64 = cycle.edges().map(
+        |(i, j)| (px.get(i.number, i.number) - px.get(i.number, j.number)) as f64
+    ).sum();
+
+    let expenditure : f64 = cycle.vertices.iter().map(
+        |v| px.get(v.number, v.number) as f64
+    ).sum();
+
+    if expenditure > 0.0 {
+        savings / expenditure
+    } else {
+        0.0
+    }
+}
 
-    while let Some((i,j)) = find_intersect(&components) {
-        let cj = components.swap_remove(j);
-        components[i].extend(cj);
+/// Mean and median of the money pump indices of the GARP cycles.
+fn mpi_stats(px : &Matrix<f32>, garp_cycles : &[Cycle]) -> (f64, f64) {
+    if garp_cycles.is_empty() {
+        return (0.0, 0.0);
     }
 
-    components
+    let mut mpis : Vec<f64> = garp_cycles.iter().map(|c| money_pump(px, c)).collect();
+    mpis.sort_by(|x, y| x.partial_cmp(y).unwrap());
+
+    let n = mpis.len();
+    let mean = mpis.iter().sum::<f64>() / n as f64;
+    let median = if n % 2 == 1 {
+        mpis[n / 2]
+    } else {
+        (mpis[n/2 - 1] + mpis[n/2]) / 2.0
+    };
+
+    (mean, median)
 }
 
-fn hm_bounds(vertex_count : usize, cycles : &[Cycle]) -> BoundEstimate<usize> {
-    let cycles_per_vertex : Vec<HashSet<usize>> = {
-        let mut groups = vec![HashSet::new(); vertex_count];
-        for (cycle_nr, cycle) in cycles.iter().enumerate() {
-            for vertex in &cycle.vertices {
-                groups[vertex.number].insert(cycle_nr);
-            }
+/// Dean and Martin: the cheapest way to break all GARP cycles
+/// by removing revealed preference relations, where removing x^i R x^j
+/// costs p^i x^i - p^i x^j, as a fraction of the total expenditure.
+fn minimum_cost_index(px : &Matrix<f32>, garp_cycles : &[Cycle]) -> f64 {
+    let mut cycles_per_edge : BTreeMap<(Vertex, Vertex), HashSet<usize>> = BTreeMap::new();
+    for (cycle_nr, cycle) in garp_cycles.iter().enumerate() {
+        for edge in cycle.edges() {
+            cycles_per_edge.entry(edge).or_default().insert(cycle_nr);
         }
-        groups
-    };
+    }
 
-    BoundEstimate{
-        lower: connected_components(cycles).len(),
-        upper: set_cover::greedy(&cycles_per_vertex).len(),
+    let costs : Vec<f64> = cycles_per_edge.keys().map(
+        |&(i, j)| (px.get(i.number, i.number) - px.get(i.number, j.number)) as f64
+    ).collect();
+    let sets : Vec<HashSet<usize>> = cycles_per_edge.into_values().collect();
+    let (cost, _edges) = set_cover::weighted_exact(&sets, &costs);
+
+    let expenditure : f64 = (0..px.nrows).map(|i| px.get(i, i) as f64).sum();
+    if expenditure > 0.0 {
+        cost / expenditure
+    } else {
+        0.0
     }
 }
 
-pub fn run<L : Log>(mut log : L, subject : Subject) -> Result<Response> {
+pub fn run<L : Log>(mut log : L, request : Request, cancel : &CancelFlag) -> Result<Response> {
+    let subject = request.subject;
     assert_eq!(subject.prices.nrows, subject.amounts.nrows);
     assert_eq!(subject.prices.ncols, subject.amounts.ncols);
 
     let n_obs = subject.prices.nrows;
 
-    // (row i, column j) contains p^i x^j
-    let px = {
-        let mut px = Vec::new();
-        for p in subject.prices.iter_rows() {
-            px.extend(
-                subject.amounts.iter_rows().map(
-                    |x| dot_product(p, x)
-                )
-            );
-        }
-        Matrix::new(n_obs, n_obs, px)
-    };
+    let px = budgetary::px_matrix(&subject);
 
     let edges_nonstrict = Matrix::from_indices(n_obs, n_obs,
-        |i, j| px.get(i, i) >= px.get(i, j)
+        |i, j| budgetary::revealed_preferred(&px, 1.0, i, j)
     );
     let edges_strict = Matrix::from_indices(n_obs, n_obs,
-        |i, j| px.get(i, i) > px.get(i, j)
+        |i, j| budgetary::strictly_revealed_preferred(&px, 1.0, i, j)
     );
     let edges_neq = Matrix::from_indices(n_obs, n_obs,
         |i, j| subject.amounts.row(i) != subject.amounts.row(j)
     );
 
-    let cycles = find_cycles(&edges_nonstrict);
+    let cycles = find_cycles(&edges_nonstrict, cancel)?;
     log.debug(format!("found {} cycles", cycles.len()));
 
     let mut warp_strict = 0;
@@ -310,6 +427,10 @@ pub fn run<L : Log>(mut log : L, subject : Subject) -> Result<Response> {
     let mut warp_nonstrict_cycles = Vec::new();
 
     for cycle in cycles.iter() {
+        if cancel.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+
         /* this counts multiplicities
         let sarp_count = cycle.edges().filter(
             |(i, j)| edges_neq.get(i.number, j.number)
@@ -361,10 +482,19 @@ pub fn run<L : Log>(mut log : L, subject : Subject) -> Result<Response> {
         }
     }
 
-    let hm_garp = hm_bounds(n_obs, &garp_cycles);
-    let hm_sarp = hm_bounds(n_obs, &sarp_cycles);
-    let hm_warp_strict = hm_bounds(n_obs, &warp_strict_cycles);
-    let hm_warp_nonstrict = hm_bounds(n_obs, &warp_nonstrict_cycles);
+    let hm_garp = houtman_maks(n_obs, &garp_cycles);
+    let hm_sarp = houtman_maks(n_obs, &sarp_cycles);
+    let hm_warp_strict = houtman_maks(n_obs, &warp_strict_cycles);
+    let hm_warp_nonstrict = houtman_maks(n_obs, &warp_nonstrict_cycles);
+
+    let (mpi_mean, mpi_median) = mpi_stats(&px, &garp_cycles);
+    let mci = minimum_cost_index(&px, &garp_cycles);
+
+    let harp = cyclical::harp(&px, request.cycle_limit, cancel)?;
+    let quasilinear = cyclical::quasilinear(&px, request.cycle_limit, cancel)?;
+
+    let ccei = efficiency::ccei(&px);
+    let varian = efficiency::varian(&px);
 
     Ok(Response{
         name: subject.name,
@@ -375,6 +505,13 @@ pub fn run<L : Log>(mut log : L, subject : Subject) -> Result<Response> {
         hm_sarp,
         hm_warp_strict,
         hm_warp_nonstrict,
+        ccei,
+        varian,
+        mpi_mean,
+        mpi_median,
+        mci,
+        harp,
+        quasilinear,
     })
 }
 
@@ -401,7 +538,7 @@ mod test {
             ]),
         };
 
-        let resp = super::run(DummyLogger, subject).unwrap();
+        let resp = super::run(DummyLogger, Request{subject, cycle_limit: Some(1000)}, &CancelFlag::new()).unwrap();
 
         assert_eq!(resp.name, "subj01");
         assert_eq!(resp.warp_strict, 1);
@@ -409,14 +546,16 @@ mod test {
         assert_eq!(resp.violations, &[
             (2, Violations{ garp: 1, sarp: 1 }),
         ]);
-        assert_eq!(resp.hm_garp.lower, 1);
-        assert_eq!(resp.hm_garp.upper, 1);
-        assert_eq!(resp.hm_sarp.lower, 1);
-        assert_eq!(resp.hm_sarp.upper, 1);
-        assert_eq!(resp.hm_warp_strict.lower, 1);
-        assert_eq!(resp.hm_warp_strict.upper, 1);
-        assert_eq!(resp.hm_warp_nonstrict.lower, 1);
-        assert_eq!(resp.hm_warp_nonstrict.upper, 1);
+        assert_eq!(resp.hm_garp.count, 1);
+        assert_eq!(resp.hm_sarp.count, 1);
+        assert_eq!(resp.hm_warp_strict.count, 1);
+        assert_eq!(resp.hm_warp_nonstrict.count, 1);
+        assert_eq!(resp.mpi_mean, 3.0 / 22.0);
+        assert_eq!(resp.mpi_median, 3.0 / 22.0);
+        // x^0 is exactly as expensive as x^3 so its preference is free to remove
+        assert_eq!(resp.mci, 0.0);
+        assert_eq!(resp.harp, CyclicalTest{pass: false, violations: vec![(2, 4), (3, 6), (4, 5)], truncated: false});
+        assert_eq!(resp.quasilinear, CyclicalTest{pass: false, violations: vec![(2, 3), (3, 4), (4, 3)], truncated: false});
     }
 
     #[test]
@@ -436,20 +575,82 @@ mod test {
             ]),
         };
 
-        let resp = super::run(DummyLogger, subject).unwrap();
+        let resp = super::run(DummyLogger, Request{subject, cycle_limit: Some(1000)}, &CancelFlag::new()).unwrap();
 
         assert_eq!(resp.name, "subj01");
         assert_eq!(resp.violations, &[
         ]);
         assert_eq!(resp.warp_strict, 0);
         assert_eq!(resp.warp_nonstrict, 0);
-        assert_eq!(resp.hm_garp.lower, 0);
-        assert_eq!(resp.hm_garp.upper, 0);
-        assert_eq!(resp.hm_sarp.lower, 0);
-        assert_eq!(resp.hm_sarp.upper, 0);
-        assert_eq!(resp.hm_warp_strict.lower, 0);
-        assert_eq!(resp.hm_warp_strict.upper, 0);
-        assert_eq!(resp.hm_warp_nonstrict.lower, 0);
-        assert_eq!(resp.hm_warp_nonstrict.upper, 0);
+        assert_eq!(resp.hm_garp.count, 0);
+        assert_eq!(resp.hm_sarp.count, 0);
+        assert_eq!(resp.hm_warp_strict.count, 0);
+        assert_eq!(resp.hm_warp_nonstrict.count, 0);
+        assert_eq!(resp.mpi_mean, 0.0);
+        assert_eq!(resp.mpi_median, 0.0);
+        assert_eq!(resp.mci, 0.0);
+        assert_eq!(resp.harp, CyclicalTest{pass: true, violations: vec![], truncated: false});
+        assert_eq!(resp.quasilinear, CyclicalTest{pass: true, violations: vec![], truncated: false});
+    }
+
+    #[test]
+    fn money_pump() {
+        let subject = Subject{
+            name: String::from("subj01"),
+            prices: Matrix::from_slice(3, 2, &[
+                4.0, 1.0,
+                2.0, 2.0,
+                3.0, 4.0,
+            ]),
+            amounts: Matrix::from_slice(3, 2, &[
+                3.0, 0.0,
+                2.0, 3.0,
+                1.0, 2.0,
+            ]),
+        };
+
+        let resp = super::run(DummyLogger, Request{subject, cycle_limit: Some(1000)}, &CancelFlag::new()).unwrap();
+
+        // cycles 0-1, 0-2 and 0-1-2
+        assert_eq!(resp.violations, &[
+            (2, Violations{ garp: 2, sarp: 2 }),
+            (3, Violations{ garp: 1, sarp: 1 }),
+        ]);
+
+        let mpis = [7.0 / 33.0, 5.0 / 22.0, 8.0 / 23.0];
+        assert!((resp.mpi_mean - mpis.iter().sum::<f64>() / 3.0).abs() < 1e-12);
+        assert!((resp.mpi_median - mpis[1]).abs() < 1e-12);
+
+        // every cycle goes through observation 0
+        assert_eq!(resp.hm_garp, HoutmanMaks{count: 1, removed: vec![0]});
+        assert_eq!(resp.hm_warp_nonstrict, HoutmanMaks{count: 1, removed: vec![0]});
+
+        // remove x^0 R x^1 (cost 1) and x^2 R x^0 (cost 2), out of the total expenditure 33
+        assert!((resp.mci - 3.0 / 33.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn many_observations() {
+        // Cobb-Douglas demand with budget shares 1/2, 1/3, 1/6
+        // under varying prices and incomes: homothetic but not quasilinear
+        let n_obs = 30;
+        let prices = Matrix::from_indices(n_obs, 3, |i, g| 1.0 + ((i * (g + 3) + g) % 7) as f32);
+        let amounts = Matrix::from_indices(n_obs, 3, |i, g| {
+            let income = 20.0 * (1 + i % 4) as f32;
+            let share = [1.0 / 2.0, 1.0 / 3.0, 1.0 / 6.0][g];
+            share * income / prices.get(i, g)
+        });
+        let subject = Subject{name: String::from("subj01"), prices, amounts};
+
+        // without a cycle limit, HARP and quasilinearity are decided without enumerating cycles
+        let resp = super::run(DummyLogger, Request{subject: subject.clone(), cycle_limit: None}, &CancelFlag::new()).unwrap();
+        assert_eq!(resp.hm_garp.count, 0);
+        assert_eq!(resp.harp, CyclicalTest{pass: true, violations: vec![], truncated: false});
+        assert_eq!(resp.quasilinear, CyclicalTest{pass: false, violations: vec![], truncated: false});
+
+        // with a cycle limit, the enumeration gives up early rather than running for ages
+        let resp = super::run(DummyLogger, Request{subject, cycle_limit: Some(10)}, &CancelFlag::new()).unwrap();
+        assert!(!resp.quasilinear.pass);
+        assert!(resp.quasilinear.truncated);
     }
 }
@@ -0,0 +1,267 @@
+use std::io::Write;
+use std::collections::BTreeMap;
+use matrix::Matrix;
+use rpc_common::CancelFlag;
+use codec::{self,Encode};
+use json::{self,Json,ToJson};
+use budgetary::consistency::{Error,Result};
+
+/* Tests of the form "every cycle i_1 -> i_2 -> ... -> i_k -> i_1
+ * has a non-negative total weight", for a weight matrix w derived from px.
+ *
+ * - homothetic rationalisability (Varian's HARP):
+ *     the product of p^i x^j / p^i x^i along the cycle is at least 1,
+ *     i.e. w(i,j) = ln(p^i x^j / p^i x^i)
+ *
+ * - quasilinear rationalisability (cyclical monotonicity):
+ *     the sum of p^i (x^j - x^i) along the cycle is non-negative,
+ *     i.e. w(i,j) = p^i x^j - p^i x^i
+ *
+ * Pass/fail is decided by Floyd-Warshall, in cubic time. The violations
+ * are counted only on request, by enumerating simple cycles, pruning
+ * partial paths that cannot get negative anymore even if every remaining
+ * vertex contributed its most negative outgoing edge. The graph is complete
+ * so this is exponential in the number of observations even with few violations,
+ * hence the limit on the number of cycles, a limit on the number of partial paths
+ * explored per cycle allowed, and the cancellation checks.
+ */
+
+// tolerance for rounding errors; e.g. products of ratios that are exactly 1
+const EPSILON : f64 = 1e-9;
+
+// the enumeration gives up after exploring this many partial paths per cycle allowed
+const PATHS_PER_CYCLE : usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CyclicalTest {
+    pub pass : bool,
+    pub violations : Vec<(usize, usize)>,  // (cycle_length, count), empty if not counted
+    pub truncated : bool,  // the enumeration stopped early
+}
+
+impl Encode for CyclicalTest {
+    fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+        (self.pass, &self.violations, self.truncated).encode(f)
+    }
+}
+
+impl ToJson for CyclicalTest {
+    fn to_json(&self) -> Json {
+        json::object(vec![
+            ("pass", self.pass.to_json()),
+            ("violations", self.violations.to_json()),
+            ("truncated", self.truncated.to_json()),
+        ])
+    }
+}
+
+fn has_negative_cycle(weights : &Matrix<f64>) -> bool {
+    let n = weights.nrows;
+    let mut dist = weights.clone();
+
+    for k in 0..n {
+        for i in 0..n {
+            for j in 0..n {
+                let via_k = dist.get(i, k) + dist.get(k, j);
+                if via_k < dist.get(i, j) {
+                    *dist.get_mut_ref(i, j) = via_k;
+                }
+            }
+        }
+    }
+
+    (0..n).any(|i| dist.get(i, i) < -EPSILON)
+}
+
+struct Search<'a> {
+    weights : &'a Matrix<f64>,
+    min_out : Vec<f64>,  // the most negative outgoing edge, or zero
+    on_path : Vec<bool>,
+    counts : BTreeMap<usize, usize>,
+    found : usize,
+    limit : usize,
+    paths : usize,
+    max_paths : usize,
+    truncated : bool,
+    cancel : &'a CancelFlag,
+}
+
+impl<'a> Search<'a> {
+    // extend the path root -> ... -> current, visiting only vertices above root
+    fn extend(&mut self, root : usize, current : usize, length : usize, total : f64) -> Result<()> {
+        let n = self.weights.nrows;
+
+        if self.cancel.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+
+        self.paths += 1;
+        if self.paths > self.max_paths {
+            self.truncated = true;
+            return Ok(());
+        }
+
+        // close the cycle
+        if length >= 2 && total + self.weights.get(current, root) < -EPSILON {
+            if self.found >= self.limit {
+                self.truncated = true;
+                return Ok(());
+            }
+
+            *self.counts.entry(length).or_insert(0) += 1;
+            self.found += 1;
+        }
+
+        for next in root+1..n {
+            if self.truncated {
+                break;
+            }
+
+            if self.on_path[next] {
+                continue;
+            }
+
+            let total = total + self.weights.get(current, next);
+            let bound = total + (root+1..n).filter(
+                |&v| !self.on_path[v]  // includes next, which must leave again
+            ).map(|v| self.min_out[v]).sum::<f64>();
+
+            if bound >= -EPSILON {
+                continue;  // no cycle continuing this way can be negative
+            }
+
+            self.on_path[next] = true;
+            let result = self.extend(root, next, length + 1, total);
+            self.on_path[next] = false;
+            result?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether all cycles are non-negative and, given a cycle limit,
+/// how many simple cycles of each length are negative, up to the limit
+/// and within a search effort proportional to it.
+pub fn test(weights : &Matrix<f64>, cycle_limit : Option<u32>, cancel : &CancelFlag) -> Result<CyclicalTest> {
+    let pass = !has_negative_cycle(weights);
+
+    let limit = match cycle_limit {
+        Some(limit) if !pass => limit as usize,
+        _ => return Ok(CyclicalTest{pass, violations: Vec::new(), truncated: false}),
+    };
+
+    let n = weights.nrows;
+    let mut search = Search {
+        weights,
+        min_out: (0..n).map(
+            |i| (0..n).filter(|&j| j != i).map(|j| weights.get(i, j)).fold(0.0, f64::min)
+        ).collect(),
+        on_path: vec![false; n],
+        counts: BTreeMap::new(),
+        found: 0,
+        limit,
+        paths: 0,
+        max_paths: limit.max(1).saturating_mul(PATHS_PER_CYCLE),
+        truncated: false,
+        cancel,
+    };
+
+    // every cycle is enumerated once, from its smallest vertex
+    for root in 0..n {
+        if search.truncated {
+            break;
+        }
+
+        search.on_path[root] = true;
+        search.extend(root, root, 1, 0.0)?;
+        search.on_path[root] = false;
+    }
+
+    Ok(CyclicalTest {
+        pass,
+        violations: search.counts.into_iter().collect(),
+        truncated: search.truncated,
+    })
+}
+
+pub fn harp(px : &Matrix<f32>, cycle_limit : Option<u32>, cancel : &CancelFlag) -> Result<CyclicalTest> {
+    test(&Matrix::from_indices(px.nrows, px.nrows, |i, j|
+        if i == j {
+            0.0
+        } else if px.get(i, i) > 0.0 {
+            (px.get(i, j) as f64 / px.get(i, i) as f64).ln()
+        } else {
+            f64::INFINITY  // no budget, no revealed preference
+        }
+    ), cycle_limit, cancel)
+}
+
+pub fn quasilinear(px : &Matrix<f32>, cycle_limit : Option<u32>, cancel : &CancelFlag) -> Result<CyclicalTest> {
+    test(&Matrix::from_indices(px.nrows, px.nrows, |i, j|
+        px.get(i, j) as f64 - px.get(i, i) as f64
+    ), cycle_limit, cancel)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn negative_cycles() {
+        let weights = Matrix::from_slice(3, 3, &[
+             0.0, -1.0,  5.0,
+             2.0,  0.0, -2.0,
+             1.0,  3.0,  0.0,
+        ]);
+
+        // 0-1: +1, 1-2: +1, 0-2: +6, 0-1-2: -2, 0-2-1: +10
+        let result = test(&weights, Some(100), &CancelFlag::new()).unwrap();
+        assert_eq!(result, CyclicalTest{pass: false, violations: vec![(3, 1)], truncated: false});
+
+        let weights = Matrix::from_slice(2, 2, &[0.0, -1.0, 1.0, 0.0]);
+        let result = test(&weights, Some(100), &CancelFlag::new()).unwrap();
+        assert_eq!(result, CyclicalTest{pass: true, violations: vec![], truncated: false});
+    }
+
+    #[test]
+    fn harp_vs_quasilinear() {
+        // p^0 x^0 = 4, p^0 x^1 = 2; p^1 x^1 = 4, p^1 x^0 = 6
+        let px = Matrix::from_slice(2, 2, &[4.0, 2.0, 6.0, 4.0]);
+
+        // quasilinear: -2 + 2 = 0, fine; homothetic: 1/2 * 3/2 < 1
+        assert!(quasilinear(&px, Some(100), &CancelFlag::new()).unwrap().pass);
+        assert_eq!(
+            harp(&px, Some(100), &CancelFlag::new()).unwrap(),
+            CyclicalTest{pass: false, violations: vec![(2, 1)], truncated: false}
+        );
+
+        // scaling the second budget keeps the ratios but not the differences
+        let px = Matrix::from_slice(2, 2, &[4.0, 2.0, 3.0, 2.0]);
+        assert!(!quasilinear(&px, Some(100), &CancelFlag::new()).unwrap().pass);
+        assert!(!harp(&px, Some(100), &CancelFlag::new()).unwrap().pass);
+    }
+
+    #[test]
+    fn cycle_limit() {
+        // every cycle is negative: 6 of length 2, 8 of length 3, 6 of length 4
+        let weights = Matrix::from_indices(4, 4, |i, j| if i == j { 0.0 } else { -1.0 });
+
+        assert_eq!(
+            test(&weights, None, &CancelFlag::new()).unwrap(),
+            CyclicalTest{pass: false, violations: vec![], truncated: false}
+        );
+        assert_eq!(
+            test(&weights, Some(20), &CancelFlag::new()).unwrap(),
+            CyclicalTest{pass: false, violations: vec![(2, 6), (3, 8), (4, 6)], truncated: false}
+        );
+
+        let result = test(&weights, Some(5), &CancelFlag::new()).unwrap();
+        assert!(result.truncated);
+        assert_eq!(result.violations.iter().map(|&(_length, count)| count).sum::<usize>(), 5);
+
+        let cancel = CancelFlag::new();
+        cancel.cancel();
+        assert!(test(&weights, Some(20), &cancel).is_err());
+    }
+}
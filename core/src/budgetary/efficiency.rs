@@ -0,0 +1,151 @@
+use matrix::Matrix;
+use budgetary::{transitive_closure,revealed_preferred,strictly_revealed_preferred};
+
+/* Efficiency indices for budgetary data.
+ *
+ * At efficiency e, observation i reveals x^i preferred to x^j
+ * if e p^i x^i >= p^i x^j, and strictly if e p^i x^i > p^i x^j
+ * (see `budgetary::revealed_preferred`).
+ * Lowering the efficiency removes edges, so GARP violations can only disappear.
+ */
+
+// the efficiency below which observation i no longer reveals x^i preferred to x^j;
+// with a zero budget, the relations do not depend on the efficiency at all
+fn ratio(px : &Matrix<f32>, i : usize, j : usize) -> f64 {
+    let budget = px.get(i, i) as f64;
+    if budget > 0.0 {
+        px.get(i, j) as f64 / budget
+    } else {
+        f64::INFINITY
+    }
+}
+
+/// Does GARP hold when every observation i spends only `efficiency(i)` of its budget?
+pub fn garp_holds<F : Fn(usize) -> f64>(px : &Matrix<f32>, efficiency : F) -> bool {
+    let n = px.nrows;
+    let revealed = Matrix::from_indices(n, n, |i, j| revealed_preferred(px, efficiency(i), i, j));
+    let closure = transitive_closure(&revealed);
+
+    // x^i R x^j and x^j P x^i
+    !(0..n).any(|i| (0..n).any(|j|
+        closure.get(i, j) && strictly_revealed_preferred(px, efficiency(j), j, i)
+    ))
+}
+
+/// Afriat's critical cost efficiency index: the supremum
+/// of the efficiencies at which GARP holds.
+pub fn ccei(px : &Matrix<f32>) -> f64 {
+    let n = px.nrows;
+
+    // the revealed preference relations change only at these levels
+    let mut levels = vec![0.0, 1.0];
+    for i in 0..n {
+        for j in 0..n {
+            let r = ratio(px, i, j);
+            if i != j && r < 1.0 {
+                levels.push(r);
+            }
+        }
+    }
+    levels.sort_by(|x, y| x.partial_cmp(y).unwrap());
+    levels.dedup();
+
+    // GARP is constant between two consecutive levels so we test
+    // the levels and the midpoints: 0 = l_0 < m_0 < l_1 < m_1 < ... < l_k = 1
+    let point = |idx : usize| if idx & 1 == 0 {
+        levels[idx / 2]
+    } else {
+        (levels[idx / 2] + levels[idx / 2 + 1]) / 2.0
+    };
+
+    let mut lo = 0;  // GARP holds here
+    let mut hi = 2 * (levels.len() - 1);  // we don't know yet
+    if garp_holds(px, |_| point(hi)) {
+        return 1.0;
+    }
+
+    // GARP fails at hi
+    while hi - lo > 1 {
+        let mid = (lo + hi) / 2;
+        if garp_holds(px, |_| point(mid)) {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+
+    if lo & 1 == 0 {
+        // GARP holds at a level but fails just above it
+        levels[lo / 2]
+    } else {
+        // GARP holds all the way up to the next level
+        levels[lo / 2 + 1]
+    }
+}
+
+/// Varian's per-observation efficiencies: observation i is deflated until it no longer
+/// reveals anything preferred to the bundles that are (indirectly) revealed preferred to x^i.
+/// GARP holds at these efficiencies but they need not be the least deflating ones.
+pub fn varian(px : &Matrix<f32>) -> Vec<f64> {
+    let n = px.nrows;
+    let revealed = Matrix::from_indices(n, n, |i, j| revealed_preferred(px, 1.0, i, j));
+    let closure = transitive_closure(&revealed);
+
+    (0..n).map(|i|
+        (0..n).filter(
+            |&j| closure.get(j, i)
+        ).map(
+            |j| ratio(px, i, j)
+        ).fold(1.0, f64::min)
+    ).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use budgetary::{Subject,px_matrix};
+
+    fn px(prices : &[f32], amounts : &[f32]) -> Matrix<f32> {
+        let n = prices.len() / 2;
+        px_matrix(&Subject {
+            name: String::from("subject"),
+            prices: Matrix::from_slice(n, 2, prices),
+            amounts: Matrix::from_slice(n, 2, amounts),
+        })
+    }
+
+    #[test]
+    fn consistent() {
+        let px = px(&[1.0, 1.0, 4.0, 1.0], &[2.0, 0.0, 0.0, 1.0]);
+        assert_eq!(ccei(&px), 1.0);
+        assert_eq!(varian(&px), vec![1.0, 1.0]);
+    }
+
+    #[test]
+    fn two_cycle() {
+        // p^0 x^0 = 4, p^0 x^1 = 3; p^1 x^1 = 7, p^1 x^0 = 6
+        let px = px(&[1.0, 1.0, 3.0, 1.0], &[1.0, 3.0, 2.0, 1.0]);
+        assert!(!garp_holds(&px, |_| 1.0));
+
+        // the cycle breaks just below the efficiency 6/7
+        assert_eq!(ccei(&px), 6.0 / 7.0);
+        assert!(!garp_holds(&px, |_| 6.0 / 7.0));
+        assert!(garp_holds(&px, |_| 0.85));
+
+        assert_eq!(varian(&px), vec![3.0 / 4.0, 6.0 / 7.0]);
+        assert!(garp_holds(&px, |i| [3.0 / 4.0, 6.0 / 7.0][i]));
+    }
+
+    #[test]
+    fn zero_budget() {
+        // p^0 x^0 = 0 = p^0 x^1 so x^0 R x^1 at any efficiency, as in the GARP test;
+        // p^1 x^1 = 2 > 1 = p^1 x^0 so x^1 P x^0 down to the efficiency 1/2
+        let px = px(&[0.0, 1.0, 1.0, 1.0], &[1.0, 0.0, 2.0, 0.0]);
+        assert!(!garp_holds(&px, |_| 1.0));
+        assert!(!garp_holds(&px, |i| [0.0, 0.6][i]));
+        assert!(garp_holds(&px, |_| 0.5));
+
+        assert_eq!(ccei(&px), 0.5);
+        assert_eq!(varian(&px), vec![1.0, 0.5]);
+    }
+}
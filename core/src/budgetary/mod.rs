@@ -1,8 +1,14 @@
 pub mod consistency;
+pub mod efficiency;
+pub mod afriat;
+pub mod support;
+pub mod cyclical;
+pub mod reader;
 
-use std::io::Read;
-use codec::{self,Decode};
-use matrix::Matrix;
+use std::io::{Read,Write};
+use codec::{self,Encode,Decode};
+use json::{self,Json,ToJson,FromJson};
+use matrix::{Matrix,dot_product};
 
 /* For each matrix:
  * - columns ~ goods
@@ -24,3 +30,84 @@ impl Decode for Subject {
         })
     }
 }
+
+impl Encode for Subject {
+    fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+        (&self.name, &self.prices, &self.amounts).encode(f)
+    }
+}
+
+impl ToJson for Subject {
+    fn to_json(&self) -> Json {
+        json::object(vec![
+            ("name", self.name.to_json()),
+            ("prices", self.prices.to_json()),
+            ("amounts", self.amounts.to_json()),
+        ])
+    }
+}
+
+impl FromJson for Subject {
+    fn from_json(js : &Json) -> json::Result<Subject> {
+        Ok(Subject{
+            name:    json::field(js, "name")?,
+            prices:  json::field(js, "prices")?,
+            amounts: json::field(js, "amounts")?,
+        })
+    }
+}
+
+/// (row i, column j) contains p^i x^j
+pub fn px_matrix(subject : &Subject) -> Matrix<f32> {
+    let n_obs = subject.prices.nrows;
+
+    let mut px = Vec::new();
+    for p in subject.prices.iter_rows() {
+        px.extend(
+            subject.amounts.iter_rows().map(
+                |x| dot_product(p, x)
+            )
+        );
+    }
+
+    Matrix::new(n_obs, n_obs, px)
+}
+
+/* Revealed preference when observation i spends only the fraction `efficiency`
+ * of its budget: x^i is revealed preferred to x^j if e p^i x^i >= p^i x^j,
+ * and strictly if e p^i x^i > p^i x^j. The efficiency 1 gives the usual relations.
+ *
+ * All tests share this rule, so an observation with a zero budget
+ * reveals its bundle preferred to every bundle that costs nothing at its prices,
+ * whatever the efficiency, and strictly preferred to none.
+ */
+
+pub fn revealed_preferred(px : &Matrix<f32>, efficiency : f64, i : usize, j : usize) -> bool {
+    efficiency * px.get(i, i) as f64 >= px.get(i, j) as f64
+}
+
+pub fn strictly_revealed_preferred(px : &Matrix<f32>, efficiency : f64, i : usize, j : usize) -> bool {
+    efficiency * px.get(i, i) as f64 > px.get(i, j) as f64
+}
+
+/// Reflexive and transitive closure of a relation (Warshall's algorithm).
+pub fn transitive_closure(edges : &Matrix<bool>) -> Matrix<bool> {
+    let n = edges.nrows;
+    let mut closure = Matrix::from_indices(n, n, |i, j| i == j || edges.get(i, j));
+
+    for k in 0..n {
+        for i in 0..n {
+            if !closure.get(i, k) {
+                continue;
+            }
+
+            for j in 0..n {
+                if closure.get(k, j) {
+                    *closure.get_mut_ref(i, j) = true;
+                }
+            }
+        }
+    }
+
+    closure
+}
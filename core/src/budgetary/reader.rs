@@ -0,0 +1,453 @@
+use std::fmt;
+use std::result;
+use std::io::{Read,Write};
+use std::collections::HashMap;
+use budgetary::Subject;
+use matrix::Matrix;
+use codec::{self,Encode,Decode};
+use json::{self,Json,ToJson,FromJson};
+use csv::{FromRow,read_subjects};
+use csv::Error as ReadError;
+
+/* Budgetary CSV files come in two formats.
+ *
+ * Long: one row per subject, observation and good,
+ * with the columns `subject`, `observation`, `good`, `price`, `amount`
+ * (other columns are ignored). Goods are named by the `good` column.
+ *
+ * Wide: one row per subject and observation: the subject name,
+ * then the prices of all goods, then the amounts of all goods,
+ * matched by position. Goods are numbered from 1.
+ *
+ * Every observation must have all goods and the values must be non-negative.
+ */
+
+#[derive(Debug, Clone)]
+pub enum Error {
+    Csv(String),
+    MissingColumns,
+    OddColumnCount,
+    RowLength{line : usize},
+    BadNumber{subject : String, value : String},
+    Negative{subject : String, value : String},
+    MissingGood{subject : String, observation : String, good : String},
+    DuplicateGood{subject : String, observation : String, good : String},
+    SubjectDiscontiguous(String),
+}
+
+impl Encode for Error {
+    fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+        match *self {
+            Error::Csv(ref msg) => (0u8, msg).encode(f),
+            Error::MissingColumns => 1u8.encode(f),
+            Error::OddColumnCount => 2u8.encode(f),
+            Error::RowLength{line} => (3u8, line).encode(f),
+            Error::BadNumber{ref subject, ref value} => (4u8, subject, value).encode(f),
+            Error::Negative{ref subject, ref value} => (5u8, subject, value).encode(f),
+            Error::MissingGood{ref subject, ref observation, ref good} => (6u8, subject, observation, good).encode(f),
+            Error::DuplicateGood{ref subject, ref observation, ref good} => (7u8, subject, observation, good).encode(f),
+            Error::SubjectDiscontiguous(ref subject) => (8u8, subject).encode(f),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Csv(ref msg) => write!(f, "could not read CSV: {}", msg),
+            Error::MissingColumns => write!(
+                f, "long-format budgetary data need the columns subject, observation, good, price, amount"
+            ),
+            Error::OddColumnCount => write!(f, "wide-format budgetary data need an even number of numeric columns"),
+            Error::RowLength{line} => write!(f, "line {}: incorrect number of columns", line),
+            Error::BadNumber{ref subject, ref value} => write!(f, "subject {}: not a number: {:?}", subject, value),
+            Error::Negative{ref subject, ref value} => write!(f, "subject {}: negative value: {}", subject, value),
+            Error::MissingGood{ref subject, ref observation, ref good} => write!(
+                f, "subject {}, observation {}: missing good {}", subject, observation, good
+            ),
+            Error::DuplicateGood{ref subject, ref observation, ref good} => write!(
+                f, "subject {}, observation {}: good {} given more than once", subject, observation, good
+            ),
+            Error::SubjectDiscontiguous(ref subject) => write!(f, "subject {} is not contiguous", subject),
+        }
+    }
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Format {
+    Long,
+    Wide,
+}
+
+impl Decode for Format {
+    fn decode<R : Read>(f : &mut R) -> codec::Result<Format> {
+        match Decode::decode(f)? {
+            0u8 => Ok(Format::Long),
+            1u8 => Ok(Format::Wide),
+            _ => Err(codec::Error::BadEnumTag),
+        }
+    }
+}
+
+impl FromJson for Format {
+    fn from_json(js : &Json) -> json::Result<Format> {
+        match String::from_json(js)?.as_str() {
+            "long" => Ok(Format::Long),
+            "wide" => Ok(Format::Wide),
+            tag => Err(json::Error::BadTag(String::from(tag))),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Request {
+    pub csv : String,
+    pub format : Format,
+}
+
+impl Decode for Request {
+    fn decode<R : Read>(f : &mut R) -> codec::Result<Request> {
+        Ok(Request {
+            csv: Decode::decode(f)?,
+            format: Decode::decode(f)?,
+        })
+    }
+}
+
+impl FromJson for Request {
+    fn from_json(js : &Json) -> json::Result<Request> {
+        Ok(Request {
+            csv: json::field(js, "csv")?,
+            format: json::field(js, "format")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub goods : Vec<String>,
+    pub subjects : Vec<Subject>,
+}
+
+impl Encode for Response {
+    fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+        (&self.goods, &self.subjects).encode(f)
+    }
+}
+
+impl ToJson for Response {
+    fn to_json(&self) -> Json {
+        json::object(vec![
+            ("goods", self.goods.to_json()),
+            ("subjects", self.subjects.to_json()),
+        ])
+    }
+}
+
+fn parse_value(subject : &str, cell : &str) -> Result<f32> {
+    match cell.trim().parse::<f32>() {
+        Ok(x) if x.is_finite() && x >= 0.0 => Ok(x),
+        Ok(x) if x < 0.0 => Err(Error::Negative{subject: String::from(subject), value: String::from(cell)}),
+        _ => Err(Error::BadNumber{subject: String::from(subject), value: String::from(cell)}),
+    }
+}
+
+#[derive(Debug)]
+pub struct MissingColumns;
+
+// the numbers are parsed later, when we know the subject
+#[derive(Debug, Clone)]
+pub struct LongRow {
+    observation : String,
+    good : usize,  // index into the goods
+    price : String,
+    amount : String,
+}
+
+impl FromRow for LongRow {
+    type ParseError = MissingColumns;
+    const COLUMN_NAMES : &'static[&'static str] = &["observation", "good", "price", "amount"];
+
+    fn from_row(goods : &mut Vec<String>, row : &[&str]) -> result::Result<LongRow, MissingColumns> {
+        match row {
+            [observation, good, price, amount] => {
+                let good = good.trim();
+                let good = match goods.iter().position(|g| g == good) {
+                    Some(i) => i,
+                    None => {
+                        goods.push(String::from(good));
+                        goods.len() - 1
+                    }
+                };
+
+                Ok(LongRow {
+                    observation: String::from(observation.trim()),
+                    good,
+                    price: String::from(*price),
+                    amount: String::from(*amount),
+                })
+            }
+
+            _ => Err(MissingColumns),
+        }
+    }
+}
+
+// prices and amounts by observation and good
+struct Partial {
+    name : String,
+    observations : Vec<String>,
+    cells : Vec<HashMap<usize, (f32, f32)>>,
+}
+
+impl Partial {
+    fn finish(self, goods : &[String]) -> Result<Subject> {
+        let n_obs = self.observations.len();
+        let mut prices = Vec::with_capacity(n_obs * goods.len());
+        let mut amounts = Vec::with_capacity(n_obs * goods.len());
+
+        for (observation, cells) in self.observations.iter().zip(&self.cells) {
+            for (i, good) in goods.iter().enumerate() {
+                match cells.get(&i) {
+                    Some(&(price, amount)) => {
+                        prices.push(price);
+                        amounts.push(amount);
+                    }
+
+                    None => return Err(Error::MissingGood{
+                        subject: self.name.clone(),
+                        observation: observation.clone(),
+                        good: good.clone(),
+                    }),
+                }
+            }
+        }
+
+        Ok(Subject {
+            name: self.name,
+            prices: Matrix::new(n_obs, goods.len(), prices),
+            amounts: Matrix::new(n_obs, goods.len(), amounts),
+        })
+    }
+}
+
+pub fn read_long<R : Read>(mut rdr : R) -> Result<Response> {
+    let mut text = String::new();
+    rdr.read_to_string(&mut text).map_err(|e| Error::Csv(e.to_string()))?;
+
+    // the generic reader silently skips missing columns
+    {
+        let mut csv_reader = csv::Reader::from_reader(text.as_bytes());
+        let headers = csv_reader.headers().map_err(|e| Error::Csv(e.to_string()))?;
+        let complete = ["subject", "observation", "good", "price", "amount"].iter().all(
+            |&col| headers.iter().any(|h| h == col)
+        );
+
+        if !complete {
+            return Err(Error::MissingColumns);
+        }
+    }
+
+    let subjects = read_subjects::<_, (), LongRow>(text.as_bytes(), "subject").map_err(
+        |_| Error::MissingColumns  // we've already checked the columns
+    )?;
+
+    let mut partials = Vec::new();
+    let mut goods = Vec::new();
+    for subject in subjects {
+        let subject = subject.map_err(|e| match e {
+            ReadError::IO(e) => Error::Csv(e.to_string()),
+            ReadError::Csv(e) => Error::Csv(e.to_string()),
+            ReadError::SubjectDiscontiguous(name) => Error::SubjectDiscontiguous(name),
+            _ => Error::MissingColumns,
+        })?;
+
+        let mut partial = Partial {
+            name: subject.name,
+            observations: Vec::new(),
+            cells: Vec::new(),
+        };
+
+        for row in subject.rows {
+            let obs = match partial.observations.iter().position(|o| *o == row.observation) {
+                Some(obs) => obs,
+                None => {
+                    partial.observations.push(row.observation.clone());
+                    partial.cells.push(HashMap::new());
+                    partial.observations.len() - 1
+                }
+            };
+
+            let values = (parse_value(&partial.name, &row.price)?, parse_value(&partial.name, &row.amount)?);
+            if partial.cells[obs].insert(row.good, values).is_some() {
+                return Err(Error::DuplicateGood{
+                    subject: partial.name,
+                    observation: row.observation,
+                    good: subject.alternatives[row.good].clone(),
+                });
+            }
+        }
+
+        goods = subject.alternatives;
+        partials.push(partial);
+    }
+
+    // only now we know all the goods
+    Ok(Response {
+        subjects: partials.into_iter().map(|p| p.finish(&goods)).collect::<Result<Vec<Subject>>>()?,
+        goods,
+    })
+}
+
+pub fn read_wide<R : Read>(rdr : R) -> Result<Response> {
+    let mut csv_reader = csv::Reader::from_reader(rdr);
+    let n_columns = csv_reader.headers().map_err(|e| Error::Csv(e.to_string()))?.len();
+    if n_columns < 1 || (n_columns - 1) % 2 != 0 {
+        return Err(Error::OddColumnCount);
+    }
+
+    let n_goods = (n_columns - 1) / 2;
+    let goods : Vec<String> = (1..n_goods+1).map(|i| i.to_string()).collect();
+
+    // subjects need not be contiguous here, like in the GUI
+    let mut partials : Vec<Partial> = Vec::new();
+    for (line, record) in csv_reader.into_records().enumerate() {
+        let record = record.map_err(|e| Error::Csv(e.to_string()))?;
+        if record.len() != n_columns {
+            return Err(Error::RowLength{line: line + 2});  // 1-based, after the header
+        }
+
+        let name = &record[0];
+        let values = record.iter().skip(1).map(
+            |cell| parse_value(name, cell)
+        ).collect::<Result<Vec<f32>>>()?;
+
+        let ix = match partials.iter().position(|p| p.name == name) {
+            Some(ix) => ix,
+            None => {
+                partials.push(Partial {
+                    name: String::from(name),
+                    observations: Vec::new(),
+                    cells: Vec::new(),
+                });
+                partials.len() - 1
+            }
+        };
+
+        let partial = &mut partials[ix];
+        partial.observations.push((partial.observations.len() + 1).to_string());
+        partial.cells.push(
+            (0..n_goods).map(|i| (i, (values[i], values[n_goods + i]))).collect()
+        );
+    }
+
+    Ok(Response {
+        subjects: partials.into_iter().map(|p| p.finish(&goods)).collect::<Result<Vec<Subject>>>()?,
+        goods,
+    })
+}
+
+/// Write the subjects in the wide format, which the GUI can import.
+pub fn write_wide<W : Write>(out : W, response : &Response) -> Result<()> {
+    let mut csv_writer = csv::Writer::from_writer(out);
+    let mut header = vec![String::from("subject")];
+    header.extend(response.goods.iter().map(|g| format!("price_{}", g)));
+    header.extend(response.goods.iter().map(|g| format!("amount_{}", g)));
+    csv_writer.write_record(&header).map_err(|e| Error::Csv(e.to_string()))?;
+
+    for subject in &response.subjects {
+        for (prices, amounts) in subject.prices.iter_rows().zip(subject.amounts.iter_rows()) {
+            let mut record = vec![subject.name.clone()];
+            record.extend(prices.iter().chain(amounts).map(|x| x.to_string()));
+            csv_writer.write_record(&record).map_err(|e| Error::Csv(e.to_string()))?;
+        }
+    }
+
+    csv_writer.flush().map_err(|e| Error::Csv(e.to_string()))
+}
+
+pub fn run(request : &Request) -> Result<Response> {
+    match request.format {
+        Format::Long => read_long(request.csv.as_bytes()),
+        Format::Wide => read_wide(request.csv.as_bytes()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn long_and_wide() {
+        let long = read_long("\
+subject,observation,good,price,amount
+s1,a,apples,1,2
+s1,a,pears,2,0.5
+s1,b,pears,1,1
+s1,b,apples,3,0
+s2,1,apples,1,1
+s2,1,pears,1,1
+".as_bytes()).unwrap();
+
+        let wide = read_wide("\
+subject,p1,p2,x1,x2
+s1,1,2,2,0.5
+s2,1,1,1,1
+s1,3,1,0,1
+".as_bytes()).unwrap();
+
+        assert_eq!(long.goods, vec!["apples", "pears"]);
+        assert_eq!(wide.goods, vec!["1", "2"]);
+
+        for response in &[long, wide] {
+            let s1 = &response.subjects[0];
+            assert_eq!(s1.name, "s1");
+            assert_eq!(s1.prices, Matrix::from_slice(2, 2, &[1.0, 2.0, 3.0, 1.0]));
+            assert_eq!(s1.amounts, Matrix::from_slice(2, 2, &[2.0, 0.5, 0.0, 1.0]));
+            assert_eq!(response.subjects[1].prices, Matrix::from_slice(1, 2, &[1.0, 1.0]));
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let long = read_long("\
+subject,observation,good,price,amount
+s1,a,apples,1,2
+s1,a,pears,2,0.5
+".as_bytes()).unwrap();
+
+        let mut buf = Vec::new();
+        write_wide(&mut buf, &long).unwrap();
+        assert_eq!(String::from_utf8(buf.clone()).unwrap(), "\
+subject,price_apples,price_pears,amount_apples,amount_pears
+s1,1,2,2,0.5
+");
+        assert_eq!(read_wide(&buf[..]).unwrap().subjects, long.subjects);
+    }
+
+    #[test]
+    fn validation() {
+        let missing = read_long("\
+subject,observation,good,price,amount
+s1,a,apples,1,2
+s1,a,pears,2,0.5
+s1,b,apples,3,0
+".as_bytes());
+        assert!(matches!(missing, Err(Error::MissingGood{ref observation, ref good, ..})
+            if observation == "b" && good == "pears"));
+
+        let negative = read_wide("subject,p1,x1\ns1,1,-2\n".as_bytes());
+        assert!(matches!(negative, Err(Error::Negative{..})));
+
+        let odd = read_wide("subject,p1,p2,x1\ns1,1,2,3\n".as_bytes());
+        assert!(matches!(odd, Err(Error::OddColumnCount)));
+
+        let missing = read_long("subject,good,price,amount\n".as_bytes());
+        assert!(matches!(missing, Err(Error::MissingColumns)));
+
+        let duplicate = read_long("subject,observation,good,price,amount\ns1,a,x,1,1\ns1,a,x,1,1\n".as_bytes());
+        assert!(matches!(duplicate, Err(Error::DuplicateGood{..})));
+    }
+}
@@ -0,0 +1,360 @@
+use std::fmt;
+use std::result;
+use std::io::{Read,Write};
+use budgetary::{self,Subject,efficiency};
+use matrix::{Matrix,dot_product};
+use rpc_common::CancelFlag;
+use codec::{self,Encode,Decode};
+use json::{self,Json,ToJson,FromJson};
+
+/* Varian's support set: the bundles x on a new budget (p, m)
+ * that a subject could buy without violating GARP.
+ *
+ * The new observation reveals x preferred to x^j iff p x^j <= m,
+ * which does not depend on x. Hence a new GARP cycle goes
+ *
+ *   x  R  x^j  R ... R  x^i  R  x
+ *
+ * and it is broken iff p^i x > p^i x^i. Let W be the set of observations i
+ * that x is (indirectly) revealed preferred to in this way. Up to the boundary,
+ * the support set is then the polytope
+ *
+ *   { x >= 0  |  p x = m,  p^i x >= p^i x^i for all i in W }
+ *
+ * whose vertices we enumerate by intersecting the budget hyperplane
+ * with all (goods - 1)-tuples of the other constraints. This is exponential
+ * in the number of goods but fine for the usual handful of goods.
+ */
+
+const EPSILON : f64 = 1e-6;
+
+#[derive(Debug, Clone)]
+pub enum Error {
+    Cancelled,
+    GarpViolated,
+    PriceSize{expected : usize, got : usize},
+    NonPositiveIncome,
+    NoGoods,
+    InvalidPrice{good : usize, price : f32},
+}
+
+impl Encode for Error {
+    fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+        match *self {
+            Error::Cancelled => 0u8.encode(f),
+            Error::GarpViolated => 1u8.encode(f),
+            Error::PriceSize{expected, got} => (2u8, expected, got).encode(f),
+            Error::NonPositiveIncome => 3u8.encode(f),
+            Error::NoGoods => 4u8.encode(f),
+            Error::InvalidPrice{good, price} => (5u8, good, price).encode(f),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Cancelled => write!(f, "cancelled"),
+            Error::GarpViolated => write!(f, "the data violate GARP so no new choice is consistent with them"),
+            Error::PriceSize{expected, got} => write!(
+                f, "the new price vector has {} goods but the subject has {} goods", got, expected
+            ),
+            Error::NonPositiveIncome => write!(f, "the new income must be positive"),
+            Error::NoGoods => write!(f, "the subject has no goods"),
+            Error::InvalidPrice{good, price} => write!(
+                f, "the new price of good {} must be positive, got {}", good + 1, price
+            ),
+        }
+    }
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+#[derive(Debug)]
+pub struct Request {
+    subject : Subject,
+    prices : Vec<f32>,
+    income : f32,
+}
+
+impl Decode for Request {
+    fn decode<R : Read>(f : &mut R) -> codec::Result<Request> {
+        Ok(Request {
+            subject: Decode::decode(f)?,
+            prices: Decode::decode(f)?,
+            income: Decode::decode(f)?,
+        })
+    }
+}
+
+impl FromJson for Request {
+    fn from_json(js : &Json) -> json::Result<Request> {
+        Ok(Request {
+            subject: json::field(js, "subject")?,
+            prices: json::field(js, "prices")?,
+            income: json::field(js, "income")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub name : String,
+    pub constraints : Vec<u32>,  // the set W: observations i such that p^i x >= p^i x^i
+    pub vertices : Matrix<f32>,  // rows ~ vertices, columns ~ goods
+    pub lower : Vec<f32>,  // for each good, NaN if there are no vertices
+    pub upper : Vec<f32>,  // for each good, NaN if there are no vertices
+}
+
+impl Encode for Response {
+    fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+        (&self.name, &self.constraints, &self.vertices, &self.lower, &self.upper).encode(f)
+    }
+}
+
+impl ToJson for Response {
+    fn to_json(&self) -> Json {
+        json::object(vec![
+            ("name", self.name.to_json()),
+            ("constraints", self.constraints.to_json()),
+            ("vertices", self.vertices.to_json()),
+            ("lower", self.lower.to_json()),
+            ("upper", self.upper.to_json()),
+        ])
+    }
+}
+
+// coefs x >= rhs
+struct HalfSpace {
+    coefs : Vec<f64>,
+    rhs : f64,
+}
+
+/// Gaussian elimination with partial pivoting; `None` if (nearly) singular.
+fn solve(mut a : Vec<Vec<f64>>, mut b : Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n).max_by(
+            |&i, &j| a[i][col].abs().partial_cmp(&a[j][col].abs()).unwrap()
+        ).unwrap();
+        if a[pivot][col].abs() < EPSILON {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+
+        let pivot_row = a[col].clone();
+        for row in col+1..n {
+            let factor = a[row][col] / pivot_row[col];
+            for (x, &p) in a[row][col..].iter_mut().zip(&pivot_row[col..]) {
+                *x -= factor * p;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let rest : f64 = (row+1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - rest) / a[row][row];
+    }
+
+    Some(x)
+}
+
+// advance to the next k-subset of 0..n in lexicographic order
+fn next_combination(idxs : &mut [usize], n : usize) -> bool {
+    let k = idxs.len();
+    for pos in (0..k).rev() {
+        if idxs[pos] < n - k + pos {
+            idxs[pos] += 1;
+            for next in pos+1..k {
+                idxs[next] = idxs[next-1] + 1;
+            }
+            return true;
+        }
+    }
+
+    false
+}
+
+/// The observations whose bundles the new budget reveals (indirectly) preferred
+/// to anything it could buy.
+pub fn revealed_worse(subject : &Subject, prices : &[f32], income : f32) -> Vec<usize> {
+    let px = budgetary::px_matrix(subject);
+    let n = px.nrows;
+    let revealed = Matrix::from_indices(n, n, |i, j| budgetary::revealed_preferred(&px, 1.0, i, j));
+    let closure = budgetary::transitive_closure(&revealed);
+
+    (0..n).filter(|&i| (0..n).any(
+        |j| dot_product(prices, subject.amounts.row(j)) <= income && closure.get(j, i)
+    )).collect()
+}
+
+pub fn run(request : &Request, cancel : &CancelFlag) -> Result<Response> {
+    let subject = &request.subject;
+    let n_goods = subject.prices.ncols;
+    if request.prices.len() != n_goods {
+        return Err(Error::PriceSize{expected: n_goods, got: request.prices.len()});
+    }
+
+    if n_goods == 0 {
+        // there is no budget hyperplane to intersect
+        return Err(Error::NoGoods);
+    }
+
+    // otherwise the budget hyperplane is degenerate or NaN
+    if let Some((good, &price)) = request.prices.iter().enumerate().find(
+        |&(_, &p)| !p.is_finite() || p <= 0.0
+    ) {
+        return Err(Error::InvalidPrice{good, price});
+    }
+
+    if !request.income.is_finite() || request.income <= 0.0 {
+        return Err(Error::NonPositiveIncome);
+    }
+
+    if !efficiency::garp_holds(&budgetary::px_matrix(subject), |_| 1.0) {
+        return Err(Error::GarpViolated);
+    }
+
+    let constraints = revealed_worse(subject, &request.prices, request.income);
+
+    let mut half_spaces : Vec<HalfSpace> = (0..n_goods).map(|g| HalfSpace {
+        coefs: (0..n_goods).map(|h| if g == h { 1.0 } else { 0.0 }).collect(),
+        rhs: 0.0,
+    }).collect();
+    half_spaces.extend(constraints.iter().map(|&i| HalfSpace {
+        coefs: subject.prices.row(i).iter().map(|&p| p as f64).collect(),
+        rhs: dot_product(subject.prices.row(i), subject.amounts.row(i)) as f64,
+    }));
+
+    let budget : Vec<f64> = request.prices.iter().map(|&p| p as f64).collect();
+    let income = request.income as f64;
+    let feasible = |x : &[f64]| half_spaces.iter().all(|h|
+        h.coefs.iter().zip(x).map(|(c, x)| c * x).sum::<f64>() >= h.rhs - EPSILON * (1.0 + h.rhs.abs())
+    );
+
+    let mut vertices : Vec<Vec<f64>> = Vec::new();
+    let mut idxs : Vec<usize> = (0..n_goods-1).collect();
+    loop {
+        if cancel.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+
+        let mut a = vec![budget.clone()];
+        let mut b = vec![income];
+        for &k in &idxs {
+            a.push(half_spaces[k].coefs.clone());
+            b.push(half_spaces[k].rhs);
+        }
+
+        if let Some(x) = solve(a, b) {
+            let is_new = !vertices.iter().any(
+                |v| v.iter().zip(&x).all(|(v, x)| (v - x).abs() <= EPSILON * (1.0 + x.abs()))
+            );
+
+            if is_new && feasible(&x) {
+                vertices.push(x);
+            }
+        }
+
+        if !next_combination(&mut idxs, half_spaces.len()) {
+            break;
+        }
+    }
+
+    let bound = |g : usize, init : f64, f : fn(f64, f64) -> f64|
+        if vertices.is_empty() {
+            f64::NAN
+        } else {
+            vertices.iter().map(|v| v[g]).fold(init, f)
+        };
+
+    Ok(Response {
+        name: subject.name.clone(),
+        constraints: constraints.iter().map(|&i| i as u32).collect(),
+        lower: (0..n_goods).map(|g| bound(g, f64::INFINITY, f64::min) as f32).collect(),
+        upper: (0..n_goods).map(|g| bound(g, f64::NEG_INFINITY, f64::max) as f32).collect(),
+        vertices: Matrix::from_indices(vertices.len(), n_goods, |i, g| vertices[i][g] as f32),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn request(prices : &[f32], income : f32) -> Request {
+        Request {
+            subject: Subject {
+                name: String::from("subject"),
+                prices: Matrix::from_slice(1, 2, &[1.0, 1.0]),
+                amounts: Matrix::from_slice(1, 2, &[1.0, 1.0]),
+            },
+            prices: prices.to_vec(),
+            income,
+        }
+    }
+
+    #[test]
+    fn one_observation() {
+        // x^0 = (1,1) is affordable so x must not be affordable at p^0
+        let resp = run(&request(&[1.0, 2.0], 3.0), &CancelFlag::new()).unwrap();
+        assert_eq!(resp.constraints, vec![0]);
+        assert_eq!(resp.vertices, Matrix::from_slice(2, 2, &[3.0, 0.0, 1.0, 1.0]));
+        assert_eq!(resp.lower, vec![1.0, 0.0]);
+        assert_eq!(resp.upper, vec![3.0, 1.0]);
+
+        // x^0 is not affordable: anything on the budget line goes
+        let resp = run(&request(&[1.0, 2.0], 2.0), &CancelFlag::new()).unwrap();
+        assert_eq!(resp.constraints, Vec::<u32>::new());
+        assert_eq!(resp.vertices, Matrix::from_slice(2, 2, &[0.0, 1.0, 2.0, 0.0]));
+    }
+
+    #[test]
+    fn three_goods() {
+        let mut req = request(&[1.0, 1.0, 2.0], 4.0);
+        req.subject.prices = Matrix::from_slice(1, 3, &[1.0, 1.0, 1.0]);
+        req.subject.amounts = Matrix::from_slice(1, 3, &[1.0, 1.0, 1.0]);
+
+        // x1 + x2 + x3 >= 3 cuts off the vertex (0,0,2) of the budget simplex
+        let resp = run(&req, &CancelFlag::new()).unwrap();
+        assert_eq!(resp.constraints, vec![0]);
+        assert_eq!(resp.vertices, Matrix::from_slice(4, 3, &[
+            0.0, 4.0, 0.0,
+            0.0, 2.0, 1.0,
+            4.0, 0.0, 0.0,
+            2.0, 0.0, 1.0,
+        ]));
+        assert_eq!(resp.lower, vec![0.0, 0.0, 0.0]);
+        assert_eq!(resp.upper, vec![4.0, 4.0, 1.0]);
+    }
+
+    #[test]
+    fn violation() {
+        let mut req = request(&[1.0, 1.0], 1.0);
+        req.subject.prices = Matrix::from_slice(2, 2, &[1.0, 1.0, 3.0, 1.0]);
+        req.subject.amounts = Matrix::from_slice(2, 2, &[1.0, 3.0, 2.0, 1.0]);
+        assert!(matches!(run(&req, &CancelFlag::new()), Err(Error::GarpViolated)));
+    }
+
+    #[test]
+    fn no_goods() {
+        let mut req = request(&[], 1.0);
+        req.subject.prices = Matrix::from_slice(1, 0, &[]);
+        req.subject.amounts = Matrix::from_slice(1, 0, &[]);
+        assert!(matches!(run(&req, &CancelFlag::new()), Err(Error::NoGoods)));
+    }
+
+    #[test]
+    fn invalid_prices() {
+        for &price in &[0.0, -1.0, f32::NAN, f32::INFINITY] {
+            match run(&request(&[1.0, price], 3.0), &CancelFlag::new()) {
+                Err(Error::InvalidPrice{good: 1, ..}) => (),
+                r => panic!("price {} accepted: {:?}", price, r),
+            }
+        }
+
+        assert!(matches!(run(&request(&[1.0, 2.0], f32::NAN), &CancelFlag::new()), Err(Error::NonPositiveIncome)));
+    }
+}
@@ -10,6 +10,7 @@ pub enum Error {
     Overflow,
     BadInteger,
     BadEnumTag,
+    UnknownRequest(String),
     IO(std::io::Error),
     Unicode(std::string::FromUtf8Error),
 }
@@ -60,6 +61,18 @@ impl Decode for f32 {
     }
 }
 
+impl Encode for f64 {
+    fn encode<W : Write>(&self, f : &mut W) -> Result<()> {
+        Ok(f.write_f64::<NativeEndian>(*self)?)
+    }
+}
+
+impl Decode for f64 {
+    fn decode<R : Read>(f : &mut R) -> Result<f64> {
+        Ok(f.read_f64::<NativeEndian>()?)
+    }
+}
+
 #[allow(dead_code)]
 fn encode_bytes<W : Write>(f : &mut W, bytes : &[u8]) -> Result<()> {
     bytes.len().encode(f)?;
@@ -240,6 +253,8 @@ impl_tuple!(t1 : T1, t2 : T2, t3 : T3, t4 : T4, t5 : T5);
 impl_tuple!(t1 : T1, t2 : T2, t3 : T3, t4 : T4, t5 : T5, t6 : T6);
 impl_tuple!(t1 : T1, t2 : T2, t3 : T3, t4 : T4, t5 : T5, t6 : T6, t7 : T7);
 impl_tuple!(t1 : T1, t2 : T2, t3 : T3, t4 : T4, t5 : T5, t6 : T6, t7 : T7, t8 : T8);
+impl_tuple!(t1 : T1, t2 : T2, t3 : T3, t4 : T4, t5 : T5, t6 : T6, t7 : T7, t8 : T8, t9 : T9);
+impl_tuple!(t1 : T1, t2 : T2, t3 : T3, t4 : T4, t5 : T5, t6 : T6, t7 : T7, t8 : T8, t9 : T9, t10 : T10);
 
 impl<'a, T : Encode> Encode for &'a T {
     fn encode<W : Write>(&self, f : &mut W) -> Result<()> {
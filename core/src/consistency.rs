@@ -1,15 +1,19 @@
 use std::result;
 use std::fmt::{self, Display};
-use std::collections::{BTreeMap,HashSet};
+use std::collections::{BTreeMap,HashSet,VecDeque};
 use std::collections::btree_map::Entry;
 use num::{zero, one};
 use std::io::{Read,Write};
 use std::iter::FromIterator;
 
 use alt::Alt;
+use alt_set::AltSet;
+use graph;
 use integer::Integer;
-use rpc_common::{ChoiceRow,Subject};
+use set_cover;
+use rpc_common::{ChoiceRow,Subject,CancelFlag};
 use codec::{self,Encode,Decode,Packed};
+use json::{self,Json,ToJson,FromJson};
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Hash, Copy)]
 struct Edge(pub u32);  // choice row index
@@ -128,41 +132,19 @@ impl Cycle {
     }
 
     fn garp_multiplicity_in(&self, strict : &Graph, non_strict : &Graph) -> Integer {
-        fn multiplicity_from(
-            strict : &Graph,
-            non_strict : &Graph,
-            got_strict_edge : bool,
-            edges : &[(Alt, Alt)]
-        ) -> Integer {
-            if edges.is_empty() {
-                if got_strict_edge {
-                    return one();
-                } else {
-                    return zero();
-                }
-            }
-
-            let (u, v) = edges[0];
-            assert!(non_strict.has_edge(u, v));
+        // all choices of edges along the cycle,
+        // minus those that do not use any strict edge
+        let mut all : Integer = one();
+        let mut non_strict_only : Integer = one();
 
-            if strict.has_edge(u, v) {
-                // we may choose a strict edge here (or not)
-                let strict_edges = strict.edges(u, v).len();
-                let remaining_edges = non_strict.edges(u, v).len() - strict_edges;  // non_strict includes strict, we want the rest
-
-                strict_edges * multiplicity_from(strict, non_strict, true, &edges[1..])
-                + remaining_edges * multiplicity_from(strict, non_strict, got_strict_edge, &edges[1..]) 
-            } else {
-                // we must choose a non-strict edge here
-                //
-                // this branch is a special case of the previous one
-                // but is more efficient because it does not branch execution
-                non_strict.edges(u, v).len() * multiplicity_from(strict, non_strict, got_strict_edge, &edges[1..])
-            }
+        for (u, v) in self.edges() {
+            let edges = non_strict.edges(u, v).len();
+            let strict_edges = strict.edges(u, v).len();  // non_strict includes strict
+            all *= edges;
+            non_strict_only *= edges - strict_edges;
         }
 
-        let edges : Vec<_> = self.edges().collect();
-        multiplicity_from(strict, non_strict, false, &edges)
+        all - non_strict_only
     }
 
     fn len(&self) -> u32 {
@@ -225,85 +207,212 @@ fn find<T : PartialEq>(x : T, xs : &[T]) -> Option<usize> {
     None
 }
 
-fn find_cycles_from(
-    untouched : &mut HashSet<Alt>,
-    g : &Graph,
-    history : &mut Vec<Alt>,
-    root : Alt
-) -> HashSet<Cycle> {
-    //println!("find_cycles_from {}, history = {:?}", root, history);
-
-    let mut result = HashSet::new();
-    untouched.remove(&root);
-
-    /* There are 3 kinds of neighbours:
-     * - untouched: recurse and depth-first-search further
-     * - in history (and touched): close loop
-     * - touched but not in history: no (new) loop possible
-     */
-
-    // traverse all neighbours
-    history.push(root);
-    for next in Alt::all(g.vertices) {
-        if !g.has_edge(root, next) || next == root {
-            // no edge or self-loop
-            continue;
+impl Graph {
+    // successors without self-loops, which never close a cycle of interest
+    fn successors(&self, Alt(v) : Alt) -> Vec<usize> {
+        (0..self.vertices).filter(
+            |&w| w != v && self.has_edge(Alt(v), Alt(w))
+        ).map(|w| w as usize).collect()
+    }
+
+    // component index of every vertex
+    fn components(&self) -> Vec<usize> {
+        let mut result = vec![0; self.vertices as usize];
+        let components = graph::strongly_connected_components(
+            self.vertices as usize, &|v| self.successors(Alt(v as u32))
+        );
+
+        for (i, component) in components.iter().enumerate() {
+            for &v in component {
+                result[v] = i;
+            }
         }
 
-        //println!("inspecting edge {:?} -> {}", history, next);
+        result
+    }
+
+    // is there a cycle made of edges of `self` that uses at least one edge of `strict`?
+    fn has_cycle_through(&self, strict : &Graph) -> bool {
+        self.cycle_through(strict).is_some()
+    }
+
+    // the choice rows along one such cycle, if there is any
+    fn cycle_through(&self, strict : &Graph) -> Option<Vec<Edge>> {
+        let component = self.components();
+
+        for u in Alt::all(self.vertices) {
+            for v in strict.successors(u) {
+                if component[u.index() as usize] != component[v] {
+                    continue;
+                }
+
+                // shortest path back from v to u, which exists within the component
+                let mut previous = vec![None; self.vertices as usize];
+                let mut queue = VecDeque::new();
+                queue.push_back(v);
+                while let Some(w) = queue.pop_front() {
+                    if w == u.index() as usize {
+                        break;
+                    }
+
+                    for x in self.successors(Alt(w as u32)) {
+                        if x != v && previous[x].is_none() {
+                            previous[x] = Some(w);
+                            queue.push_back(x);
+                        }
+                    }
+                }
 
-        match find(next, history) { // since history never repeats, find == rfind
-            Some(idx) => {
-                // we've been there in our own history
-                // println!("-> new cycle! {:?}", &history[idx..]);
-                result.insert(Cycle::new(&history[idx..]));
+                let mut rows = vec![strict.edges(u, Alt(v as u32))[0]];
+                let mut w = u.index() as usize;
+                while w != v {
+                    let p = previous[w].unwrap();  // w was reached from v
+                    rows.push(self.edges(Alt(p as u32), Alt(w as u32))[0]);
+                    w = p;
+                }
+
+                return Some(rows);
             }
+        }
 
-            None => {
-                // println!("-> someone's been there already, but recursing anyway");
-                let mut new_cycles = find_cycles_from(untouched, g, history, next);
-                result.extend(new_cycles.drain());
+        None
+    }
+}
+
+/* Elementary cycles are enumerated by Johnson's algorithm:
+ * every cycle is found from its smallest vertex, searching only
+ * the strongly connected component of that vertex in the subgraph
+ * of the larger vertices. Vertices that cannot reach the root
+ * anymore are blocked so the time spent is linear in the number of cycles.
+ */
+struct Circuits<'a> {
+    g : &'a Graph,
+    allowed : Vec<bool>,  // the component of the current root
+    blocked : Vec<bool>,
+    blocked_by : Vec<HashSet<usize>>,
+    stack : Vec<Alt>,
+    cycles : HashSet<Cycle>,
+    limit : Option<usize>,
+    truncated : bool,
+}
+
+impl<'a> Circuits<'a> {
+    fn unblock(&mut self, v : usize) {
+        self.blocked[v] = false;
+        let waiting : Vec<usize> = self.blocked_by[v].drain().collect();
+        for w in waiting {
+            if self.blocked[w] {
+                self.unblock(w);
             }
         }
     }
-    history.pop();
 
-    result
+    fn circuit(&mut self, root : usize, v : usize) -> bool {
+        let mut found = false;
+        self.stack.push(Alt(v as u32));
+        self.blocked[v] = true;
+
+        for w in self.g.successors(Alt(v as u32)) {
+            if self.truncated || !self.allowed[w] {
+                continue;
+            }
+
+            if w == root {
+                if self.limit.is_some_and(|limit| self.cycles.len() >= limit) {
+                    self.truncated = true;
+                    continue;
+                }
+
+                self.cycles.insert(Cycle::new(&self.stack));
+                found = true;
+            } else if !self.blocked[w] && self.circuit(root, w) {
+                found = true;
+            }
+        }
+
+        if found || self.truncated {
+            self.unblock(v);
+        } else {
+            for w in self.g.successors(Alt(v as u32)) {
+                if self.allowed[w] {
+                    self.blocked_by[w].insert(v);
+                }
+            }
+        }
+
+        self.stack.pop();
+        found
+    }
 }
 
-fn find_cycles(g : &Graph) -> HashSet<Cycle> {
-    let mut untouched : HashSet<Alt> = Alt::all(g.vertices).collect();
-    let mut result = HashSet::new();
+// all elementary cycles, or only the first `limit` of them,
+// along with whether the enumeration has been cut short
+fn find_cycles(g : &Graph, limit : Option<u32>, cancel : &CancelFlag) -> Result<(HashSet<Cycle>, bool)> {
+    let n = g.vertices as usize;
+    let mut circuits = Circuits {
+        g,
+        allowed: vec![false; n],
+        blocked: vec![false; n],
+        blocked_by: vec![HashSet::new(); n],
+        stack: Vec::new(),
+        cycles: HashSet::new(),
+        limit: limit.map(|limit| limit as usize),
+        truncated: false,
+    };
+
+    for root in 0..n {
+        if cancel.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+
+        if circuits.truncated {
+            break;
+        }
 
-    // pop a vertex
-    while let Some(&root) = untouched.iter().next() {
-        // create history for this run
-        let mut history = Vec::new();
+        // the component of the root among the vertices root..n
+        let components = graph::strongly_connected_components(n - root, &|v| {
+            g.successors(Alt((root + v) as u32)).into_iter().filter(
+                |&w| w >= root
+            ).map(|w| w - root).collect()
+        });
+        let component = components.iter().find(|c| c.contains(&0)).unwrap();  // root is somewhere
+        if component.len() < 2 {
+            continue;
+        }
 
-        // launch loop search
-        let mut new_cycles = find_cycles_from(&mut untouched, g, &mut history, root);
-        result.extend(new_cycles.drain());
+        for v in 0..n {
+            circuits.allowed[v] = false;
+            circuits.blocked[v] = false;
+            circuits.blocked_by[v].clear();
+        }
+        for &v in component {
+            circuits.allowed[root + v] = true;
+        }
+
+        circuits.circuit(root, root);
     }
 
-    result
+    Ok((circuits.cycles, circuits.truncated))
 }
 
 #[derive(Debug)]
 pub enum Error {
-    TooManyTuples,
+    Cancelled,
 }
 
 impl Encode for Error {
     fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
         match self {
-            Error::TooManyTuples => 0u8.encode(f)            
+            Error::Cancelled => 0u8.encode(f),
         }
     }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "too many tuples")
+        match self {
+            Error::Cancelled => write!(f, "cancelled"),
+        }
     }
 }
 
@@ -311,13 +420,28 @@ pub type Result<T> = result::Result<T, Error>;
 
 #[derive(Debug)]
 pub struct Request {
-    subject : Packed<Subject>,
+    pub subject : Packed<Subject>,
+
+    /// Enumerate at most this many cycles of every revealed preference graph.
+    /// Without a limit, `run` skips the enumeration and reports no rows
+    /// while the tuple listings enumerate everything.
+    pub cycle_limit : Option<u32>,
 }
 
 impl Decode for Request {
     fn decode<R : Read>(f : &mut R) -> codec::Result<Request> {
         Ok(Request {
             subject: Decode::decode(f)?,
+            cycle_limit: Decode::decode(f)?,
+        })
+    }
+}
+
+impl FromJson for Request {
+    fn from_json(js : &Json) -> json::Result<Request> {
+        Ok(Request {
+            subject: json::field(js, "subject")?,
+            cycle_limit: json::field(js, "cycle_limit")?,
         })
     }
 }
@@ -356,24 +480,154 @@ impl Encode for Row {
     }
 }
 
+impl ToJson for Row {
+    fn to_json(&self) -> Json {
+        json::object(vec![
+            ("cycle_length", self.cycle_length.to_json()),
+            ("garp", self.garp.to_json()),
+            ("sarp", self.sarp.to_json()),
+            ("garp_binary_menus", self.garp_binary_menus.to_json()),
+            ("sarp_binary_menus", self.sarp_binary_menus.to_json()),
+        ])
+    }
+}
+
+// whether the data pass each test; found without enumerating cycles
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Verdict {
+    garp : bool,
+    sarp : bool,
+    garp_binary_menus : bool,
+    sarp_binary_menus : bool,
+}
+
+impl Encode for Verdict {
+    fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+        (
+            self.garp,
+            self.sarp,
+            self.garp_binary_menus,
+            self.sarp_binary_menus,
+        ).encode(f)
+    }
+}
+
+impl ToJson for Verdict {
+    fn to_json(&self) -> Json {
+        json::object(vec![
+            ("garp", self.garp.to_json()),
+            ("sarp", self.sarp.to_json()),
+            ("garp_binary_menus", self.garp_binary_menus.to_json()),
+            ("sarp_binary_menus", self.sarp_binary_menus.to_json()),
+        ])
+    }
+}
+
+/// Houtman and Maks: the fewest choice rows whose removal makes the data consistent.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct HoutmanMaks {
+    count : u32,
+    removed : Vec<u32>,  // choice rows, ascending
+    exact : bool,  // otherwise `count` is only an upper bound
+}
+
+impl Encode for HoutmanMaks {
+    fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+        (&self.count, &self.removed, self.exact).encode(f)
+    }
+}
+
+// choice rows numbered from 1, like budgetary observations
+impl ToJson for HoutmanMaks {
+    fn to_json(&self) -> Json {
+        json::object(vec![
+            ("count", self.count.to_json()),
+            ("removed", self.removed.iter().map(|&i| i + 1).collect::<Vec<u32>>().to_json()),
+            ("exact", self.exact.to_json()),
+        ])
+    }
+}
+
+// a strongly connected component of the revealed preference graph
+// with at least two alternatives
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Component {
+    alternatives : AltSet,
+    strict : bool,  // contains a strict preference, i.e. violates GARP
+}
+
+impl Encode for Component {
+    fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+        (&self.alternatives, self.strict).encode(f)
+    }
+}
+
+impl Component {
+    fn json_with(&self, alternatives : Option<&[String]>) -> Json {
+        json::object(vec![
+            ("alternatives", json::alt_set(&self.alternatives, alternatives)),
+            ("strict", self.strict.to_json()),
+        ])
+    }
+}
+
 pub struct Response {
     subject_name : String,
-    rows : Vec<Row>,
+    verdict : Verdict,
+    hm_garp : HoutmanMaks,
+    hm_sarp : HoutmanMaks,
+    components : Vec<Component>,
     warp_pairs : u32,
     warp : Integer,
+    rows : Vec<Row>,  // empty without a cycle limit
+    truncated : bool,  // some cycles were not enumerated
 }
 
 impl Encode for Response {
     fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
         (
             &self.subject_name,
-            &self.rows,
+            &self.verdict,
+            &self.hm_garp,
+            &self.hm_sarp,
+            &self.components,
             &self.warp_pairs,
             &self.warp,
+            &self.rows,
+            self.truncated,
         ).encode(f)
     }
 }
 
+impl Response {
+    /// With the alternatives by name rather than by index.
+    pub fn to_named_json(&self, alternatives : &[String]) -> Json {
+        self.json_with(Some(alternatives))
+    }
+
+    fn json_with(&self, alternatives : Option<&[String]>) -> Json {
+        json::object(vec![
+            ("subject", self.subject_name.to_json()),
+            ("verdict", self.verdict.to_json()),
+            ("hm_garp", self.hm_garp.to_json()),
+            ("hm_sarp", self.hm_sarp.to_json()),
+            ("components", Json::Array(self.components.iter().map(
+                |c| c.json_with(alternatives)
+            ).collect())),
+            ("warp_pairs", self.warp_pairs.to_json()),
+            ("warp", self.warp.to_json()),
+            ("rows", self.rows.to_json()),
+            ("truncated", self.truncated.to_json()),
+        ])
+    }
+}
+
+impl ToJson for Response {
+    fn to_json(&self) -> Json {
+        self.json_with(None)
+    }
+}
+
 trait MakeEmpty {
     fn make_empty(size : u32) -> Self;
 }
@@ -435,14 +689,159 @@ fn compute_warp_pairs(alt_count : u32, g_strict : &Graph, g_non_strict : &Graph)
     menu_pairs.len() as u32
 }
 
-pub fn run(request : &Request) -> Result<Response> {
+fn compute_warp(alt_count : u32, g_strict : &Graph, g_non_strict : &Graph) -> Integer {
+    let mut warp = zero();
+
+    for v in Alt::all(alt_count) {
+        for w in Alt::all(alt_count) {
+            if v < w && g_non_strict.has_edge(v, w) && g_non_strict.has_edge(w, v) {
+                warp += Cycle::new(&[v, w]).garp_multiplicity_in(g_strict, g_non_strict);
+            }
+        }
+    }
+
+    warp
+}
+
+fn compute_components(g_strict : &Graph, g_non_strict : &Graph) -> Vec<Component> {
+    let components = graph::strongly_connected_components(
+        g_non_strict.vertices as usize, &|v| g_non_strict.successors(Alt(v as u32))
+    );
+
+    let mut result : Vec<Component> = components.into_iter().filter(|c| c.len() >= 2).map(|c| {
+        let alternatives = AltSet::from_iter(c.iter().map(|&v| Alt(v as u32)));
+        let strict = c.iter().any(
+            |&v| g_strict.successors(Alt(v as u32)).into_iter().any(|w| c.contains(&w))
+        );
+        Component{alternatives, strict}
+    }).collect();
+
+    result.sort_by(|c, d| c.alternatives.cmp(&d.alternatives));
+    result
+}
+
+/* The Houtman-Maks removal is found by implicit hitting sets:
+ * remove the fewest choice rows that break all violations found so far
+ * and look for another violation among the remaining rows.
+ * Once there is none, the removal is optimal because any removal
+ * must break at least the violations found. With too many violations
+ * or too many rows to remove, the exact cover would take too long
+ * so the greedy cover stands in for it and is then only extended
+ * row by row, giving an upper bound.
+ */
+const MAX_EXACT_VIOLATIONS : usize = 64;
+const MAX_EXACT_REMOVED : usize = 8;
+
+fn houtman_maks<F>(alt_count : u32, choices : &[ChoiceRow], violation : F, cancel : &CancelFlag) -> Result<HoutmanMaks>
+    where F : Fn(&Graph, &Graph) -> Option<Vec<Edge>>  // (strict, non_strict) -> choice rows
+{
+    let remaining_violation = |removed : &HashSet<usize>| {
+        let mut strict = Graph::new(alt_count);
+        let mut non_strict = Graph::new(alt_count);
+
+        for (idx, cr) in choices.iter().enumerate() {
+            if !removed.contains(&idx) {
+                add_choice_row(&mut strict, &mut non_strict, cr, &Edge(idx as u32));
+            }
+        }
+
+        violation(&strict, &non_strict)
+    };
+
+    // for each choice row, the violations it takes part in
+    let mut violations_per_row = vec![HashSet::new(); choices.len()];
+    let mut violation_count = 0;
+    let mut removed = HashSet::new();
+    let mut exact = true;
+
+    while let Some(rows) = remaining_violation(&removed) {
+        if cancel.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+
+        let rows_of_violation : Vec<usize> = rows.into_iter().map(|Edge(cr)| cr as usize).collect();
+        for &cr in &rows_of_violation {
+            violations_per_row[cr].insert(violation_count);
+        }
+        violation_count += 1;
+
+        if exact {
+            let greedy = set_cover::greedy(&violations_per_row);
+            exact = violation_count <= MAX_EXACT_VIOLATIONS && greedy.len() <= MAX_EXACT_REMOVED;
+            removed = if exact {
+                set_cover::exact(&violations_per_row)
+            } else {
+                greedy
+            };
+        } else {
+            // the row of the new violation that takes part in the most violations
+            let cr = rows_of_violation.into_iter().max_by_key(
+                |&cr| violations_per_row[cr].len()
+            ).unwrap();  // a cycle has rows
+            removed.insert(cr);
+        }
+    }
+
+    if !exact {
+        // put back the rows that the greedy cover did not need after all
+        for cr in sort(removed.clone()) {
+            removed.remove(&cr);
+            if remaining_violation(&removed).is_some() {
+                removed.insert(cr);
+            }
+        }
+    }
+
+    Ok(HoutmanMaks {
+        count: removed.len() as u32,
+        removed: sort(removed.into_iter().map(|cr| cr as u32)),
+        exact,
+    })
+}
+
+pub fn run(request : &Request, cancel : &CancelFlag) -> Result<Response> {
     let ref subject = request.subject.unpack();
     let alt_count = subject.alternatives.len() as u32;
     let choices = &subject.choices;
 
     let (g_strict, g_non_strict) = build_graphs(alt_count, choices);
-    let cycles_non_strict = find_cycles(&g_non_strict);  // will be used for GARP
-    let cycles_strict = find_cycles(&g_strict);
+    let choices_binary = Vec::from_iter(
+        choices.iter().filter(|c| c.menu.size() == 2).cloned()
+    );
+    let (g_strict_binary, g_non_strict_binary) = build_graphs(alt_count, &choices_binary);
+
+    // everything up to the cycle enumeration takes polynomial time
+    let verdict = Verdict {
+        garp: !g_non_strict.has_cycle_through(&g_strict),
+        sarp: !g_strict.has_cycle_through(&g_strict),
+        garp_binary_menus: !g_non_strict_binary.has_cycle_through(&g_strict_binary),
+        sarp_binary_menus: !g_strict_binary.has_cycle_through(&g_strict_binary),
+    };
+
+    let hm_garp = houtman_maks(alt_count, choices, |strict, non_strict| non_strict.cycle_through(strict), cancel)?;
+    let hm_sarp = houtman_maks(alt_count, choices, |strict, _| strict.cycle_through(strict), cancel)?;
+
+    let components = compute_components(&g_strict, &g_non_strict);
+    let warp = compute_warp(alt_count, &g_strict, &g_non_strict);
+    let warp_pairs = compute_warp_pairs(alt_count, &g_strict, &g_non_strict);
+
+    let limit = match request.cycle_limit {
+        None => return Ok(Response {
+            subject_name: subject.name.clone(),
+            verdict,
+            hm_garp,
+            hm_sarp,
+            components,
+            warp_pairs,
+            warp,
+            rows: Vec::new(),
+            truncated: false,
+        }),
+        Some(limit) => Some(limit),
+    };
+
+    let (cycles_non_strict, truncated_non_strict) = find_cycles(&g_non_strict, limit, cancel)?;  // will be used for GARP
+    let (cycles_strict, truncated_strict) = find_cycles(&g_strict, limit, cancel)?;
     let mut rows = BTreeMap::new();
 
     // SARP (includes 2-cycles)
@@ -452,10 +851,6 @@ pub fn run(request : &Request) -> Result<Response> {
         |r : &mut Row, c| r.sarp += c.multiplicity_in(&g_strict)
     );
 
-    let warp = cycles_non_strict.iter().filter(|c| c.len() == 2).map(
-        |c| c.garp_multiplicity_in(&g_strict, &g_non_strict)
-    ).sum();
-
     // GARP (includes 2-cycles)
     summarise(
         &mut rows,
@@ -463,15 +858,8 @@ pub fn run(request : &Request) -> Result<Response> {
         |r, c| r.garp += c.garp_multiplicity_in(&g_strict, &g_non_strict)
     );
 
-    let warp_pairs = compute_warp_pairs(alt_count, &g_strict, &g_non_strict);
-
-    let choices_binary = Vec::from_iter(
-        choices.iter().filter(|c| c.menu.size() == 2).cloned()
-    );
-
-    let (g_strict_binary, g_non_strict_binary) = build_graphs(alt_count, &choices_binary);
-    let cycles_strict_binary = find_cycles(&g_strict_binary);
-    let cycles_non_strict_binary = find_cycles(&g_non_strict_binary);
+    let (cycles_strict_binary, truncated_strict_binary) = find_cycles(&g_strict_binary, limit, cancel)?;
+    let (cycles_non_strict_binary, truncated_non_strict_binary) = find_cycles(&g_non_strict_binary, limit, cancel)?;
 
     // garp_binary
     summarise(
@@ -489,9 +877,15 @@ pub fn run(request : &Request) -> Result<Response> {
 
     Ok(Response {
         subject_name: subject.name.clone(),
-        rows: rows.into_iter().map(|(_l,r)| r).collect(),
-        warp,
+        verdict,
+        hm_garp,
+        hm_sarp,
+        components,
         warp_pairs,
+        warp,
+        rows: rows.into_iter().map(|(_l,r)| r).collect(),
+        truncated: truncated_non_strict || truncated_strict
+            || truncated_strict_binary || truncated_non_strict_binary,
     })
 }
 
@@ -504,6 +898,7 @@ pub fn sort<I : IntoIterator<Item=T>, T : Ord>(items : I) -> Vec<T> {
 pub mod tuple_intrans {
     use super::{Request,Result,Error,Cycle,Graph,Edge,HasSize,MakeEmpty};
     use super::{build_graphs,find_cycles,summarise};
+    use rpc_common::CancelFlag;
     use std::collections::{BTreeSet,HashSet,BTreeMap};
     use alt_set::AltSet;
     use alt::Alt;
@@ -513,6 +908,11 @@ pub mod tuple_intrans {
     use codec::{self,Encode};
     use std::io::Write;
     use std::fmt::Debug;
+    use json::{self,Json,ToJson};
+
+    // the number of tuples of a cycle grows exponentially with its length
+    // so longer cycles are left out, like those beyond the cycle limit
+    const MAX_CYCLE_LENGTH : u32 = 24;
 
     pub struct RowMenus {
         tuple_size : u32,
@@ -557,11 +957,75 @@ pub mod tuple_intrans {
     pub struct Response<R> {
         subject_name : String,
         rows : Vec<R>,
+        truncated : bool,  // some cycles were left out
     }
 
     impl<R : Encode> Encode for Response<R> {
         fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
-            (&self.subject_name, &self.rows).encode(f)
+            (&self.subject_name, &self.rows, self.truncated).encode(f)
+        }
+    }
+
+    // the tuples are sorted to make the output deterministic
+    impl Response<RowMenus> {
+        /// With the alternatives by name rather than by index.
+        pub fn to_named_json(&self, alternatives : &[String]) -> Json {
+            self.json_with(Some(alternatives))
+        }
+
+        fn json_with(&self, alternatives : Option<&[String]>) -> Json {
+            json::object(vec![
+                ("subject", self.subject_name.to_json()),
+                ("rows", Json::Array(self.rows.iter().map(|r| {
+                    let mut tuples = Vec::from_iter(&r.garp_menu_tuples);
+                    tuples.sort();
+                    json::object(vec![
+                        ("tuple_size", r.tuple_size.to_json()),
+                        ("garp_menu_tuples", Json::Array(tuples.into_iter().map(
+                            |menus| Json::Array(menus.iter().map(
+                                |menu| json::alt_set(menu, alternatives)
+                            ).collect())
+                        ).collect())),
+                    ])
+                }).collect())),
+                ("truncated", self.truncated.to_json()),
+            ])
+        }
+    }
+
+    impl Response<RowAlts> {
+        /// With the alternatives by name rather than by index.
+        pub fn to_named_json(&self, alternatives : &[String]) -> Json {
+            self.json_with(Some(alternatives))
+        }
+
+        fn json_with(&self, alternatives : Option<&[String]>) -> Json {
+            json::object(vec![
+                ("subject", self.subject_name.to_json()),
+                ("rows", Json::Array(self.rows.iter().map(|r| {
+                    let mut tuples = Vec::from_iter(&r.garp_alt_tuples);
+                    tuples.sort();
+                    json::object(vec![
+                        ("tuple_size", r.tuple_size.to_json()),
+                        ("garp_alt_tuples", Json::Array(tuples.into_iter().map(
+                            |alts| json::alt_set(alts, alternatives)
+                        ).collect())),
+                    ])
+                }).collect())),
+                ("truncated", self.truncated.to_json()),
+            ])
+        }
+    }
+
+    impl ToJson for Response<RowMenus> {
+        fn to_json(&self) -> Json {
+            self.json_with(None)
+        }
+    }
+
+    impl ToJson for Response<RowAlts> {
+        fn to_json(&self) -> Json {
+            self.json_with(None)
         }
     }
 
@@ -664,17 +1128,22 @@ pub mod tuple_intrans {
         paths_uncond
     }
 
-    pub fn run_menus(request : &Request) -> Result<Response<RowMenus>> {
+    pub fn run_menus(request : &Request, cancel : &CancelFlag) -> Result<Response<RowMenus>> {
         let ref subject = request.subject.unpack();
         let alt_count = subject.alternatives.len() as u32;
         let (g_strict, g_non_strict) = build_graphs(alt_count, &subject.choices);
-        let cycles_non_strict = find_cycles(&g_non_strict);
+        let (cycles_non_strict, mut truncated) = find_cycles(&g_non_strict, request.cycle_limit, cancel)?;
 
         let mut by_length = BTreeMap::new();
 
         for cycle in cycles_non_strict.iter().filter(|c| c.has_edge_in(&g_strict)) {
-            if cycle.len() > 24 {
-                return Err(Error::TooManyTuples);
+            if cancel.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+
+            if cycle.len() > MAX_CYCLE_LENGTH {
+                truncated = true;
+                continue;
             }
 
             // collect choice rows for that cycle
@@ -696,20 +1165,26 @@ pub mod tuple_intrans {
             rows: by_length.into_iter().filter_map(
                 |(l,r)| if l > 0 { Some(r) } else { None }
             ).collect(),
+            truncated,
         })
     }
 
-    pub fn run_alts(request : &Request) -> Result<Response<RowAlts>> {
+    pub fn run_alts(request : &Request, cancel : &CancelFlag) -> Result<Response<RowAlts>> {
         let ref subject = request.subject.unpack();
         let alt_count = subject.alternatives.len() as u32;
         let (g_strict, g_non_strict) = build_graphs(alt_count, &subject.choices);
-        let cycles_non_strict = find_cycles(&g_non_strict);
+        let (cycles_non_strict, mut truncated) = find_cycles(&g_non_strict, request.cycle_limit, cancel)?;
 
         let mut by_length = BTreeMap::new();
 
         for cycle in cycles_non_strict.iter().filter(|c| c.has_edge_in(&g_strict)) {
-            if cycle.len() > 24 {
-                return Err(Error::TooManyTuples);
+            if cancel.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+
+            if cycle.len() > MAX_CYCLE_LENGTH {
+                truncated = true;
+                continue;
             }
 
             // collect alternatives for that cycle
@@ -728,6 +1203,7 @@ pub mod tuple_intrans {
             rows: by_length.into_iter().filter_map(
                 |(l,r)| if l > 0 { Some(r) } else { None }
             ).collect(),
+            truncated,
         })
     }
 
@@ -741,7 +1217,7 @@ pub mod tuple_intrans {
                 name: String::from("subject"),
                 alternatives: (0..alt_count).map(|s| s.to_string()).collect(),
                 choices,
-            })}
+            }), cycle_limit: None}
         }
 
         #[test]
@@ -754,14 +1230,14 @@ pub mod tuple_intrans {
             ];
 
             let (strict, non_strict) = build_graphs(5, &choices);
-            let cycles_non_strict = find_cycles(&non_strict);
-            let cycles_strict = find_cycles(&strict);
+            let cycles_non_strict = find_cycles(&non_strict, None, &CancelFlag::new()).unwrap().0;
+            let cycles_strict = find_cycles(&strict, None, &CancelFlag::new()).unwrap().0;
             assert_eq!(cycles_non_strict.len(), 1);
             assert_eq!(cycles_strict.len(), 1);
 
             let request = testreq(4, choices);
-            let response_menus = super::run_menus(&request).unwrap();
-            let response_alts = super::run_alts(&request).unwrap();
+            let response_menus = super::run_menus(&request, &CancelFlag::new()).unwrap();
+            let response_alts = super::run_alts(&request, &CancelFlag::new()).unwrap();
 
             // 3->0, 4->1
             assert_eq!(
@@ -789,7 +1265,7 @@ mod test {
             name: String::from("subject"),
             alternatives: (0..alt_count).map(|s| s.to_string()).collect(),
             choices,
-        })}
+        }), cycle_limit: Some(1000)}
     }
 
     #[test]
@@ -801,7 +1277,7 @@ mod test {
         ];
 
         let request = testreq(3, choices);
-        let response = super::run(&request).unwrap();
+        let response = super::run(&request, &CancelFlag::new()).unwrap();
 
         assert_eq!(response.rows, vec![
             Row{
@@ -831,7 +1307,7 @@ mod test {
         ];
 
         let request = testreq(4, choices);
-        let response = super::run(&request).unwrap();
+        let response = super::run(&request, &CancelFlag::new()).unwrap();
 
         assert_eq!(response.warp_pairs, 0);
     }
@@ -857,7 +1333,7 @@ mod test {
         ];
 
         let request = testreq(6, choices);
-        let detailed = run(&request).unwrap();
+        let detailed = run(&request, &CancelFlag::new()).unwrap();
 
         assert_eq!(
             detailed.rows.into_iter().map(|r| r.garp_binary_menus).sum::<Integer>(),
@@ -879,7 +1355,7 @@ mod test {
         let (strict, non_strict) = build_graphs(5, &choices);
         assert!(strict.has_edge(Alt(1), Alt(2)));
 
-        let cycles = find_cycles(&non_strict);
+        let cycles = find_cycles(&non_strict, None, &CancelFlag::new()).unwrap().0;
 
         assert_eq!(
             sort(cycles),
@@ -924,7 +1400,7 @@ mod test {
         ];
 
         let request = testreq(5, choices);
-        let detailed = run(&request).unwrap();
+        let detailed = run(&request, &CancelFlag::new()).unwrap();
 
         assert_eq!(
             column(&detailed.rows, |r| r.sarp.clone()),
@@ -954,7 +1430,7 @@ mod test {
             [1,2,3] -> [3]  // collides with [2,3] -> 2, okay with [1,2] -> 2 and [1,3] -> 3
         ];
         let request = testreq(5, choices);
-        let response = run(&request).unwrap();
+        let response = run(&request, &CancelFlag::new()).unwrap();
 
         assert_eq!(response.warp_pairs, 1);
     }
@@ -967,12 +1443,98 @@ mod test {
         ];
 
         let request = testreq(5, choices);
-        let response = run(&request).unwrap();
+        let response = run(&request, &CancelFlag::new()).unwrap();
 
         assert_eq!(response.warp_pairs, 1);
         assert_eq!(response.warp, Integer::from(2));
     }
 
+    // the request without the rows removed by Houtman-Maks
+    fn without(request : &Request, hm : &HoutmanMaks) -> Request {
+        let subject = request.subject.unpack();
+        Request {
+            subject: codec::Packed(Subject {
+                choices: subject.choices.iter().enumerate().filter(
+                    |&(i, _)| !hm.removed.contains(&(i as u32))
+                ).map(|(_, cr)| cr.clone()).collect(),
+                ..subject.clone()
+            }),
+            cycle_limit: request.cycle_limit,
+        }
+    }
+
+    #[test]
+    fn houtman_maks() {
+        let request = testreq(3, choices![
+            [0,1] -> [0],
+            [1,2] -> [1],
+            [0,2] -> [2],
+            [0,1] -> [0,1]
+        ]);
+        let response = run(&request, &CancelFlag::new()).unwrap();
+
+        // violations {0,1,2}, {0,3} and {1,2,3}; no single row is in all of them
+        assert_eq!((response.hm_garp.count, response.hm_garp.exact), (2, true));
+        assert_eq!((response.hm_sarp.count, response.hm_sarp.exact), (1, true));
+
+        let verdict = run(&without(&request, &response.hm_garp), &CancelFlag::new()).unwrap().verdict;
+        assert!(verdict.garp && verdict.sarp);
+        let verdict = run(&without(&request, &response.hm_sarp), &CancelFlag::new()).unwrap().verdict;
+        assert!(!verdict.garp && verdict.sarp);
+
+        let request = testreq(3, choices![[0,1] -> [0,1]]);
+        let response = run(&request, &CancelFlag::new()).unwrap();
+        assert_eq!(response.hm_garp, HoutmanMaks{count: 0, removed: vec![], exact: true});
+    }
+
+    #[test]
+    fn verdict_without_enumeration() {
+        // a cyclic tournament: i beats the next 15 alternatives round the circle,
+        // with far too many cycles to enumerate
+        let n = 31;
+        let choices = (0..n).flat_map(|i| (1..16).map(move |d| ChoiceRow{
+            menu: AltSet::from_iter(&[Alt(i), Alt((i + d) % n)]),
+            default: None,
+            choice: AltSet::from_iter(&[Alt(i)]),
+        })).collect();
+
+        let mut request = testreq(n + 1, choices);
+        request.cycle_limit = None;
+        let response = run(&request, &CancelFlag::new()).unwrap();
+
+        assert_eq!(response.verdict, Verdict{
+            garp: false, sarp: false, garp_binary_menus: false, sarp_binary_menus: false,
+        });
+        assert_eq!(response.components, vec![Component{
+            alternatives: AltSet::from_iter(Alt::all(n)),
+            strict: true,
+        }]);
+        assert_eq!(response.warp_pairs, 0);
+        assert!(response.rows.is_empty() && !response.truncated);
+        assert!(!response.hm_sarp.exact);
+        assert!(run(&without(&request, &response.hm_sarp), &CancelFlag::new()).unwrap().verdict.sarp);
+
+        request.cycle_limit = Some(100);
+        let response = run(&request, &CancelFlag::new()).unwrap();
+        assert!(response.truncated);
+        assert!(response.rows.iter().map(|r| r.sarp.clone()).sum::<Integer>() <= Integer::from(100));
+
+        // indifference alone is consistent but still forms a component
+        let request = testreq(5, choices![
+            [0,1] -> [0,1],
+            [2,3] -> [2]
+        ]);
+        let response = run(&request, &CancelFlag::new()).unwrap();
+        assert_eq!(response.verdict, Verdict{
+            garp: true, sarp: true, garp_binary_menus: true, sarp_binary_menus: true,
+        });
+        assert_eq!(response.components, vec![Component{
+            alternatives: AltSet::from_iter(&[Alt(0), Alt(1)]),
+            strict: false,
+        }]);
+        assert!(!response.truncated);
+    }
+
     #[test]
     fn rotation() {
         assert_eq!(rotate_min(&[3,1,2,4]), &[1,2,4,3]);
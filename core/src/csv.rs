@@ -2,8 +2,11 @@ use std::io::Read;
 use std::marker::PhantomData;
 use std::collections::HashSet;
 
+use std::fmt;
+
 use alt::Alt;
 use alt_set::AltSet;
+use rpc_common::ChoiceRow;
 
 pub trait FromRow {
     type ParseError;
@@ -57,6 +60,48 @@ impl FromCell for AltSet {
     }
 }
 
+#[derive(Debug)]
+pub enum ChoiceRowError {
+    MissingColumns,
+    MultipleDefaults(String),
+}
+
+impl fmt::Display for ChoiceRowError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ChoiceRowError::MissingColumns => write!(f, "choice data need the columns menu and choice"),
+            ChoiceRowError::MultipleDefaults(ref cell) => write!(f, "more than one default: {}", cell),
+        }
+    }
+}
+
+// the default column is optional
+impl FromRow for ChoiceRow {
+    type ParseError = ChoiceRowError;
+    const COLUMN_NAMES : &'static[&'static str] = &["menu", "default", "choice"];
+
+    fn from_row(alternatives : &mut Vec<String>, row : &[&str]) -> Result<ChoiceRow, ChoiceRowError> {
+        let void = |e : Void| match e {};
+        let (menu_s, default_s, choice_s) = match *row {
+            [menu_s, default_s, choice_s] => (menu_s, default_s, choice_s),
+            [menu_s, choice_s] => (menu_s, "", choice_s),
+            _ => return Err(ChoiceRowError::MissingColumns),
+        };
+
+        let menu = AltSet::from_cell(alternatives, menu_s).map_err(void)?;
+        let default = AltSet::from_cell(alternatives, default_s).map_err(void)?;
+        if default.view().size() > 1 {
+            return Err(ChoiceRowError::MultipleDefaults(String::from(default_s)));
+        }
+
+        Ok(ChoiceRow {
+            menu,
+            default: default.view().iter().next(),
+            choice: AltSet::from_cell(alternatives, choice_s).map_err(void)?,
+        })
+    }
+}
+
 impl ToCell for AltSet {
     fn to_cell(&self, alternatives : &[String]) -> String {
         self.view().into_iter().map(
@@ -85,6 +130,28 @@ pub enum Error<SubE, RowE> {
     NoNameColumn(String),
 }
 
+impl fmt::Display for Void {
+    fn fmt(&self, _f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
+
+impl<S : fmt::Display, R : fmt::Display> fmt::Display for Error<S,R> {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::IO(ref e) => write!(f, "{}", e),
+            Error::Csv(ref e) => write!(f, "{}", e),
+            Error::ColumnOverlap(ref col) => write!(f, "column {} is used twice", col),
+            Error::ParseSub(ref e) => write!(f, "{}", e),
+            Error::ParseRow(ref e) => write!(f, "{}", e),
+            Error::RowTooShort(ix) => write!(f, "row too short: no column {}", ix + 1),
+            Error::SubjectDiscontiguous(ref name) => write!(f, "rows of subject {} are not contiguous", name),
+            Error::SubjectInconsistent(ref name) => write!(f, "inconsistent data for subject {}", name),
+            Error::NoNameColumn(ref col) => write!(f, "missing column {}", col),
+        }
+    }
+}
+
 impl<S,R> From<csv::Error> for Error<S,R> { fn from(e : csv::Error) -> Self { Error::Csv(e) } }
 impl<S,R> From<std::io::Error> for Error<S,R> { fn from(e : std::io::Error) -> Self { Error::IO(e) } }
 
@@ -240,6 +307,10 @@ pub fn read_subjects<R, Sub, Row>(rdr : R, subj_name_column : &str)
         }
     }
 
+    // pass the cells in the order of COLUMN_NAMES rather than the order of the header
+    ixs_sub.sort_by_key(|&ix| Sub::COLUMN_NAMES.iter().position(|&c| c == &headers[ix]));
+    ixs_row.sort_by_key(|&ix| Row::COLUMN_NAMES.iter().position(|&c| c == &headers[ix]));
+
     let ix_name = match headers.iter().position(|h| h == subj_name_column) {
         None => return Err(Error::NoNameColumn(String::from(subj_name_column))),
         Some(ix) => ix,
@@ -261,3 +332,28 @@ pub fn read_subjects<R, Sub, Row>(rdr : R, subj_name_column : &str)
         alternatives: Vec::new(),
     })
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::iter::FromIterator;
+
+    #[test]
+    fn choice_rows() {
+        let text = "choice,subject,menu\nb,s1,\"a,b\"\na,s2,\"a,c\"\n";
+        let subjects : Vec<Subject<(), ChoiceRow>> = read_subjects(text.as_bytes(), "subject").unwrap().map(
+            |s| s.unwrap()
+        ).collect();
+
+        assert_eq!(subjects.len(), 2);
+        assert_eq!(subjects[1].alternatives, vec!["a", "b", "c"]);
+        assert_eq!(subjects[0].rows[0].menu, alts![0, 1]);
+        assert_eq!(subjects[0].rows[0].default, None);
+        assert_eq!(subjects[0].rows[0].choice, alts![1]);
+
+        let text = "subject,menu,default,choice\ns1,\"a,b\",b,\n";
+        let subject : Subject<(), ChoiceRow> = read_subjects(text.as_bytes(), "subject").unwrap().next().unwrap().unwrap();
+        assert_eq!(subject.rows[0].default, Some(Alt(1)));
+        assert_eq!(subject.rows[0].choice, alts![]);
+    }
+}
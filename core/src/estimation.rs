@@ -1,13 +1,24 @@
 use std::collections::HashSet;
 use model::{self,Penalty,Model,Instance,PreorderParams};
 use precomputed::{self,Precomputed};
+use stochastic::{self,StochasticModel};
+use exact_estimation;
 use std::result;
 use std::fmt;
 use std::convert::From;
 use std::io::{Read,Write};
-use rpc_common::{Subject,ChoiceRow};
+use rpc_common::{Subject,ChoiceRow,CancelFlag};
+use alt_set::AltSet;
 use codec::{self,Encode,Decode,Packed};
+use json::{self,Json,ToJson,FromJson};
+use base64::prelude::BASE64_STANDARD;
+use base64::engine::Engine;
 use std::iter::FromIterator;
+use std::cell::Cell;
+use std::sync::mpsc;
+use std::thread;
+use rpc::Log;
+use std::cmp;
 use rayon::prelude::*;
 
 pub type Result<T> = result::Result<T, EstimationError>;
@@ -16,6 +27,8 @@ pub type Result<T> = result::Result<T, EstimationError>;
 pub enum EstimationError {
     InstanceError(model::InstanceError),
     PreorderError(precomputed::Error),
+    Cancelled,
+    StochasticError(stochastic::Error),
 }
 
 impl Encode for EstimationError {
@@ -23,6 +36,8 @@ impl Encode for EstimationError {
         match self {
             &EstimationError::InstanceError(ref e) => (0u8, e).encode(f),
             &EstimationError::PreorderError(ref e) => (1u8, e).encode(f),
+            &EstimationError::Cancelled => 2u8.encode(f),
+            &EstimationError::StochasticError(ref e) => (3u8, e).encode(f),
         }
     }
 }
@@ -32,28 +47,44 @@ impl fmt::Display for EstimationError {
         match self {
             &EstimationError::InstanceError(ref e) => e.fmt(f),
             &EstimationError::PreorderError(ref e) => e.fmt(f),
+            &EstimationError::Cancelled => write!(f, "cancelled"),
+            &EstimationError::StochasticError(ref e) => e.fmt(f),
         }
     }
 }
 
 impl From<model::InstanceError> for EstimationError {
     fn from(e : model::InstanceError) -> EstimationError {
-        EstimationError::InstanceError(e)
+        match e {
+            model::InstanceError::Cancelled => EstimationError::Cancelled,
+            e => EstimationError::InstanceError(e),
+        }
     }
 }
 
 impl From<precomputed::Error> for EstimationError {
     fn from(e : precomputed::Error) -> EstimationError {
-        EstimationError::PreorderError(e)
+        match e {
+            precomputed::Error::Cancelled => EstimationError::Cancelled,
+            e => EstimationError::PreorderError(e),
+        }
+    }
+}
+
+impl From<stochastic::Error> for EstimationError {
+    fn from(e : stochastic::Error) -> EstimationError {
+        EstimationError::StochasticError(e)
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Request {
-    subjects : Vec<Packed<Subject>>,
-    models : Vec<model::Model>,
-    disable_parallelism : bool,
-    disregard_deferrals : bool,
+    pub subjects : Vec<Packed<Subject>>,
+    pub models : Vec<model::Model>,
+    pub disable_parallelism : bool,
+    pub disregard_deferrals : bool,
+    pub diagnostics : bool,
+    pub stochastic_models : Vec<StochasticModel>,  // fitted alongside the deterministic models
 }
 
 impl Decode for Request {
@@ -63,6 +94,21 @@ impl Decode for Request {
             models: Decode::decode(f)?,
             disable_parallelism: Decode::decode(f)?,
             disregard_deferrals: Decode::decode(f)?,
+            diagnostics: Decode::decode(f)?,
+            stochastic_models: Decode::decode(f)?,
+        })
+    }
+}
+
+impl FromJson for Request {
+    fn from_json(js : &Json) -> json::Result<Request> {
+        Ok(Request {
+            subjects: json::field(js, "subjects")?,
+            models: json::field(js, "models")?,
+            disable_parallelism: json::field(js, "disable_parallelism")?,
+            disregard_deferrals: json::field(js, "disregard_deferrals")?,
+            diagnostics: json::field(js, "diagnostics")?,
+            stochastic_models: json::field(js, "stochastic_models")?,
         })
     }
 }
@@ -93,15 +139,69 @@ impl Encode for InstanceInfo {
     }
 }
 
+// the instance is given as the base64 code used by the GUI
+impl ToJson for InstanceInfo {
+    fn to_json(&self) -> Json {
+        json::object(vec![
+            ("model", self.model.to_string().to_json()),
+            ("penalty", self.penalty.to_json()),
+            ("instance", BASE64_STANDARD.encode(&self.instance).to_json()),
+        ])
+    }
+}
+
+/// A choice row that an instance fails to rationalise.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MissedRow {
+    pub row : u32,  // index into the choices of the subject
+    pub predicted : AltSet,  // the choice predicted by the instance
+}
+
+impl Encode for MissedRow {
+    fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+        (self.row, &self.predicted).encode(f)
+    }
+}
+
 pub struct Response {
     pub subject_name : String,
     pub score : Penalty,
     pub best_instances : Vec<InstanceInfo>,
+    pub diagnostics : Vec<Vec<MissedRow>>,  // for each best instance, if requested
+    pub stochastic_fits : Vec<stochastic::Fit>,  // one for each requested stochastic model
 }
 
 impl Encode for Response {
     fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
-        (&self.subject_name, self.score.clone(), &self.best_instances).encode(f)
+        (&self.subject_name, self.score.clone(), &self.best_instances, &self.diagnostics, &self.stochastic_fits).encode(f)
+    }
+}
+
+impl Response {
+    /// With the alternatives by name rather than by index.
+    pub fn to_named_json(&self, alternatives : &[String]) -> Json {
+        self.json_with(Some(alternatives))
+    }
+
+    fn json_with(&self, alternatives : Option<&[String]>) -> Json {
+        json::object(vec![
+            ("subject", self.subject_name.to_json()),
+            ("score", self.score.to_json()),
+            ("best_instances", self.best_instances.to_json()),
+            ("diagnostics", Json::Array(self.diagnostics.iter().map(
+                |rows| Json::Array(rows.iter().map(|mr| json::object(vec![
+                    ("row", mr.row.to_json()),
+                    ("predicted", json::alt_set(&mr.predicted, alternatives)),
+                ])).collect())
+            ).collect())),
+            ("stochastic_fits", self.stochastic_fits.to_json()),
+        ])
+    }
+}
+
+impl ToJson for Response {
+    fn to_json(&self) -> Json {
+        self.json_with(None)
     }
 }
 
@@ -118,6 +218,10 @@ impl BestInstances {
         }
     }
 
+    fn upper_bound(&self) -> Option<u32> {
+        self.lowest_penalty.as_ref().map(|p| p.upper_bound)
+    }
+
     fn upper_bound_for(&self, model : Model) -> Option<u32> {
         self.instances.iter().filter_map(|inst|
             if inst.model == model {
@@ -237,21 +341,69 @@ fn evaluate_model(
     model : Model,
     alt_count : u32,
     choices : &[ChoiceRow],
+    bound : Option<u32>,
+    cancel : &CancelFlag,
 ) -> Result<BestInstances> {
     let mut model_instances = BestInstances::new();
 
-    model::traverse_all(precomputed, model, alt_count, choices, &mut |inst| {
-        model_instances.add_instance(
-            model,
-            inst.penalty(choices),
-            inst,
-        )
+    if model == Model::PreorderMaximization(PreorderParams{strict: Some(true), total: Some(true)}) {
+        // linear orders are not enumerated; they are found by an exact search
+        let (penalty, orders) = exact_estimation::strict_preorder_maximization(alt_count, choices, cancel)
+            .map_err(|e| e.annotate(model))?;
+
+        for p in orders {
+            model_instances.add_instance(model, penalty.clone(), Instance::PreorderMaximization(p));
+        }
+
+        return Ok(model_instances);
+    }
+
+    if model == Model::SequentiallyRationalizableChoice {
+        // the exact search does its own pruning;
+        // with many alternatives, its penalty may be only a bound
+        let best = exact_estimation::sequentially_rationalizable_choice(
+            alt_count, choices, bound.unwrap_or(u32::MAX), cancel
+        ).map_err(|e| e.annotate(model))?;
+
+        if let Some((penalty, instances)) = best {
+            for (p, q) in instances {
+                model_instances.add_instance(model, penalty.clone(), Instance::SequentiallyRationalizableChoice(p, q));
+            }
+        }
+
+        return Ok(model_instances);
+    }
+
+    if model == Model::LimitedAttention {
+        // preferences are enumerated but attention filters are found by an exact search
+        let best = exact_estimation::limited_attention(alt_count, choices, bound.unwrap_or(u32::MAX), cancel)
+            .map_err(|e| e.annotate(model))?;
+
+        if let Some((penalty, instances)) = best {
+            for (p, filter) in instances {
+                model_instances.add_instance(model, penalty.clone(), Instance::LimitedAttention{p, filter});
+            }
+        }
+
+        return Ok(model_instances);
+    }
+
+    // instances whose lower bound exceeds this are forgotten anyway
+    let bound = Cell::new(bound.unwrap_or(u32::MAX));
+    model::traverse_all_pruned(precomputed, model, alt_count, choices, &bound, cancel, &mut |inst| {
+        if let Some(penalty) = inst.bounded_penalty(choices, bound.get()) {
+            model_instances.add_instance(model, penalty, inst);
+
+            if let Some(ref lowest_penalty) = model_instances.lowest_penalty {
+                bound.set(cmp::min(bound.get(), lowest_penalty.upper_bound));
+            }
+        }
     })?;
 
     Ok(model_instances)
 }
 
-pub fn run_one(precomputed : &Precomputed, subject : &Subject, models : &[Model]) -> Result<Response> {
+pub fn run_one(precomputed : &Precomputed, subject : &Subject, models : &[Model], cancel : &CancelFlag) -> Result<Response> {
     let alt_count = subject.alternatives.len() as u32;
 
     let mut best_instances = BestInstances::new();
@@ -261,8 +413,9 @@ pub fn run_one(precomputed : &Precomputed, subject : &Subject, models : &[Model]
             continue;
         }
 
+        let bound = best_instances.upper_bound();
         best_instances = best_instances.combine(
-            evaluate_model(precomputed, model, alt_count, &subject.choices)?
+            evaluate_model(precomputed, model, alt_count, &subject.choices, bound, cancel)?
         );
     }
 
@@ -280,12 +433,15 @@ pub fn run_one(precomputed : &Precomputed, subject : &Subject, models : &[Model]
         // then we have a multi-choice somewhere,
         // which breaks the requirements of SRC (and won't get 0).
     {
+        let bound = best_instances.upper_bound();
         best_instances = best_instances.combine(
             evaluate_model(
                 precomputed,
                 Model::SequentiallyRationalizableChoice,
                 alt_count,
-                &subject.choices
+                &subject.choices,
+                bound,
+                cancel,
             )?
         );
     }
@@ -297,30 +453,79 @@ pub fn run_one(precomputed : &Precomputed, subject : &Subject, models : &[Model]
         subject_name: subject.name.clone(),
         best_instances,
         score,
+        diagnostics: Vec::new(),
+        stochastic_fits: Vec::new(),
     })
 }
 
-pub fn run(precomputed : &mut Precomputed, request : &Request) -> Result<Vec<Packed<Response>>> {
+// the rows of `subject` missed by `instance`, skipping the disregarded deferrals
+pub fn missed_rows(instance : &Instance, subject : &Subject, disregard_deferrals : bool) -> Vec<MissedRow> {
+    subject.choices.iter().enumerate().filter(
+        |&(_, cr)| !(disregard_deferrals && cr.choice.view().is_empty())
+            && instance.row_penalty(cr) > 0
+    ).map(|(i, cr)| MissedRow {
+        row: i as u32,
+        predicted: instance.choice(cr.menu.view(), cr.default),
+    }).collect()
+}
+
+/// Prepares the preorders needed to estimate `models` with `alt_count` alternatives.
+pub fn precompute_for(precomputed : &mut Precomputed, alt_count : u32, models : &[Model]) {
+    // don't precompute if searching only permutations (strict UM)
+    if models != [Model::PreorderMaximization(PreorderParams{strict:Some(true),total:Some(true)})] {
+        precomputed.precompute(alt_count);
+    }
+}
+
+fn precompute(precomputed : &mut Precomputed, request : &Request) {
     // precompute up to the maximum number of alternatives
     let alt_count = request.subjects.iter().map(
         |subj| subj.unpack().alternatives.len() as u32
     ).max().expect("zero subjects in request");
 
-    // don't precompute if searching only permutations (strict UM)
-    if request.models != &[Model::PreorderMaximization(PreorderParams{strict:Some(true),total:Some(true)})] {
-        precomputed.precompute(alt_count)?;
+    precompute_for(precomputed, alt_count, &request.models);
+}
+
+// estimate one subject of the request unless the request has been cancelled
+fn run_subject(precomputed : &Precomputed, request : &Request, subject : &Packed<Subject>, cancel : &CancelFlag)
+    -> Result<Response>
+{
+    if cancel.is_cancelled() {
+        return Err(EstimationError::Cancelled);
     }
 
+    let subject = subject.unpack();
+    let mut response = run_one(precomputed, &subject.drop_deferrals(request.disregard_deferrals), &request.models, cancel)?;
+
+    if request.diagnostics {
+        response.diagnostics = response.best_instances.iter().map(|info| {
+            let instance : Instance = codec::decode_from_memory(&info.instance)
+                .expect("could not decode an instance we have just encoded");
+            missed_rows(&instance, subject, request.disregard_deferrals)
+        }).collect();
+    }
+
+    if !request.stochastic_models.is_empty() {
+        // the stochastic models ignore deferrals anyway
+        response.stochastic_fits = stochastic::run_one(subject, &request.stochastic_models)?.fits;
+    }
+
+    Ok(response)
+}
+
+pub fn run(precomputed : &mut Precomputed, request : &Request, cancel : &CancelFlag) -> Result<Vec<Packed<Response>>> {
+    precompute(precomputed, request);
+
     let results : Vec<Result<Response>> = if request.disable_parallelism {
         // run estimation sequentially
         request.subjects.iter().map(
-            |subj| run_one(precomputed, &subj.unpack().drop_deferrals(request.disregard_deferrals), &request.models)
+            |subj| run_subject(precomputed, request, subj, cancel)
         ).collect()
     } else {
         // run estimation in parallel
         let mut results = Vec::new();
         request.subjects.par_iter().map(
-            |subj| run_one(precomputed, &subj.unpack().drop_deferrals(request.disregard_deferrals), &request.models)
+            |subj| run_subject(precomputed, request, subj, cancel)
         ).collect_into_vec(&mut results);
         results
     };
@@ -334,6 +539,64 @@ pub fn run(precomputed : &mut Precomputed, request : &Request) -> Result<Vec<Pac
     Ok(responses)
 }
 
+/* Like `run` but every response is sent as a partial result
+ * as soon as it's ready, encoded as (subject index, packed response),
+ * followed by a progress message with the number of finished subjects.
+ *
+ * Subjects finish in arbitrary order when running in parallel.
+ * A failing subject does not stop the others; the first error
+ * is returned at the end. Otherwise the answer is the number of subjects.
+ */
+pub fn run_streaming<L : Log>(mut log : L, precomputed : &mut Precomputed, request : &Request, cancel : &CancelFlag)
+    -> Result<u32>
+{
+    precompute(precomputed, request);
+
+    let mut finished = 0;
+    let mut first_error = None;
+    let mut report = |index : usize, result : Result<Response>| {
+        match result {
+            Ok(response) => log.partial(
+                codec::encode_to_memory(&(index as u32, Packed(response))).unwrap()
+            ),
+            Err(e) => if first_error.is_none() {
+                first_error = Some(e);
+            },
+        }
+
+        finished += 1;
+        log.progress(finished);
+    };
+
+    let precomputed : &Precomputed = precomputed;
+    let estimate = |subj : &Packed<Subject>| run_subject(precomputed, request, subj, cancel);
+
+    if request.disable_parallelism {
+        for (i, subj) in request.subjects.iter().enumerate() {
+            report(i, estimate(subj));
+        }
+    } else {
+        // the workers send the results to this thread, which owns the logger
+        let (tx, rx) = mpsc::channel();
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                request.subjects.par_iter().enumerate().for_each_with(tx,
+                    |tx, (i, subj)| tx.send((i, estimate(subj))).unwrap()
+                );
+            });
+
+            for (i, result) in rx {
+                report(i, result);
+            }
+        });
+    }
+
+    match first_error {
+        Some(e) => Err(e),
+        None => Ok(request.subjects.len() as u32),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use precomputed::Precomputed;
@@ -345,7 +608,7 @@ mod test {
     use codec;
     use alt_set::AltSet;
     use alt::Alt;
-    use rpc_common::{ChoiceRow,Subject};
+    use rpc_common::{ChoiceRow,Subject,CancelFlag};
     use std::iter::FromIterator;
 
     fn testsubj(alt_count : u32, choices : Vec<ChoiceRow>) -> Subject {
@@ -377,8 +640,8 @@ mod test {
         let subject = testsubj(4, choices);
         let models = [Model::TopTwo];
         let mut precomputed = Precomputed::new(None);
-        precomputed.precompute(4).unwrap();
-        let response = super::run_one(&precomputed, &subject, &models).unwrap();
+        precomputed.precompute(4);
+        let response = super::run_one(&precomputed, &subject, &models, &CancelFlag::new()).unwrap();
 
         assert_eq!(response.score, Penalty::exact(0));
         assert_eq!(response.best_instances.len(), 2);
@@ -436,26 +699,42 @@ mod test {
         ]);
 
         let mut precomputed = Precomputed::new(None);
-        precomputed.precompute(4).unwrap();
-        let response = super::run_one(&precomputed, &subject, &models).unwrap();
+        precomputed.precompute(4);
+        let response = super::run_one(&precomputed, &subject, &models, &CancelFlag::new()).unwrap();
 
         assert_eq!(response.score, Penalty::exact(0));
-        assert_eq!(response.best_instances.len(), 11);
+
+        // both rationales range over all strict partial orders, including linear orders,
+        // so some of the best instances have a total P or Q
+        assert_eq!(response.best_instances.len(), 24);
 
         let model = Model::SequentiallyRationalizableChoice;
         let penalty = Penalty::exact(0);
         assert_eq!(response.best_instances, vec![
+            II{ model, penalty: penalty.clone(), instance: vec![7, 4, 1, 2, 5, 8, 4, 7, 6, 4, 15] },
             II{ model, penalty: penalty.clone(), instance: vec![7, 4, 1, 2, 5, 9, 4, 7, 6, 4, 14] },
+            II{ model, penalty: penalty.clone(), instance: vec![7, 4, 1, 2, 5, 9, 4, 7, 6, 4, 15] },
+            II{ model, penalty: penalty.clone(), instance: vec![7, 4, 1, 2, 5, 9, 4, 15, 6, 4, 14] },
+            II{ model, penalty: penalty.clone(), instance: vec![7, 4, 1, 2, 5, 10, 4, 7, 6, 4, 15] },
             II{ model, penalty: penalty.clone(), instance: vec![7, 4, 1, 2, 5, 11, 4, 7, 6, 4, 12] },
             II{ model, penalty: penalty.clone(), instance: vec![7, 4, 1, 2, 5, 11, 4, 7, 6, 4, 14] },
+            II{ model, penalty: penalty.clone(), instance: vec![7, 4, 1, 2, 5, 11, 4, 7, 6, 4, 15] },
             II{ model, penalty: penalty.clone(), instance: vec![7, 4, 1, 2, 5, 11, 4, 15, 6, 4, 12] },
+            II{ model, penalty: penalty.clone(), instance: vec![7, 4, 1, 2, 5, 11, 4, 15, 6, 4, 14] },
+            II{ model, penalty: penalty.clone(), instance: vec![7, 4, 1, 2, 5, 11, 4, 15, 14, 4, 12] },
             II{ model, penalty: penalty.clone(), instance: vec![7, 4, 1, 2, 5, 13, 4, 7, 6, 4, 14] },
+            II{ model, penalty: penalty.clone(), instance: vec![7, 4, 1, 2, 5, 13, 4, 7, 6, 4, 15] },
+            II{ model, penalty: penalty.clone(), instance: vec![7, 4, 1, 2, 5, 13, 4, 15, 6, 4, 14] },
             II{ model, penalty: penalty.clone(), instance: vec![7, 4, 1, 2, 5, 15, 4, 7, 6, 4, 8] },
             II{ model, penalty: penalty.clone(), instance: vec![7, 4, 1, 2, 5, 15, 4, 7, 6, 4, 12] },
             II{ model, penalty: penalty.clone(), instance: vec![7, 4, 1, 2, 5, 15, 4, 7, 6, 4, 14] },
+            II{ model, penalty: penalty.clone(), instance: vec![7, 4, 1, 2, 5, 15, 4, 7, 6, 4, 15] },
             II{ model, penalty: penalty.clone(), instance: vec![7, 4, 1, 2, 5, 15, 4, 15, 6, 4, 8] },
             II{ model, penalty: penalty.clone(), instance: vec![7, 4, 1, 2, 5, 15, 4, 15, 6, 4, 12] },
-            II{ model, penalty: penalty.clone(), instance: vec![7, 4, 1, 2, 5, 15, 4, 15, 14, 4, 8] }
+            II{ model, penalty: penalty.clone(), instance: vec![7, 4, 1, 2, 5, 15, 4, 15, 6, 4, 14] },
+            II{ model, penalty: penalty.clone(), instance: vec![7, 4, 1, 2, 5, 15, 4, 15, 14, 4, 8] },
+            II{ model, penalty: penalty.clone(), instance: vec![7, 4, 1, 2, 5, 15, 4, 15, 14, 4, 12] },
+            II{ model, penalty: penalty.clone(), instance: vec![7, 4, 1, 2, 5, 15, 4, 15, 14, 12, 8] }
         ]);
     }
 
@@ -465,7 +744,7 @@ mod test {
         use super::InstanceInfo as II;
 
         let mut precomputed = Precomputed::new(None);
-        precomputed.precompute(5).unwrap();
+        precomputed.precompute(5);
 
         let models = [Model::UndominatedChoice{strict: true}];
         let subject = testsubj(5, choices![
@@ -489,7 +768,7 @@ mod test {
                 [3,4] -> [3]
         ]);
 
-        let response = super::run_one(&precomputed, &subject, &models).unwrap();
+        let response = super::run_one(&precomputed, &subject, &models, &CancelFlag::new()).unwrap();
         assert_eq!(response.score, Penalty::exact(2));
         assert_eq!(response.best_instances.len(), 3);
 
@@ -508,7 +787,7 @@ mod test {
         use alt_set::AltSet;
 
         let mut precomputed = Precomputed::new(None);
-        precomputed.precompute(5).unwrap();
+        precomputed.precompute(5);
 
         let models = [PM(PP{ strict: None, total: None })];
         let subject = testsubj(5, choices![
@@ -566,7 +845,7 @@ mod test {
         let instance = model::Instance::PreorderMaximization(p);
         assert_eq!(instance.choice(alts![0,1].view(), None), alts![]);
 
-        let response = super::run_one(&precomputed, &subject, &models).unwrap();
+        let response = super::run_one(&precomputed, &subject, &models, &CancelFlag::new()).unwrap();
         assert_eq!(response.score, Penalty::exact(0));
         assert_eq!(response.best_instances, vec![super::InstanceInfo{
             model: PM(PP{ strict: None, total: None }),
@@ -574,4 +853,175 @@ mod test {
             instance: vec![0, 5, 1, 2, 4, 8, 16],
         }]);
     }
+
+    #[test]
+    fn streaming() {
+        use rpc::{Log,LogLevel};
+        use codec::Packed;
+        use model::{Model,PreorderParams};
+
+        struct Collect {
+            partials : Vec<(u32, Vec<u8>)>,
+            progress : Vec<u32>,
+        }
+
+        impl Log for &mut Collect {
+            fn log(&mut self, _level : LogLevel, _message : String) {}
+            fn progress(&mut self, position : u32) { self.progress.push(position) }
+            fn partial(&mut self, data : Vec<u8>) {
+                // a packed response is encoded as a byte string
+                let (index, response) : (u32, Vec<u8>) =
+                    codec::decode_from_memory(&data).unwrap();
+                self.partials.push((index, response));
+            }
+        }
+
+        let subjects : Vec<_> = (0..6).map(|i| Packed(testsubj(3, choices![
+            [0,1,2] -> [i % 3],
+            [0,1] -> [(i+1) % 2],
+            [1,2] -> [1]
+        ]))).collect();
+
+        for &disable_parallelism in &[true, false] {
+            let request = super::Request {
+                subjects: subjects.clone(),
+                models: vec![
+                    Model::PreorderMaximization(PreorderParams{strict: Some(true), total: Some(true)}),
+                    Model::UndominatedChoice{strict: true},
+                ],
+                disable_parallelism,
+                disregard_deferrals: false,
+                diagnostics: true,
+                stochastic_models: Vec::new(),
+            };
+
+            let mut precomputed = Precomputed::new(None);
+            let expected : Vec<Vec<u8>> = super::run(&mut precomputed, &request, &CancelFlag::new()).unwrap().into_iter().map(
+                |Packed(response)| codec::encode_to_memory(&response).unwrap()
+            ).collect();
+
+            let mut collect = Collect{ partials: Vec::new(), progress: Vec::new() };
+            assert_eq!(super::run_streaming(&mut collect, &mut precomputed, &request, &CancelFlag::new()).unwrap(), 6);
+            assert_eq!(collect.progress, vec![1, 2, 3, 4, 5, 6]);
+
+            collect.partials.sort();
+            assert_eq!(collect.partials, expected.into_iter().enumerate().map(
+                |(i, response)| (i as u32, response)
+            ).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn cancelled() {
+        use model::Model;
+
+        let request = super::Request {
+            subjects: vec![codec::Packed(testsubj(3, choices![[0,1] -> [0]]))],
+            models: vec![Model::UndominatedChoice{strict: true}],
+            disable_parallelism: false,
+            disregard_deferrals: false,
+            diagnostics: false,
+            stochastic_models: Vec::new(),
+        };
+
+        let cancel = CancelFlag::new();
+        cancel.cancel();
+
+        let mut precomputed = Precomputed::new(None);
+        match super::run(&mut precomputed, &request, &cancel) {
+            Err(super::EstimationError::Cancelled) => (),
+            _ => panic!("estimation not cancelled"),
+        }
+
+        // the precomputed preorders survive
+        cancel.reset();
+        assert!(super::run(&mut precomputed, &request, &cancel).is_ok());
+    }
+
+    #[test]
+    fn stochastic_fits() {
+        use model::{Model,PreorderParams};
+        use stochastic::{self,StochasticModel,Estimate};
+
+        let subject = testsubj(3, choices![
+            [0,1,2] -> [0],
+            [0,1,2] -> [1],
+            [0,1] -> [0],
+            [1,2] -> []
+        ]);
+        let mut request = super::Request {
+            subjects: vec![codec::Packed(subject.clone())],
+            // strict UM needs no precomputed preorders for the 8 alternatives below
+            models: vec![Model::PreorderMaximization(PreorderParams{strict: Some(true), total: Some(true)})],
+            disable_parallelism: true,
+            disregard_deferrals: false,
+            diagnostics: false,
+            stochastic_models: vec![StochasticModel::Luce, StochasticModel::RandomPreference],
+        };
+
+        let mut precomputed = Precomputed::new(None);
+        let codec::Packed(response) = super::run(&mut precomputed, &request, &CancelFlag::new()).unwrap().remove(0);
+
+        // the same fits as the separate stochastic request
+        let expected = stochastic::run_one(&subject, &request.stochastic_models).unwrap();
+        assert_eq!(response.stochastic_fits.len(), 2);
+        for (fit, exp) in response.stochastic_fits.iter().zip(&expected.fits) {
+            assert_eq!(fit.estimate, exp.estimate);
+            assert_eq!(fit.log_likelihood, exp.log_likelihood);
+        }
+        match response.stochastic_fits[0].estimate {
+            Estimate::Luce(ref weights) => assert_eq!(weights.len(), 3),
+            _ => panic!("wrong estimate"),
+        }
+
+        // too many alternatives for random preferences
+        request.subjects = vec![codec::Packed(testsubj(stochastic::MAX_ALTERNATIVES_RANDOM_PREFERENCE + 1, choices![
+            [0,1] -> [0]
+        ]))];
+        match super::run(&mut precomputed, &request, &CancelFlag::new()) {
+            Err(super::EstimationError::StochasticError(_)) => (),
+            _ => panic!("random preferences fitted with too many alternatives"),
+        }
+    }
+
+    #[test]
+    fn diagnostics() {
+        use model::{Model,PreorderParams};
+        use super::MissedRow;
+        use codec::Packed;
+        use std::collections::HashSet;
+
+        let request = super::Request {
+            subjects: vec![Packed(testsubj(3, choices![
+                [0,1,2] -> [0],
+                [0,1] -> [1],
+                [1,2] -> [],
+                [0,2] -> [0]
+            ]))],
+            models: vec![Model::PreorderMaximization(PreorderParams{strict: Some(true), total: Some(true)})],
+            disable_parallelism: true,
+            disregard_deferrals: true,
+            diagnostics: true,
+            stochastic_models: Vec::new(),
+        };
+
+        let mut precomputed = Precomputed::new(None);
+        let Packed(response) = super::run(&mut precomputed, &request, &CancelFlag::new()).unwrap().remove(0);
+
+        // 0 > 1 > 2, 0 > 2 > 1 and 1 > 0 > 2, each missing one row
+        // the disregarded deferral is not reported but it still counts in the row indices
+        assert_eq!(response.score, Penalty::exact(1));
+        assert_eq!(response.best_instances.len(), 3);
+        assert_eq!(response.diagnostics.len(), 3);
+
+        let missed : HashSet<(u32, Vec<Alt>)> = response.diagnostics.iter().map(|rows| {
+            assert_eq!(rows.len(), 1);
+            let MissedRow{row, ref predicted} = rows[0];
+            (row, predicted.view().iter().collect())
+        }).collect();
+        assert_eq!(missed, HashSet::from_iter(vec![
+            (0, vec![Alt(1)]),
+            (1, vec![Alt(0)]),
+        ]));
+    }
 }
@@ -0,0 +1,1013 @@
+use alt::Alt;
+use alt_set::{AltSet,AltSetView};
+use preorder::Preorder;
+use model::{Instance,Penalty,undominated_choice};
+use rpc_common::{ChoiceRow,CancelFlag};
+use precomputed::Error as PreorderError;
+use fast_preorder::{self,FastPreorder};
+use approximate_estimation;
+use linear_preorders;
+use std::cmp;
+use std::cell::Cell;
+
+// The dynamic programme below keeps one u32 per subset of alternatives,
+// so 20 alternatives take 4 MB of memory and 2^20 steps.
+pub const MAX_ALTERNATIVES_STRICT_UM : u32 = 20;
+
+// Both rationales of SRC are generated by `fast_preorder::Stream`, which goes up to 8,
+// but pairs of strict partial orders are too many for an exact search: with 40 random rows,
+// 5 alternatives take a few seconds while 6 alternatives do not finish in minutes.
+pub const MAX_ALTERNATIVES_SRC : u32 = 5;
+
+// The search for perfect instances prunes every pair that misses a row,
+// so it goes all the way, but sparse data leave too little to prune;
+// we give up after this many steps and report a looser bound.
+pub const MAX_ALTERNATIVES_SRC_PERFECT : u32 = fast_preorder::MAX_SIZE;
+const SRC_PERFECT_STEPS : u64 = 10_000_000;
+
+// Without enough data, the number of optimal linear orders
+// grows factorially so we report only the first few of them.
+// The score is exact either way.
+pub const MAX_BEST_INSTANCES : usize = 10_000;
+
+// Limited attention goes through all linear orders, pruning those whose disjoint conflicts
+// already exceed the best penalty. For 8 alternatives, that is 40320 orders.
+pub const MAX_ALTERNATIVES_LIMITED_ATTENTION : u32 = 8;
+const LIMITED_ATTENTION_STEPS : u64 = 1_000_000;
+
+const INFINITY : u32 = u32::MAX;
+
+struct Row {
+    menu : u32,  // bit mask
+    chosen : bool,  // is the alternative that decides this row the chosen one?
+}
+
+/* Exact Houtman-Maks estimation of strict utility maximization.
+ *
+ * A linear order decides every row by the first alternative (from the top)
+ * that appears in the menu of that row. Hence the penalty of placing
+ * alternative `a` right below the set of alternatives `above` depends only
+ * on `above` and `a`: it's the number of rows whose menus contain `a`,
+ * avoid `above`, and where `a` was not the (only) chosen alternative.
+ *
+ * This gives a dynamic programme over subsets of alternatives,
+ * where best[S] is the lowest penalty of any ordering of the top |S| elements
+ * such that they form the set S. We expand the subsets in numerical order
+ * and prune those that are already worse than the penalty of a greedy order.
+ *
+ * Rows whose choice is not a singleton are never rationalised by a strict order
+ * so they are counted separately.
+ */
+struct Search {
+    alt_count : u32,
+    forced_penalty : u32,
+    rows_by_alt : Vec<Vec<Row>>,  // rows whose menus contain the given alternative
+}
+
+impl Search {
+    fn new(alt_count : u32, choices : &[ChoiceRow]) -> Search {
+        let mut forced_penalty = 0;
+        let mut rows_by_alt : Vec<Vec<Row>> = (0..alt_count).map(|_| Vec::new()).collect();
+
+        for cr in choices {
+            let menu = cr.menu.view().iter().fold(0u32, |m, Alt(i)| m | (1 << i));
+
+            match cr.choice.view().as_singleton() {
+                Some(choice) => {
+                    for a in cr.menu.view().iter() {
+                        rows_by_alt[a.index() as usize].push(Row{
+                            menu,
+                            chosen: a == choice,
+                        });
+                    }
+                }
+
+                None => {
+                    // deferrals and multiple choices
+                    forced_penalty += 1;
+                }
+            }
+        }
+
+        Search {
+            alt_count,
+            forced_penalty,
+            rows_by_alt,
+        }
+    }
+
+    // penalty incurred by placing `Alt(a)` immediately below `above`
+    fn step_penalty(&self, above : u32, a : u32) -> u32 {
+        self.rows_by_alt[a as usize].iter().filter(
+            |row| !row.chosen && (row.menu & above == 0)
+        ).count() as u32
+    }
+
+    fn order_penalty(&self, order : &[u32]) -> u32 {
+        let mut above = 0;
+        let mut penalty = 0;
+        for &a in order {
+            penalty += self.step_penalty(above, a);
+            above |= 1 << a;
+        }
+        penalty
+    }
+
+    // order the alternatives by how often they are chosen
+    fn greedy_order(&self) -> Vec<u32> {
+        let mut order : Vec<u32> = (0..self.alt_count).collect();
+        order.sort_by_key(|&a|
+            -(self.rows_by_alt[a as usize].iter().filter(|row| row.chosen).count() as i64)
+        );
+        order
+    }
+
+    fn best_penalties(&self, upper_bound : u32, cancel : &CancelFlag) -> Result<Vec<u32>, PreorderError> {
+        let full = (1u32 << self.alt_count) - 1;
+        let mut best = vec![INFINITY; full as usize + 1];
+        best[0] = 0;
+
+        for above in 0 ..= full {
+            if cancel.is_cancelled() {
+                return Err(PreorderError::Cancelled);
+            }
+
+            let penalty = best[above as usize];
+            if penalty > upper_bound {
+                // unreachable or hopeless
+                continue;
+            }
+
+            for a in 0..self.alt_count {
+                if above & (1 << a) != 0 {
+                    continue;
+                }
+
+                let next = (above | (1 << a)) as usize;
+                let next_penalty = penalty + self.step_penalty(above, a);
+                if next_penalty < best[next] {
+                    best[next] = next_penalty;
+                }
+            }
+        }
+
+        Ok(best)
+    }
+
+    // walk back from the full set along the optimal transitions
+    // `below` holds the alternatives placed under `above`, bottom first
+    fn collect_orders(
+        &self,
+        best : &[u32],
+        above : u32,
+        below : &mut Vec<u32>,
+        orders : &mut Vec<Preorder>,
+    ) {
+        if orders.len() >= MAX_BEST_INSTANCES {
+            return;
+        }
+
+        if above == 0 {
+            // values[a] = number of alternatives below a
+            let mut values = vec![0; self.alt_count as usize];
+            for (rank, &a) in below.iter().enumerate() {
+                values[a as usize] = rank as u32;
+            }
+            orders.push(Preorder::from_values(&values));
+            return;
+        }
+
+        for a in 0..self.alt_count {
+            if above & (1 << a) == 0 {
+                continue;
+            }
+
+            let rest = above & !(1 << a);
+            let rest_penalty = best[rest as usize];
+            if rest_penalty != INFINITY
+                && rest_penalty + self.step_penalty(rest, a) == best[above as usize]
+            {
+                below.push(a);
+                self.collect_orders(best, rest, below, orders);
+                below.pop();
+            }
+        }
+    }
+}
+
+/// Returns the exact penalty of strict utility maximization
+/// together with (up to `MAX_BEST_INSTANCES`) linear orders attaining it.
+pub fn strict_preorder_maximization(alt_count : u32, choices : &[ChoiceRow], cancel : &CancelFlag)
+    -> Result<(Penalty, Vec<Preorder>), PreorderError>
+{
+    if alt_count > MAX_ALTERNATIVES_STRICT_UM {
+        return Err(PreorderError::TooManyAlternatives(alt_count));
+    }
+
+    let search = Search::new(alt_count, choices);
+    let upper_bound = search.order_penalty(&search.greedy_order());
+    let best = search.best_penalties(upper_bound, cancel)?;
+
+    let full = (1u32 << alt_count) - 1;
+    let mut orders = Vec::new();
+    search.collect_orders(&best, full, &mut Vec::new(), &mut orders);
+
+    Ok((
+        Penalty::exact(search.forced_penalty + best[full as usize]),
+        orders,
+    ))
+}
+
+/* Exact estimation of sequentially rationalizable choice.
+ *
+ * An instance consists of two strict partial orders: P makes the shortlist M_P(A)
+ * from the menu A and Q chooses M_Q(M_P(A)) from the shortlist.
+ * Linear orders are strict partial orders, too, so either rationale may be total.
+ * A row is lost already by P if its choice is not in the shortlist;
+ * otherwise it's lost by Q if M_Q(shortlist) is not exactly the choice.
+ *
+ * M_P(A) depends only on the restriction of P to A, and the same holds for Q
+ * and the shortlists, so we generate P (and then Q for every surviving P)
+ * alternative by alternative and abandon the subtrees where the rows
+ * already decided exceed the best penalty found so far.
+ *
+ * The bound is seeded with the instance given by the construction
+ * of Dutta and Horan, which is perfect whenever it exists.
+ *
+ * Above `MAX_ALTERNATIVES_SRC`, we run the same search with the bound 0,
+ * which finds the perfect instances if there are any.
+ *
+ * Rows whose choice is not a singleton are never rationalised by SRC
+ * so they are counted separately.
+ */
+
+// rows_by_size[k] = rows whose sets fit in the first k alternatives
+fn rows_by_size<T, F>(alt_count : u32, rows : &[T], alts : F) -> Vec<Vec<&T>>
+    where F : Fn(&T) -> AltSetView
+{
+    let mut rows_by_size : Vec<Vec<&T>> = (0..alt_count+1).map(|_| Vec::new()).collect();
+    for row in rows {
+        let size = alts(row).iter().map(|Alt(i)| i+1).max().unwrap_or(0);
+        for rows in &mut rows_by_size[size as usize ..] {
+            rows.push(row);
+        }
+    }
+    rows_by_size
+}
+
+// the number of rows where `choice` is not shortlisted,
+// or `None` if it exceeds `bound`
+fn shortlist_penalty(p : &Preorder, rows : &[&(AltSetView, Alt)], bound : u32) -> Option<u32> {
+    let mut penalty = 0;
+    for &&(menu, choice) in rows {
+        if !undominated_choice(p, menu).view().contains(choice) {
+            penalty += 1;
+            if penalty > bound {
+                return None;
+            }
+        }
+    }
+    Some(penalty)
+}
+
+// the number of rows where `choice` is not the only element chosen from the shortlist,
+// or `None` if it exceeds `bound`
+fn final_choice_penalty(q : &Preorder, rows : &[&(AltSet, Alt)], bound : u32) -> Option<u32> {
+    let mut penalty = 0;
+    for &&(ref shortlist, choice) in rows {
+        if undominated_choice(q, shortlist.view()).view().as_singleton() != Some(choice) {
+            penalty += 1;
+            if penalty > bound {
+                return None;
+            }
+        }
+    }
+    Some(penalty)
+}
+
+// the shortlisting and the choosing rationale
+pub type SrcRationales = (Preorder, Preorder);
+
+// the lowest penalty with the instances attaining it
+type SrcBest = Option<(u32, Vec<SrcRationales>)>;
+
+// the lowest penalty of the singleton rows, if at most `bound`,
+// together with (up to `MAX_BEST_INSTANCES`) pairs `(P, Q)` attaining it;
+// the flag says whether the search stopped after `max_steps` candidates
+fn src_search(alt_count : u32, rows : &[(AltSetView, Alt)], bound : u32, max_steps : u64, cancel : &CancelFlag)
+    -> Result<(SrcBest, bool), PreorderError>
+{
+    let bound = Cell::new(bound);
+    let steps = Cell::new(0u64);
+    let mut best = Vec::new();
+
+    // prunes everything once we are out of steps or cancelled
+    let exhausted = || {
+        steps.set(steps.get() + 1);
+        steps.get() > max_steps || cancel.is_cancelled()
+    };
+
+    let menus_by_size = rows_by_size(alt_count, rows, |&(menu, _)| menu);
+    let mut p_stream = fast_preorder::Stream::new(alt_count, true, false);
+    let mut prune_p = |size : u32, p : FastPreorder| {
+        let p = Preorder::from_fast_preorder(size, p);
+        exhausted() || shortlist_penalty(&p, &menus_by_size[size as usize], bound.get()).is_none()
+    };
+
+    'search: while let Some(p) = p_stream.next_pruned(&mut prune_p) {
+        let p = Preorder::from_fast_preorder(alt_count, p);
+
+        let mut lost = 0;
+        let mut shortlists = Vec::new();
+        for &(menu, choice) in rows {
+            let shortlist = undominated_choice(&p, menu);
+            if !shortlist.view().contains(choice) {
+                lost += 1;
+            } else if !shortlist.view().is_singleton() {
+                // singleton shortlists are chosen by any Q
+                shortlists.push((shortlist, choice));
+            }
+        }
+
+        let shortlists_by_size = rows_by_size(alt_count, &shortlists, |(shortlist, _)| shortlist.view());
+        let mut q_stream = fast_preorder::Stream::new(alt_count, true, false);
+        let mut prune_q = |size : u32, q : FastPreorder| {
+            let q = Preorder::from_fast_preorder(size, q);
+            exhausted()
+                || bound.get() < lost
+                || final_choice_penalty(&q, &shortlists_by_size[size as usize], bound.get() - lost).is_none()
+        };
+
+        while let Some(q) = q_stream.next_pruned(&mut prune_q) {
+            let q = Preorder::from_fast_preorder(alt_count, q);
+            let penalty = lost + final_choice_penalty(&q, &shortlists_by_size[alt_count as usize], u32::MAX)
+                .expect("unbounded penalty");
+
+            if penalty < bound.get() {
+                bound.set(penalty);
+                best.clear();
+            }
+
+            if best.len() < MAX_BEST_INSTANCES {
+                best.push((p.clone(), q));
+            } else if bound.get() == 0 {
+                // nothing can be better and we have enough instances
+                break 'search;
+            }
+        }
+    }
+
+    if cancel.is_cancelled() {
+        return Err(PreorderError::Cancelled);
+    }
+
+    let truncated = steps.get() > max_steps;
+    if best.is_empty() {
+        Ok((None, truncated))
+    } else {
+        Ok((Some((bound.get(), best)), truncated))
+    }
+}
+
+/// Returns the penalty of sequentially rationalizable choice
+/// together with (up to `MAX_BEST_INSTANCES`) pairs `(P, Q)` attaining it,
+/// or `None` if every instance incurs more than `bound`.
+///
+/// Up to `MAX_ALTERNATIVES_SRC` alternatives, the penalty is exact.
+/// Above that, we look only for perfect instances. If there are none,
+/// the penalty is only bounded from above by strict utility maximization,
+/// which is SRC with an empty shortlisting rationale, and those are the instances returned.
+pub fn sequentially_rationalizable_choice(alt_count : u32, choices : &[ChoiceRow], bound : u32, cancel : &CancelFlag)
+    -> Result<Option<(Penalty, Vec<SrcRationales>)>, PreorderError>
+{
+    if alt_count > MAX_ALTERNATIVES_SRC_PERFECT {
+        return Err(PreorderError::TooManyAlternatives(alt_count));
+    }
+
+    let mut forced_penalty = 0;
+    let mut rows = Vec::new();
+    for cr in choices {
+        match cr.choice.view().as_singleton() {
+            Some(choice) => rows.push((cr.menu.view(), choice)),
+            None => forced_penalty += 1,  // deferrals and multiple choices
+        }
+    }
+
+    if forced_penalty > bound {
+        return Ok(None);
+    }
+
+    if alt_count > MAX_ALTERNATIVES_SRC {
+        let lower_bound = match src_search(alt_count, &rows, 0, SRC_PERFECT_STEPS, cancel)? {
+            (Some((_, best)), _) => return Ok(Some((Penalty::exact(forced_penalty), best))),
+            (None, true) => forced_penalty,  // we don't know
+            (None, false) => forced_penalty + 1,  // every instance misses a singleton row
+        };
+
+        if lower_bound > bound {
+            return Ok(None);
+        }
+
+        let (upper_bound, orders) = strict_preorder_maximization(alt_count, choices, cancel)?;
+        return Ok(Some((
+            Penalty{
+                lower_bound,
+                upper_bound: upper_bound.upper_bound,
+            },
+            orders.into_iter().map(|q| (Preorder::diagonal(alt_count), q)).collect(),
+        )));
+    }
+
+    let bound = match approximate_estimation::sequentially_rationalizable_choice(alt_count, choices) {
+        Some((p, q)) => cmp::min(
+            bound,
+            Instance::SequentiallyRationalizableChoice(p, q).penalty(choices).upper_bound,
+        ),
+        None => bound,
+    };
+
+    // the highest interesting penalty of the singleton rows
+    let (best, _) = src_search(alt_count, &rows, bound - forced_penalty, u64::MAX, cancel)?;
+    Ok(best.map(
+        |(penalty, best)| (Penalty::exact(forced_penalty + penalty), best)
+    ))
+}
+
+// an observation with a single chosen alternative
+struct Observation {
+    index : usize,  // into the choice rows
+    choice : Alt,
+    menu : u32,  // bit mask
+    considered : u32,  // the choice and the worse alternatives of the menu, bit mask
+}
+
+fn bit_mask(set : AltSetView) -> u32 {
+    set.iter().fold(0u32, |m, Alt(i)| m | (1 << i))
+}
+
+/* Choice with limited attention (Masatlioglu, Nakajima and Ozbay, 2012).
+ *
+ * Fix a linear preference. If `x` is chosen from `S`, the alternatives of `S`
+ * better than `x` are not considered, and removing them does not change
+ * the consideration set. Hence `x` is also chosen from every menu `T`
+ * such that `L ⊆ T ⊆ S`, where `L` consists of `x` and the alternatives of `S` worse than `x`.
+ * Two observations with different choices thus conflict if the menu of each
+ * contains the `L` of the other.
+ *
+ * Conversely, pairwise compatible observations are explained by the attention filter
+ * that considers `L` in every menu between `L` and `S`, and the whole menu everywhere else.
+ * With all menus observed, a preference without conflicts exists
+ * if and only if the revealed preference of MNO is acyclic.
+ *
+ * The penalty of the preference is the smallest number of observations to drop
+ * so that the rest are compatible, which is a vertex cover of the conflicts.
+ * Rows whose choice is not a singleton are never explained so they are counted separately.
+ */
+struct Conflicts {
+    forced_penalty : u32,
+    observations : Vec<Observation>,
+    edges : Vec<(usize, usize)>,  // pairs of conflicting observations
+}
+
+impl Conflicts {
+    fn new(p : &Preorder, choices : &[ChoiceRow]) -> Conflicts {
+        debug_assert!(p.is_strict());
+        debug_assert!(p.is_total());
+
+        let mut forced_penalty = 0;
+        let mut observations = Vec::new();
+        for (index, cr) in choices.iter().enumerate() {
+            match cr.choice.view().as_singleton() {
+                Some(choice) => {
+                    let menu = bit_mask(cr.menu.view());
+                    let better = bit_mask(p.upset(choice)) & !(1 << choice.index());
+                    observations.push(Observation{
+                        index,
+                        choice,
+                        menu,
+                        considered: menu & !better,
+                    });
+                }
+
+                None => {
+                    // deferrals and multiple choices
+                    forced_penalty += 1;
+                }
+            }
+        }
+
+        let mut edges = Vec::new();
+        for (i, x) in observations.iter().enumerate() {
+            for (j, y) in observations.iter().enumerate().skip(i+1) {
+                if x.choice != y.choice
+                    && x.considered & !y.menu == 0
+                    && y.considered & !x.menu == 0
+                {
+                    edges.push((i, j));
+                }
+            }
+        }
+
+        Conflicts {
+            forced_penalty,
+            observations,
+            edges,
+        }
+    }
+
+    // the observations that remain after dropping the cover
+    fn explained(&self, choices : &[ChoiceRow], cover : &[usize]) -> Vec<(AltSet, Alt)> {
+        let mut explained : Vec<(AltSet, Alt)> = self.observations.iter().enumerate()
+            .filter(|&(i, _)| !cover.contains(&i))
+            .map(|(_, obs)| (choices[obs.index].menu.clone(), obs.choice))
+            .collect();
+        explained.sort();
+        explained.dedup();
+        explained
+    }
+}
+
+// conflicts that share no observation need distinct observations in the cover
+fn matching_bound(edges : &[(usize, usize)], vertex_count : usize) -> usize {
+    let mut used = vec![false; vertex_count];
+    let mut bound = 0;
+    for &(i, j) in edges {
+        if !used[i] && !used[j] {
+            used[i] = true;
+            used[j] = true;
+            bound += 1;
+        }
+    }
+    bound
+}
+
+/* Branch and bound for vertex covers of `edges` with at most `limit` vertices.
+ * We branch on a vertex of the largest degree: either it is in the cover or all its neighbours are.
+ * The first branch comes first so the first cover found is the greedy one.
+ * Every cover found lowers `limit` below its size.
+ *
+ * Returns `false` if it ran out of steps.
+ */
+struct CoverSearch {
+    vertex_count : usize,
+    limit : Option<usize>,  // None = nothing more to look for
+    best : Option<Vec<usize>>,
+    steps_left : u64,
+}
+
+impl CoverSearch {
+    fn branch(&mut self, edges : &[(usize, usize)], chosen : &mut Vec<usize>) -> bool {
+        let limit = match self.limit {
+            Some(limit) => limit,
+            None => return true,
+        };
+
+        if self.steps_left == 0 {
+            return false;
+        }
+        self.steps_left -= 1;
+
+        if edges.is_empty() {
+            self.limit = chosen.len().checked_sub(1);
+            self.best = Some(chosen.clone());
+            return true;
+        }
+
+        if chosen.len() + matching_bound(edges, self.vertex_count) > limit {
+            return true;
+        }
+
+        let mut neighbours : Vec<Vec<usize>> = vec![Vec::new(); self.vertex_count];
+        for &(i, j) in edges {
+            neighbours[i].push(j);
+            neighbours[j].push(i);
+        }
+        let v = (0..self.vertex_count).max_by_key(|&i| neighbours[i].len()).unwrap();
+
+        // `v` in the cover
+        let rest : Vec<(usize, usize)> = edges.iter().cloned()
+            .filter(|&(i, j)| i != v && j != v)
+            .collect();
+        chosen.push(v);
+        let finished = self.branch(&rest, chosen);
+        chosen.pop();
+        if !finished {
+            return false;
+        }
+
+        // all neighbours of `v` in the cover
+        let outside = &neighbours[v];
+        let still_fits = self.limit.is_some_and(|limit| chosen.len() + outside.len() <= limit);
+        if still_fits {
+            let rest : Vec<(usize, usize)> = edges.iter().cloned()
+                .filter(|&(i, j)| !outside.contains(&i) && !outside.contains(&j))
+                .collect();
+            let depth = chosen.len();
+            chosen.extend(outside);
+            let finished = self.branch(&rest, chosen);
+            chosen.truncate(depth);
+            if !finished {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Returns the exact penalty of limited attention with the given linear preference,
+/// together with the explained observations, which determine the attention filter.
+pub fn limited_attention_filter(p : &Preorder, choices : &[ChoiceRow]) -> (u32, Vec<(AltSet, Alt)>) {
+    let conflicts = Conflicts::new(p, choices);
+    let mut search = CoverSearch{
+        vertex_count: conflicts.observations.len(),
+        limit: Some(conflicts.observations.len()),
+        best: None,
+        steps_left: u64::MAX,
+    };
+    search.branch(&conflicts.edges, &mut Vec::new());
+
+    let cover = search.best.unwrap();  // all observations always cover
+    (
+        conflicts.forced_penalty + cover.len() as u32,
+        conflicts.explained(choices, &cover),
+    )
+}
+
+// preference and explained observations
+pub type AttentionInstance = (Preorder, Vec<(AltSet, Alt)>);
+
+/// Returns the penalty of choice with limited attention
+/// together with (up to `MAX_BEST_INSTANCES`) preferences and attention filters attaining it,
+/// or `None` if no instance within `bound` was found.
+///
+/// The search over the preferences shares a budget of steps.
+/// When it runs out, the penalty is a bound: the lower end comes from disjoint conflicts
+/// and the upper end from the best attention filters found so far.
+pub fn limited_attention(alt_count : u32, choices : &[ChoiceRow], bound : u32, cancel : &CancelFlag)
+    -> Result<Option<(Penalty, Vec<AttentionInstance>)>, PreorderError>
+{
+    if alt_count > MAX_ALTERNATIVES_LIMITED_ATTENTION {
+        return Err(PreorderError::TooManyAlternatives(alt_count));
+    }
+
+    let mut upper_bound = bound;
+    let mut lower_bound = INFINITY;  // of the preferences whose search did not finish
+    let mut best = Vec::new();
+    let mut steps_left = LIMITED_ATTENTION_STEPS;
+
+    for p in linear_preorders::all(alt_count) {
+        if cancel.is_cancelled() {
+            return Err(PreorderError::Cancelled);
+        }
+
+        let conflicts = Conflicts::new(&p, choices);
+        let vertex_count = conflicts.observations.len();
+        let p_lower_bound = conflicts.forced_penalty + matching_bound(&conflicts.edges, vertex_count) as u32;
+        if p_lower_bound > upper_bound {
+            continue;
+        }
+
+        // enough steps for the greedy cover even when out of budget
+        let steps = cmp::max(steps_left, vertex_count as u64 + 1);
+        let mut search = CoverSearch{
+            vertex_count,
+            limit: Some((upper_bound - conflicts.forced_penalty) as usize),
+            best: None,
+            steps_left: steps,
+        };
+        let finished = search.branch(&conflicts.edges, &mut Vec::new());
+        steps_left = steps_left.saturating_sub(steps - search.steps_left);
+
+        if !finished {
+            lower_bound = cmp::min(lower_bound, p_lower_bound);
+        }
+
+        if let Some(cover) = search.best {
+            let penalty = conflicts.forced_penalty + cover.len() as u32;
+            if penalty < upper_bound || best.is_empty() {
+                upper_bound = penalty;
+                best.clear();
+            }
+
+            if best.len() < MAX_BEST_INSTANCES {
+                let filter = conflicts.explained(choices, &cover);
+                best.push((p, filter));
+            }
+        }
+    }
+
+    if best.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some((
+        Penalty{
+            lower_bound: cmp::min(lower_bound, upper_bound),
+            upper_bound,
+        },
+        best,
+    )))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use model::{self,Instance,Model,PreorderParams};
+    use precomputed::Precomputed;
+    use alt_set::AltSet;
+    use std::collections::HashSet;
+    use std::iter::FromIterator;
+
+    fn brute_force(alt_count : u32, choices : &[ChoiceRow]) -> (u32, HashSet<Preorder>) {
+        let precomputed = Precomputed::new(None);
+        let mut best = None;
+        let mut orders = HashSet::new();
+
+        model::traverse_all(
+            &precomputed,
+            Model::PreorderMaximization(PreorderParams{strict: Some(true), total: Some(true)}),
+            alt_count,
+            choices,
+            &CancelFlag::new(),
+            &mut |inst| {
+                let penalty = inst.penalty(choices).upper_bound;
+                if let Instance::PreorderMaximization(p) = inst {
+                    if best.is_none_or(|b| penalty < b) {
+                        best = Some(penalty);
+                        orders.clear();
+                    }
+
+                    if best == Some(penalty) {
+                        orders.insert(p);
+                    }
+                }
+            }
+        ).unwrap();
+
+        (best.unwrap(), orders)
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let choices = choices![
+            [0,1,2,3,4] -> [2],
+            [0,1,2] -> [1],
+            [1,2,3] -> [3],
+            [0,3] -> [3],
+            [0,1] -> [0],
+            [2,4] -> [4],
+            [3,4] -> [3],
+            [1,4] -> [],
+            [0,2,4] -> [0,2],
+            [1,3] -> [1]
+        ];
+
+        let (penalty, orders) = strict_preorder_maximization(5, &choices, &CancelFlag::new()).unwrap();
+        let (expected_penalty, expected_orders) = brute_force(5, &choices);
+
+        assert_eq!(penalty, Penalty::exact(expected_penalty));
+        assert_eq!(HashSet::from_iter(orders), expected_orders);
+    }
+
+    #[test]
+    fn many_alternatives() {
+        // all binary menus over 12 alternatives, consistent with 0 < 1 < ... < 11
+        // except for the choice between the worst and the best alternative
+        let alt_count = 12;
+        let mut choices = Vec::new();
+        for (i, j) in Alt::distinct_pairs(alt_count) {
+            choices.push(ChoiceRow{
+                menu: AltSet::from_iter(&[i, j]),
+                default: None,
+                choice: AltSet::singleton(if (i, j) == (Alt(0), Alt(11)) { i } else { j }),
+            });
+        }
+
+        let (penalty, orders) = strict_preorder_maximization(alt_count, &choices, &CancelFlag::new()).unwrap();
+        assert_eq!(penalty, Penalty::exact(1));
+        assert_eq!(orders, vec![Preorder::from_values(&(0..alt_count).collect::<Vec<_>>())]);
+    }
+
+    #[test]
+    fn too_many_alternatives() {
+        assert!(strict_preorder_maximization(MAX_ALTERNATIVES_STRICT_UM + 1, &[], &CancelFlag::new()).is_err());
+    }
+
+    #[test]
+    fn cancelled() {
+        let choices = choices![
+            [0,1,2] -> [0],
+            [0,1] -> [1],
+            [1,2] -> [2]
+        ];
+        let cancel = CancelFlag::new();
+        cancel.cancel();
+
+        assert!(matches!(strict_preorder_maximization(3, &choices, &cancel), Err(PreorderError::Cancelled)));
+        assert!(matches!(limited_attention(3, &choices, u32::MAX, &cancel), Err(PreorderError::Cancelled)));
+        for &alt_count in &[3, MAX_ALTERNATIVES_SRC + 1] {
+            assert!(matches!(
+                sequentially_rationalizable_choice(alt_count, &choices, u32::MAX, &cancel),
+                Err(PreorderError::Cancelled)
+            ));
+        }
+    }
+
+    #[test]
+    fn src_matches_brute_force() {
+        let alt_count = 4;
+        let choices = choices![
+            [0,1,2,3] -> [1],
+            [0,1,2] -> [2],
+            [0,1,3] -> [1],
+            [0,2,3] -> [0],
+            [1,2,3] -> [3],
+            [0,1] -> [1],
+            [0,2] -> [0],
+            [1,2] -> [2],
+            [1,3] -> [],
+            [2,3] -> [2]
+        ];
+
+        let strict_partial_orders : Vec<Preorder> = fast_preorder::Stream::new(alt_count, true, false)
+            .map(|p| Preorder::from_fast_preorder(alt_count, p))
+            .collect();
+
+        let mut expected_penalty = u32::MAX;
+        let mut expected_instances = HashSet::new();
+        for p in &strict_partial_orders {
+            for q in &strict_partial_orders {
+                let penalty = Instance::SequentiallyRationalizableChoice(p.clone(), q.clone())
+                    .penalty(&choices).upper_bound;
+
+                if penalty < expected_penalty {
+                    expected_penalty = penalty;
+                    expected_instances.clear();
+                }
+
+                if penalty == expected_penalty {
+                    expected_instances.insert((p.clone(), q.clone()));
+                }
+            }
+        }
+
+        let (penalty, instances) = sequentially_rationalizable_choice(alt_count, &choices, u32::MAX, &CancelFlag::new())
+            .unwrap().unwrap();
+        assert!(expected_penalty > 1);
+        assert_eq!(penalty, Penalty::exact(expected_penalty));
+        assert_eq!(HashSet::from_iter(instances), expected_instances);
+
+        // nothing within a tighter bound
+        assert!(sequentially_rationalizable_choice(alt_count, &choices, expected_penalty - 1, &CancelFlag::new())
+            .unwrap().is_none());
+    }
+
+    #[test]
+    fn src_warm_start() {
+        // complete data generated by an SRC instance
+        let alt_count = 4;
+        let mut p = Preorder::diagonal(alt_count);
+        p.set_leq(Alt(0), Alt(2), true);
+        let q = Preorder::from_values(&[0, 1, 2, 3]);
+        let inst = Instance::SequentiallyRationalizableChoice(p, q);
+
+        let choices : Vec<ChoiceRow> = AltSet::powerset(alt_count)
+            .filter(|menu| menu.size() >= 2)
+            .map(|menu| ChoiceRow{
+                choice: inst.choice(menu.view(), None),
+                menu,
+                default: None,
+            })
+            .collect();
+
+        // Dutta and Horan's rationales are partial orders on such data
+        let (p, q) = approximate_estimation::sequentially_rationalizable_choice(alt_count, &choices).unwrap();
+        for r in &[p, q] {
+            assert!(r.is_transitive());
+            assert!(r.is_reflexive());
+            assert!(r.is_strict());
+        }
+
+        let (penalty, _) = sequentially_rationalizable_choice(alt_count, &choices, 0, &CancelFlag::new()).unwrap().unwrap();
+        assert_eq!(penalty, Penalty::exact(0));
+    }
+
+    #[test]
+    fn src_perfect_beyond_exact_limit() {
+        // consistent with a linear order, so perfectly SRC
+        let alt_count = MAX_ALTERNATIVES_SRC + 1;
+        let choices : Vec<ChoiceRow> = Alt::distinct_pairs(alt_count)
+            .map(|(i, j)| ChoiceRow{
+                menu: AltSet::from_iter(&[i, j]),
+                default: None,
+                choice: AltSet::singleton(j),
+            })
+            .collect();
+
+        let (penalty, instances) = sequentially_rationalizable_choice(alt_count, &choices, u32::MAX, &CancelFlag::new())
+            .unwrap().unwrap();
+        assert_eq!(penalty, Penalty::exact(0));
+        assert!(!instances.is_empty());
+    }
+
+    #[test]
+    fn src_bound_beyond_exact_limit() {
+        // two different choices from the same menu:
+        // no instance gets both right, so we get a bound from UM
+        let alt_count = MAX_ALTERNATIVES_SRC + 1;
+        let choices = choices![
+            [0,1] -> [0],
+            [0,1] -> [1],
+            [1,2] -> [],
+            [2,3,4,5] -> [5]
+        ];
+
+        let (penalty, instances) = sequentially_rationalizable_choice(alt_count, &choices, u32::MAX, &CancelFlag::new())
+            .unwrap().unwrap();
+        assert_eq!(penalty, Penalty{lower_bound: 2, upper_bound: 2});
+        assert!(!instances.is_empty());
+
+        assert!(sequentially_rationalizable_choice(alt_count, &choices, 1, &CancelFlag::new()).unwrap().is_none());
+    }
+
+    #[test]
+    fn limited_attention_matches_brute_force() {
+        // all attention filters on 3 alternatives,
+        // as consideration sets indexed by the bit masks of menus
+        let alt_count = 3;
+        let full = (1u32 << alt_count) - 1;
+        let menus : Vec<u32> = (1..=full).filter(|m| m.count_ones() >= 2).collect();
+
+        let mut filters : Vec<Vec<u32>> = vec![(0..=full).collect()];
+        for &menu in &menus {
+            filters = filters.iter().flat_map(|filter|
+                (1..=menu).filter(move |&sub| sub & !menu == 0).map(move |sub| {
+                    let mut filter = filter.clone();
+                    filter[menu as usize] = sub;
+                    filter
+                })
+            ).collect();
+        }
+        filters.retain(|filter| menus.iter().all(|&menu|
+            (0..alt_count).all(|y| {
+                let overlooked = menu & !filter[menu as usize] & (1 << y) != 0;
+                !overlooked || filter[(menu & !(1 << y)) as usize] == filter[menu as usize]
+            })
+        ));
+
+        let orders : Vec<Preorder> = ::linear_preorders::all(alt_count).collect();
+        let best = |p : &Preorder, set : u32| Alt::all(alt_count).find(|&a|
+            set & (1 << a.index()) != 0
+            && !Alt::all(alt_count).any(|b| set & (1 << b.index()) != 0 && p.lt(a, b))
+        ).unwrap();
+
+        // all datasets: every menu is either unobserved or has a single choice
+        let mut datasets : Vec<Vec<ChoiceRow>> = vec![Vec::new()];
+        for &menu in &menus {
+            datasets = datasets.iter().flat_map(|choices| {
+                let unobserved = choices.clone();
+                Alt::all(alt_count).filter(move |a| menu & (1 << a.index()) != 0).map(move |a| {
+                    let mut choices = choices.clone();
+                    choices.push(ChoiceRow{
+                        menu: AltSet::from_block(menu),
+                        default: None,
+                        choice: AltSet::singleton(a),
+                    });
+                    choices
+                }).chain(Some(unobserved))
+            }).collect();
+        }
+
+        for choices in &datasets {
+            let expected_penalty = orders.iter().flat_map(|p| filters.iter().map(move |filter|
+                choices.iter().filter(|cr| {
+                    let menu = bit_mask(cr.menu.view());
+                    cr.choice != AltSet::singleton(best(p, filter[menu as usize]))
+                }).count() as u32
+            )).min().unwrap();
+
+            let mut penalty = u32::MAX;
+            for p in &orders {
+                let (p_penalty, filter) = limited_attention_filter(p, choices);
+                let inst = Instance::LimitedAttention{p: p.clone(), filter};
+                assert_eq!(inst.penalty(choices), Penalty::exact(p_penalty));
+                penalty = cmp::min(penalty, p_penalty);
+            }
+
+            assert_eq!(penalty, expected_penalty);
+
+            let (penalty, instances) = limited_attention(alt_count, choices, u32::MAX, &CancelFlag::new()).unwrap().unwrap();
+            assert_eq!(penalty, Penalty::exact(expected_penalty));
+            for (p, filter) in instances {
+                let inst = Instance::LimitedAttention{p, filter};
+                assert_eq!(inst.penalty(choices), penalty);
+            }
+        }
+    }
+
+    #[test]
+    fn src_too_many_alternatives() {
+        assert!(sequentially_rationalizable_choice(MAX_ALTERNATIVES_SRC_PERFECT + 1, &[], u32::MAX, &CancelFlag::new()).is_err());
+    }
+}
@@ -4,6 +4,7 @@ use std::io::{Read,Write};
 use void::Void;
 use codec::{Encode,Decode,Packed,self};
 use rpc_common::{Subject};
+use json::{self,Json,ToJson,FromJson};
 
 type Error = Void;
 
@@ -20,6 +21,12 @@ impl Decode for Request {
     }
 }
 
+impl FromJson for Request {
+    fn from_json(js : &Json) -> json::Result<Request> {
+        Ok(Request{ subject: json::field(js, "subject")? })
+    }
+}
+
 // usize for convenience
 pub struct Response {
     name : String,
@@ -38,6 +45,18 @@ impl Encode for Response {
     }
 }
 
+impl ToJson for Response {
+    fn to_json(&self) -> Json {
+        json::object(vec![
+            ("subject", self.name.to_json()),
+            ("observations", self.observations.to_json()),
+            ("active_choices", self.active_choices.to_json()),
+            ("active_choices_binary", self.active_choices_binary.to_json()),
+            ("deferrals", self.deferrals.to_json()),
+        ])
+    }
+}
+
 pub fn run(request : Request) -> Result<Response> {
     let Request{ subject: Packed(Subject{name, choices, ..}) } = request;
 
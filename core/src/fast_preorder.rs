@@ -120,3 +120,196 @@ pub fn all(n : u32) -> Vec<FastPreorder> {
 
     history.keys().cloned().map(FastPreorder).collect()
 }
+
+// FastPreorder stores one 8-bit row per alternative
+pub const MAX_SIZE : u32 = 8;
+
+/* Streaming enumeration of preorders without materialising them.
+ *
+ * Every preorder on {0..k} is obtained from its restriction to {0..k-1}
+ * by adding the element k in exactly one way:
+ * - either k is equivalent to some existing class (we pick the class representative),
+ * - or k forms a new class, determined by its strict upset U and strict downset D,
+ *   where U is up-closed, D is down-closed, they are disjoint,
+ *   and every element of D is below every element of U.
+ *
+ * Hence every preorder is generated exactly once, and we can restrict the generation
+ * to partial orders (no equivalences) or weak orders (D and U cover everything)
+ * because both properties are inherited by restrictions.
+ *
+ * Memory usage is bounded by the candidate lists of the (at most MAX_SIZE) levels.
+ */
+pub struct Stream {
+    size : u32,
+    strict_only : bool,
+    total_only : bool,
+    stack : Vec<Vec<Matrix>>,  // pending candidates for each level
+}
+
+impl Stream {
+    pub fn new(size : u32, strict_only : bool, total_only : bool) -> Stream {
+        assert!(size <= MAX_SIZE);
+
+        Stream {
+            size,
+            strict_only,
+            total_only,
+            stack: if size == 0 {
+                vec![vec![0]]  // the empty preorder
+            } else {
+                vec![vec![1]]  // the only preorder on one element
+            },
+        }
+    }
+
+    // all ways of adding the element k to the preorder m on {0..k-1}
+    fn extensions(&self, m : Matrix, k : usize) -> Vec<Matrix> {
+        let everything : u32 = (1 << k) - 1;
+        let upset = |i : usize| ((m >> (8*i)) & 0xFF) as u32 & everything;
+        let downset = |j : usize| (0..k).filter(|&i| ix(m, i, j)).fold(0, |s, i| s | (1 << i));
+        let is_up_closed = |s : u32| (0..k).all(|i| s & (1 << i) == 0 || upset(i) & !s == 0);
+        let is_down_closed = |s : u32| (0..k).all(|j| s & (1 << j) == 0 || downset(j) & !s == 0);
+
+        let diagonal = 1 << (8*k + k);
+        let mut result = Vec::new();
+
+        // k joins an existing class
+        if !self.strict_only {
+            for e in 0..k {
+                if (0..e).any(|i| ix(m, i, e) && ix(m, e, i)) {
+                    // not the class representative
+                    continue;
+                }
+
+                let mut new_m = m | diagonal;
+                for i in 0..k {
+                    if ix(m, i, e) { new_m |= 1 << (8*i + k); }
+                    if ix(m, e, i) { new_m |= 1 << (8*k + i); }
+                }
+                new_m |= 1 << (8*e + k);
+                new_m |= 1 << (8*k + e);
+                result.push(new_m);
+            }
+        }
+
+        // k forms a new class
+        for up in 0 ..= everything {
+            if !is_up_closed(up) {
+                continue;
+            }
+
+            // elements below everything in `up`
+            let below = (0..k).filter(
+                |&i| upset(i) & up == up
+            ).fold(0, |s, i| s | (1 << i)) & !up;
+
+            let mut add_down = |down : u32| {
+                if !is_down_closed(down) {
+                    return;
+                }
+
+                let mut new_m = m | diagonal;
+                for i in 0..k {
+                    if up & (1 << i) != 0 { new_m |= 1 << (8*k + i); }
+                    if down & (1 << i) != 0 { new_m |= 1 << (8*i + k); }
+                }
+                result.push(new_m);
+            };
+
+            if self.total_only {
+                let down = everything & !up;
+                if down & !below == 0 {
+                    add_down(down);
+                }
+            } else {
+                // all subsets of `below`
+                let mut down = below;
+                loop {
+                    add_down(down);
+                    if down == 0 {
+                        break;
+                    }
+                    down = (down - 1) & below;
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Like `next()` but skips all extensions of the partial preorders
+    /// (on the first `k` alternatives) for which `prune(k, p)` returns true.
+    pub fn next_pruned<P>(&mut self, prune : &mut P) -> Option<FastPreorder>
+        where P : FnMut(u32, FastPreorder) -> bool
+    {
+        loop {
+            let m = match self.stack.last_mut()?.pop() {
+                Some(m) => m,
+                None => {
+                    // this level is exhausted
+                    self.stack.pop();
+                    continue;
+                }
+            };
+
+            let elements = if self.size == 0 { 0 } else { self.stack.len() };
+            if prune(elements as u32, FastPreorder(m)) {
+                continue;
+            }
+
+            if elements == self.size as usize {
+                return Some(FastPreorder(m));
+            }
+
+            let candidates = self.extensions(m, elements);
+            self.stack.push(candidates);
+        }
+    }
+}
+
+impl Iterator for Stream {
+    type Item = FastPreorder;
+
+    fn next(&mut self) -> Option<FastPreorder> {
+        self.next_pruned(&mut |_, _| false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use preorder::Preorder;
+
+    fn count(size : u32, strict_only : bool, total_only : bool) -> usize {
+        Stream::new(size, strict_only, total_only).inspect(|&p| {
+            let p = Preorder::from_fast_preorder(size, p);
+            assert!(p.is_reflexive());
+            assert!(p.is_transitive());
+            assert!(!strict_only || p.is_strict());
+            assert!(!total_only || p.is_total());
+        }).count()
+    }
+
+    #[test]
+    fn counts() {
+        // OEIS A000798, A001035, A000670, A000142
+        assert_eq!((0..6).map(|n| count(n, false, false)).collect::<Vec<_>>(), vec![1, 1, 4, 29, 355, 6942]);
+        assert_eq!((0..6).map(|n| count(n, true, false)).collect::<Vec<_>>(), vec![1, 1, 3, 19, 219, 4231]);
+        assert_eq!((0..6).map(|n| count(n, false, true)).collect::<Vec<_>>(), vec![1, 1, 3, 13, 75, 541]);
+        assert_eq!((0..6).map(|n| count(n, true, true)).collect::<Vec<_>>(), vec![1, 1, 2, 6, 24, 120]);
+    }
+
+    #[test]
+    fn same_as_precomputed() {
+        use std::collections::HashSet;
+
+        for n in 0..5 {
+            let mut streamed : Vec<u64> = Stream::new(n, false, false).map(|FastPreorder(m)| m).collect();
+            let mut precomputed : Vec<u64> = all(n).into_iter().map(|FastPreorder(m)| m).collect();
+            streamed.sort();
+            precomputed.sort();
+            assert_eq!(streamed.iter().collect::<HashSet<_>>().len(), streamed.len());
+            assert_eq!(streamed, precomputed);
+        }
+    }
+}
@@ -48,3 +48,89 @@ impl<V> Graph<V> {
         )
     }
 }
+
+struct Tarjan<'a> {
+    successors : &'a dyn Fn(usize) -> Vec<usize>,
+    index : Vec<Option<usize>>,
+    lowlink : Vec<usize>,
+    on_stack : Vec<bool>,
+    stack : Vec<usize>,
+    next_index : usize,
+    components : Vec<Vec<usize>>,
+}
+
+impl<'a> Tarjan<'a> {
+    fn visit(&mut self, v : usize) {
+        self.index[v] = Some(self.next_index);
+        self.lowlink[v] = self.next_index;
+        self.next_index += 1;
+        self.stack.push(v);
+        self.on_stack[v] = true;
+
+        for w in (self.successors)(v) {
+            match self.index[w] {
+                None => {
+                    self.visit(w);
+                    self.lowlink[v] = self.lowlink[v].min(self.lowlink[w]);
+                }
+                Some(index_w) if self.on_stack[w] => {
+                    self.lowlink[v] = self.lowlink[v].min(index_w);
+                }
+                Some(_) => (),  // in a finished component
+            }
+        }
+
+        if Some(self.lowlink[v]) == self.index[v] {
+            let mut component = Vec::new();
+            loop {
+                let w = self.stack.pop().unwrap();  // v is still on the stack
+                self.on_stack[w] = false;
+                component.push(w);
+                if w == v {
+                    break;
+                }
+            }
+            component.sort();
+            self.components.push(component);
+        }
+    }
+}
+
+/// Tarjan's algorithm: the strongly connected components of the graph
+/// on vertices `0..n`, in reverse topological order, in time O(n + edges).
+pub fn strongly_connected_components(n : usize, successors : &dyn Fn(usize) -> Vec<usize>) -> Vec<Vec<usize>> {
+    let mut tarjan = Tarjan {
+        successors,
+        index: vec![None; n],
+        lowlink: vec![0; n],
+        on_stack: vec![false; n],
+        stack: Vec::new(),
+        next_index: 0,
+        components: Vec::new(),
+    };
+
+    for v in 0..n {
+        if tarjan.index[v].is_none() {
+            tarjan.visit(v);
+        }
+    }
+
+    tarjan.components
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn components() {
+        // 0 <-> 1 -> 2 -> 3 -> 2, 4 alone
+        let edges = [(0, 1), (1, 0), (1, 2), (2, 3), (3, 2)];
+        let successors = |v| edges.iter().filter(|&&(p, _)| p == v).map(|&(_, q)| q).collect();
+
+        assert_eq!(
+            strongly_connected_components(5, &successors),
+            vec![vec![2, 3], vec![0, 1], vec![4]],
+        );
+    }
+}
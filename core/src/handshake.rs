@@ -0,0 +1,143 @@
+use std::io::{Read,Write};
+use std::fmt;
+use std::result::Result;
+use codec::{self,Encode,Decode};
+use json::{self,Json,ToJson,FromJson};
+use model::Model;
+use stochastic::StochasticModel;
+use rpc;
+
+/* The "hello" request lets a front-end check that it speaks the same protocol
+ * as the core before sending anything else, and find out what the core supports.
+ *
+ * The protocol version in `rpc::PROTOCOL_VERSION` must be bumped
+ * whenever the encoding of a request or a response changes.
+ */
+
+#[derive(Debug)]
+pub struct Request {
+    pub protocol_version : u32,  // the version spoken by the front-end
+}
+
+impl Decode for Request {
+    fn decode<R : Read>(f : &mut R) -> codec::Result<Request> {
+        Ok(Request {
+            protocol_version: Decode::decode(f)?,
+        })
+    }
+}
+
+impl FromJson for Request {
+    fn from_json(js : &Json) -> json::Result<Request> {
+        Ok(Request {
+            protocol_version: json::field(js, "protocol_version")?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub protocol_version : u32,
+    pub core_version : String,
+    pub tags : Vec<String>,
+    pub models : Vec<(Model, u32)>,  // (model, max alternatives)
+    pub stochastic_models : Vec<(StochasticModel, Option<u32>)>,  // (model, max alternatives)
+}
+
+impl Encode for Response {
+    fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+        (
+            self.protocol_version,
+            &self.core_version,
+            &self.tags,
+            &self.models,
+            &self.stochastic_models,
+        ).encode(f)
+    }
+}
+
+impl ToJson for Response {
+    fn to_json(&self) -> Json {
+        fn limits<M : ToJson, L : ToJson>(models : &[(M, L)]) -> Json {
+            Json::Array(models.iter().map(|(model, max_alternatives)| json::object(vec![
+                ("model", model.to_json()),
+                ("max_alternatives", max_alternatives.to_json()),
+            ])).collect())
+        }
+
+        json::object(vec![
+            ("protocol_version", self.protocol_version.to_json()),
+            ("core_version", self.core_version.to_json()),
+            ("tags", self.tags.to_json()),
+            ("models", limits(&self.models)),
+            ("stochastic_models", limits(&self.stochastic_models)),
+        ])
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    ProtocolMismatch {
+        core : u32,
+        front_end : u32,
+    },
+}
+
+impl Encode for Error {
+    fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+        match *self {
+            Error::ProtocolMismatch{core, front_end} => (0u8, core, front_end).encode(f),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::ProtocolMismatch{core, front_end} => write!(f,
+                "protocol mismatch: the core speaks version {} but the front-end speaks version {}; \
+                please install matching versions of the core and the front-end",
+                core, front_end,
+            ),
+        }
+    }
+}
+
+pub fn run(req : &Request) -> Result<Response, Error> {
+    if req.protocol_version != rpc::PROTOCOL_VERSION {
+        return Err(Error::ProtocolMismatch{
+            core: rpc::PROTOCOL_VERSION,
+            front_end: req.protocol_version,
+        });
+    }
+
+    Ok(Response {
+        protocol_version: rpc::PROTOCOL_VERSION,
+        core_version: String::from(env!("CARGO_PKG_VERSION")),
+        tags: rpc::TAGS.iter().map(|&tag| String::from(tag)).collect(),
+        models: Model::all().into_iter().map(
+            |model| (model, model.max_alternatives())
+        ).collect(),
+        stochastic_models: StochasticModel::all().into_iter().map(
+            |model| (model, model.max_alternatives())
+        ).collect(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hello() {
+        let resp = run(&Request{protocol_version: rpc::PROTOCOL_VERSION}).unwrap();
+        assert!(resp.tags.iter().any(|tag| tag == "hello"));
+        assert!(resp.models.contains(&(Model::TopTwo, 10)));
+        assert!(resp.stochastic_models.contains(&(StochasticModel::Luce, None)));
+
+        assert_eq!(
+            run(&Request{protocol_version: 0}).unwrap_err(),
+            Error::ProtocolMismatch{core: rpc::PROTOCOL_VERSION, front_end: 0}
+        );
+    }
+}
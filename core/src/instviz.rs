@@ -1,13 +1,14 @@
 use std::io::{Read,Write};
 use codec;
 use codec::{Decode,Encode};
+use json::{self,Json,ToJson,FromJson};
 use std::fmt;
 use std::result::Result;
 use base64::prelude::BASE64_STANDARD;
 use base64::engine::Engine;
 
 use alt_set::AltSet;
-use model::Instance;
+use model::{Instance,ATTENTION_DENOMINATOR};
 use preorder::Preorder;
 
 #[derive(Debug)]
@@ -23,6 +24,14 @@ impl Decode for Request {
     }
 }
 
+impl FromJson for Request {
+    fn from_json(js : &Json) -> json::Result<Self> {
+        Ok(Request {
+            instance_code: json::field(js, "instance_code")?,
+        })
+    }
+}
+
 pub struct GraphRepr {
     vertices : Vec<AltSet>,  // classes of equivalence
     edges : Vec<(AltSet, AltSet)>,  // (P, Q) such that P ≥ Q
@@ -34,6 +43,15 @@ impl Encode for GraphRepr {
     }
 }
 
+impl ToJson for GraphRepr {
+    fn to_json(&self) -> Json {
+        json::object(vec![
+            ("vertices", self.vertices.to_json()),
+            ("edges", self.edges.to_json()),
+        ])
+    }
+}
+
 pub struct Response {
     graphs : Vec<GraphRepr>,
     extra_info : Vec<(String, String)>,
@@ -45,6 +63,15 @@ impl Encode for Response {
     }
 }
 
+impl ToJson for Response {
+    fn to_json(&self) -> Json {
+        json::object(vec![
+            ("graphs", self.graphs.to_json()),
+            ("extra_info", self.extra_info.to_json()),
+        ])
+    }
+}
+
 pub enum Error {
     Base64Decode(base64::DecodeError),
     Codec(codec::Error),
@@ -164,5 +191,26 @@ pub fn run(req : Request) -> Result<Response, Error> {
                 graphs: vec![graph_repr(p), graph_repr(q)],
                 extra_info: vec![],
             }),
+
+        Instance::LimitedAttention{ref p, ref filter} =>
+            Ok(Response{
+                // revealed preference and the consideration sets of the explained menus
+                graphs: vec![graph_repr(p)],
+                extra_info: filter.iter().map(|&(ref menu, choice)| {
+                    let considered : AltSet = menu.view().iter().filter(|&a| !p.lt(choice, a)).collect();
+                    (format!("Consideration set of {}", menu), considered.to_string())
+                }).collect(),
+            }),
+
+        Instance::RandomConsideration{ref p, ref attention} =>
+            Ok(Response{
+                graphs: vec![graph_repr(p)],
+                extra_info: attention.iter().enumerate().map(
+                    |(i, &level)| (
+                        format!("Attention to alternative {}", i),
+                        format!("{}/{}", level, ATTENTION_DENOMINATOR),
+                    )
+                ).collect(),
+            }),
     }
 }
@@ -1,7 +1,7 @@
 use num::BigUint;
 use std::io::{Read,Write};
 use std::fmt::{self, Debug, Display, Formatter};
-use std::ops::{MulAssign, Mul, AddAssign, Add};
+use std::ops::{MulAssign, Mul, AddAssign, Add, Sub};
 use std::iter::Sum;
 use num::{Zero, One, zero, one};
 use byteorder::ReadBytesExt;
@@ -65,6 +65,14 @@ impl Add<Integer> for Integer {
     }
 }
 
+// panics if the result would be negative
+impl Sub<Integer> for Integer {
+    type Output = Integer;
+    fn sub(self, other : Integer) -> Integer {
+        Integer{ value: self.value - other.value }
+    }
+}
+
 impl Debug for Integer {
     fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
         Debug::fmt(&self.value, f)
@@ -7,10 +7,11 @@ use alt_set::{AltSet};
 use codec::{self,Packed,Encode,Decode};
 use rpc_common::{Subject};
 use std::collections::HashMap;
+use json::{self,Json,ToJson,FromJson};
 
 #[derive(Debug)]
 pub struct Request {
-    subject : Packed<Subject>
+    pub subject : Packed<Subject>
 }
 
 impl Decode for Request {
@@ -21,6 +22,14 @@ impl Decode for Request {
     }
 }
 
+impl FromJson for Request {
+    fn from_json(js : &Json) -> json::Result<Request> {
+        Ok(Request {
+            subject: json::field(js, "subject")?
+        })
+    }
+}
+
 enum Issue {
     RepeatedMenu(AltSet),
     ChoiceNotInMenu(AltSet, Alt),
@@ -51,6 +60,36 @@ impl Encode for Response {
     }
 }
 
+impl Response {
+    /// With the alternatives by name rather than by index.
+    pub fn to_named_json(&self, alternatives : &[String]) -> Json {
+        self.json_with(Some(alternatives))
+    }
+
+    fn json_with(&self, alternatives : Option<&[String]>) -> Json {
+        json::object(vec![
+            ("subject", self.subject_name.to_json()),
+            ("issues", Json::Array(self.issues.iter().map(|issue| match *issue {
+                Issue::RepeatedMenu(ref menu) => json::object(vec![
+                    ("issue", String::from("repeated-menu").to_json()),
+                    ("menu", json::alt_set(menu, alternatives)),
+                ]),
+                Issue::ChoiceNotInMenu(ref menu, choice) => json::object(vec![
+                    ("issue", String::from("choice-not-in-menu").to_json()),
+                    ("menu", json::alt_set(menu, alternatives)),
+                    ("choice", json::alt(choice, alternatives)),
+                ]),
+            }).collect())),
+        ])
+    }
+}
+
+impl ToJson for Response {
+    fn to_json(&self) -> Json {
+        self.json_with(None)
+    }
+}
+
 pub enum IntegrityError {
 }
 
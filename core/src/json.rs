@@ -0,0 +1,605 @@
+use std::fmt;
+use std::result;
+use std::str::Chars;
+use std::iter::Peekable;
+use integer::Integer;
+use model::Penalty;
+use alt::Alt;
+use alt_set::AltSet;
+use matrix::Matrix;
+use codec::Packed;
+
+/* A minimal JSON value for the command-line interface and the JSON RPC transport.
+ *
+ * Numbers are kept as text so that big integers (cycle counts)
+ * are printed exactly. Non-finite floats become `null`.
+ *
+ * For CSV output, objects are flattened into columns
+ * named by dotted paths, while arrays stay as JSON text in one cell.
+ *
+ * `ToJson` and `FromJson` mirror `Encode` and `Decode`: structs become objects
+ * with the same field names, alternatives are given by their indices,
+ * and sets of alternatives are arrays of indices.
+ */
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+pub trait ToJson {
+    fn to_json(&self) -> Json;
+}
+
+pub trait FromJson {
+    fn from_json(json : &Json) -> Result<Self> where Self : Sized;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    Syntax{position : usize},
+    Expected(&'static str),
+    MissingField(String),
+    BadTag(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Syntax{position} => write!(f, "JSON syntax error at character {}", position),
+            Error::Expected(what) => write!(f, "expected {}", what),
+            Error::MissingField(ref name) => write!(f, "missing field: {}", name),
+            Error::BadTag(ref tag) => write!(f, "unknown tag: {}", tag),
+        }
+    }
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+/// Build an object from (key, value) pairs, keeping their order.
+pub fn object(fields : Vec<(&str, Json)>) -> Json {
+    Json::Object(fields.into_iter().map(|(k, v)| (String::from(k), v)).collect())
+}
+
+fn write_string(f : &mut fmt::Formatter, s : &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(ref x) => write!(f, "{}", x),
+            Json::String(ref s) => write_string(f, s),
+            Json::Array(ref xs) => {
+                write!(f, "[")?;
+                for (i, x) in xs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", x)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(ref fields) => {
+                write!(f, "{{")?;
+                for (i, (k, v)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, k)?;
+                    write!(f, ":{}", v)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+impl Json {
+    /// (column, cell) pairs for a CSV row; see the module comment.
+    pub fn flatten(&self) -> Vec<(String, String)> {
+        let mut cells = Vec::new();
+        self.flatten_into("", &mut cells);
+        cells
+    }
+
+    fn flatten_into(&self, prefix : &str, cells : &mut Vec<(String, String)>) {
+        match *self {
+            Json::Object(ref fields) => for (k, v) in fields {
+                if prefix.is_empty() {
+                    v.flatten_into(k, cells);
+                } else {
+                    v.flatten_into(&format!("{}.{}", prefix, k), cells);
+                }
+            },
+            Json::Null => cells.push((String::from(prefix), String::new())),
+            Json::String(ref s) => cells.push((String::from(prefix), s.clone())),
+            ref other => cells.push((String::from(prefix), other.to_string())),
+        }
+    }
+}
+
+struct Parser<'a> {
+    chars : Peekable<Chars<'a>>,
+    position : usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T>(&self) -> Result<T> {
+        Err(Error::Syntax{position: self.position})
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        if c.is_some() {
+            self.position += 1;
+        }
+        c
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.peek().is_some_and(|c| c.is_whitespace()) {
+            self.next();
+        }
+    }
+
+    fn expect(&mut self, word : &str) -> Result<()> {
+        for c in word.chars() {
+            if self.next() != Some(c) {
+                return self.error();
+            }
+        }
+        Ok(())
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.expect("\"")?;
+        let mut s = String::new();
+        loop {
+            match self.next() {
+                None => return self.error(),
+                Some('"') => return Ok(s),
+                Some('\\') => match self.next() {
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some('/') => s.push('/'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some('n') => s.push('\n'),
+                    Some('r') => s.push('\r'),
+                    Some('t') => s.push('\t'),
+                    Some('u') => {
+                        let mut code = 0;
+                        for _ in 0..4 {
+                            match self.next().and_then(|c| c.to_digit(16)) {
+                                Some(d) => code = 16*code + d,
+                                None => return self.error(),
+                            }
+                        }
+                        // surrogate pairs are not combined
+                        s.push(::std::char::from_u32(code).unwrap_or('\u{fffd}'));
+                    }
+                    _ => return self.error(),
+                },
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Json> {
+        let mut text = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if c.is_ascii_digit() || "+-.eE".contains(c) {
+                text.push(c);
+                self.next();
+            } else {
+                break;
+            }
+        }
+
+        match text.parse::<f64>() {
+            Ok(_) => Ok(Json::Number(text)),
+            Err(_) => self.error(),
+        }
+    }
+
+    fn value(&mut self) -> Result<Json> {
+        self.skip_whitespace();
+        let result = match self.chars.peek() {
+            Some('n') => self.expect("null").map(|_| Json::Null),
+            Some('t') => self.expect("true").map(|_| Json::Bool(true)),
+            Some('f') => self.expect("false").map(|_| Json::Bool(false)),
+            Some('"') => self.string().map(Json::String),
+            Some('[') => {
+                self.next();
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.chars.peek() == Some(&']') {
+                    self.next();
+                } else {
+                    loop {
+                        items.push(self.value()?);
+                        self.skip_whitespace();
+                        match self.next() {
+                            Some(',') => continue,
+                            Some(']') => break,
+                            _ => return self.error(),
+                        }
+                    }
+                }
+                Ok(Json::Array(items))
+            }
+            Some('{') => {
+                self.next();
+                let mut fields = Vec::new();
+                self.skip_whitespace();
+                if self.chars.peek() == Some(&'}') {
+                    self.next();
+                } else {
+                    loop {
+                        self.skip_whitespace();
+                        let key = self.string()?;
+                        self.skip_whitespace();
+                        self.expect(":")?;
+                        fields.push((key, self.value()?));
+                        self.skip_whitespace();
+                        match self.next() {
+                            Some(',') => continue,
+                            Some('}') => break,
+                            _ => return self.error(),
+                        }
+                    }
+                }
+                Ok(Json::Object(fields))
+            }
+            Some(_) => self.number(),
+            None => self.error(),
+        };
+        self.skip_whitespace();
+        result
+    }
+}
+
+/// Parse a complete JSON document.
+pub fn parse(text : &str) -> Result<Json> {
+    let mut parser = Parser{chars: text.chars().peekable(), position: 0};
+    let json = parser.value()?;
+    if parser.chars.peek().is_some() {
+        return parser.error();
+    }
+    Ok(json)
+}
+
+impl Json {
+    pub fn get(&self, key : &str) -> Option<&Json> {
+        match *self {
+            Json::Object(ref fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_number<T : ::std::str::FromStr>(&self, what : &'static str) -> Result<T> {
+        match *self {
+            Json::Number(ref x) => x.parse().map_err(|_| Error::Expected(what)),
+            _ => Err(Error::Expected(what)),
+        }
+    }
+}
+
+/// Decode a field of an object. A missing field counts as `null`,
+/// which is fine for optional values.
+pub fn field<T : FromJson>(json : &Json, key : &str) -> Result<T> {
+    match *json {
+        Json::Object(_) => (),
+        _ => return Err(Error::Expected("an object")),
+    }
+
+    match json.get(key) {
+        Some(value) => T::from_json(value),
+        None => T::from_json(&Json::Null).map_err(|_| Error::MissingField(String::from(key))),
+    }
+}
+
+/// The `type` field of a tagged object.
+pub fn tag(json : &Json) -> Result<String> {
+    field(json, "type")
+}
+
+/// An alternative by name if the names are given, otherwise by index.
+pub fn alt(alt : Alt, alternatives : Option<&[String]>) -> Json {
+    match alternatives {
+        Some(names) => Json::String(names[alt.index() as usize].clone()),
+        None => alt.to_json(),
+    }
+}
+
+pub fn alt_set(alts : &AltSet, alternatives : Option<&[String]>) -> Json {
+    Json::Array(alts.view().iter().map(|a| alt(a, alternatives)).collect())
+}
+
+impl ToJson for bool {
+    fn to_json(&self) -> Json {
+        Json::Bool(*self)
+    }
+}
+
+impl ToJson for u32 {
+    fn to_json(&self) -> Json {
+        Json::Number(self.to_string())
+    }
+}
+
+impl ToJson for usize {
+    fn to_json(&self) -> Json {
+        Json::Number(self.to_string())
+    }
+}
+
+impl ToJson for f64 {
+    fn to_json(&self) -> Json {
+        if self.is_finite() {
+            Json::Number(self.to_string())
+        } else {
+            Json::Null
+        }
+    }
+}
+
+impl ToJson for f32 {
+    fn to_json(&self) -> Json {
+        (*self as f64).to_json()
+    }
+}
+
+impl ToJson for String {
+    fn to_json(&self) -> Json {
+        Json::String(self.clone())
+    }
+}
+
+impl ToJson for Integer {
+    fn to_json(&self) -> Json {
+        Json::Number(self.to_string())
+    }
+}
+
+impl ToJson for Penalty {
+    fn to_json(&self) -> Json {
+        object(vec![
+            ("lower_bound", self.lower_bound.to_json()),
+            ("upper_bound", self.upper_bound.to_json()),
+        ])
+    }
+}
+
+impl ToJson for u8 {
+    fn to_json(&self) -> Json {
+        Json::Number(self.to_string())
+    }
+}
+
+impl ToJson for () {
+    fn to_json(&self) -> Json {
+        Json::Null
+    }
+}
+
+impl ToJson for Alt {
+    fn to_json(&self) -> Json {
+        self.index().to_json()
+    }
+}
+
+impl ToJson for AltSet {
+    fn to_json(&self) -> Json {
+        alt_set(self, None)
+    }
+}
+
+// rows
+impl ToJson for Matrix<f32> {
+    fn to_json(&self) -> Json {
+        Json::Array(self.iter_rows().map(
+            |row| Json::Array(row.iter().map(ToJson::to_json).collect())
+        ).collect())
+    }
+}
+
+impl<T : ToJson> ToJson for Packed<T> {
+    fn to_json(&self) -> Json {
+        self.unpack().to_json()
+    }
+}
+
+impl<T : ToJson> ToJson for Option<T> {
+    fn to_json(&self) -> Json {
+        match *self {
+            None => Json::Null,
+            Some(ref x) => x.to_json(),
+        }
+    }
+}
+
+impl<T : ToJson> ToJson for Vec<T> {
+    fn to_json(&self) -> Json {
+        Json::Array(self.iter().map(ToJson::to_json).collect())
+    }
+}
+
+impl<A : ToJson, B : ToJson> ToJson for (A, B) {
+    fn to_json(&self) -> Json {
+        Json::Array(vec![self.0.to_json(), self.1.to_json()])
+    }
+}
+
+impl FromJson for bool {
+    fn from_json(json : &Json) -> Result<bool> {
+        match *json {
+            Json::Bool(b) => Ok(b),
+            _ => Err(Error::Expected("a boolean")),
+        }
+    }
+}
+
+impl FromJson for u8 {
+    fn from_json(json : &Json) -> Result<u8> {
+        json.as_number("a byte")
+    }
+}
+
+impl FromJson for u32 {
+    fn from_json(json : &Json) -> Result<u32> {
+        json.as_number("a non-negative integer")
+    }
+}
+
+impl FromJson for usize {
+    fn from_json(json : &Json) -> Result<usize> {
+        json.as_number("a non-negative integer")
+    }
+}
+
+impl FromJson for f32 {
+    fn from_json(json : &Json) -> Result<f32> {
+        json.as_number("a number")
+    }
+}
+
+impl FromJson for f64 {
+    fn from_json(json : &Json) -> Result<f64> {
+        json.as_number("a number")
+    }
+}
+
+impl FromJson for String {
+    fn from_json(json : &Json) -> Result<String> {
+        match *json {
+            Json::String(ref s) => Ok(s.clone()),
+            _ => Err(Error::Expected("a string")),
+        }
+    }
+}
+
+impl FromJson for Alt {
+    fn from_json(json : &Json) -> Result<Alt> {
+        u32::from_json(json).map(Alt)
+    }
+}
+
+impl FromJson for AltSet {
+    fn from_json(json : &Json) -> Result<AltSet> {
+        Vec::<Alt>::from_json(json).map(|alts| alts.into_iter().collect())
+    }
+}
+
+impl FromJson for Matrix<f32> {
+    fn from_json(json : &Json) -> Result<Matrix<f32>> {
+        let rows : Vec<Vec<f32>> = FromJson::from_json(json)?;
+        let ncols = rows.first().map_or(0, Vec::len);
+        if rows.iter().any(|row| row.len() != ncols) {
+            return Err(Error::Expected("rows of equal length"));
+        }
+        Ok(Matrix::new(rows.len(), ncols, rows.concat()))
+    }
+}
+
+impl<T : FromJson> FromJson for Packed<T> {
+    fn from_json(json : &Json) -> Result<Packed<T>> {
+        T::from_json(json).map(Packed)
+    }
+}
+
+impl<T : FromJson> FromJson for Option<T> {
+    fn from_json(json : &Json) -> Result<Option<T>> {
+        match *json {
+            Json::Null => Ok(None),
+            ref x => T::from_json(x).map(Some),
+        }
+    }
+}
+
+impl<T : FromJson> FromJson for Vec<T> {
+    fn from_json(json : &Json) -> Result<Vec<T>> {
+        match *json {
+            Json::Array(ref xs) => xs.iter().map(T::from_json).collect(),
+            _ => Err(Error::Expected("an array")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let json = object(vec![
+            ("name", String::from("a \"b\"\n").to_json()),
+            ("values", vec![1.5f64, f64::NAN].to_json()),
+            ("pairs", vec![(1u32, true)].to_json()),
+            ("missing", None::<u32>.to_json()),
+        ]);
+
+        assert_eq!(
+            json.to_string(),
+            r#"{"name":"a \"b\"\n","values":[1.5,null],"pairs":[[1,true]],"missing":null}"#
+        );
+    }
+
+    #[test]
+    fn flatten() {
+        let json = object(vec![
+            ("name", String::from("s1").to_json()),
+            ("score", Penalty{lower_bound: 1, upper_bound: 2}.to_json()),
+            ("rows", vec![3u32, 4].to_json()),
+            ("missing", Json::Null),
+        ]);
+
+        assert_eq!(json.flatten(), vec![
+            (String::from("name"), String::from("s1")),
+            (String::from("score.lower_bound"), String::from("1")),
+            (String::from("score.upper_bound"), String::from("2")),
+            (String::from("rows"), String::from("[3,4]")),
+            (String::from("missing"), String::new()),
+        ]);
+    }
+
+    #[test]
+    fn parse_round_trip() {
+        let text = r#"{"name":"a \"b\"\n","values":[1.5,-2e3],"ok":true,"missing":null}"#;
+        let json = parse(text).unwrap();
+        assert_eq!(json.to_string(), text);
+        assert_eq!(parse(&format!(" {} \n", json)).unwrap(), json);
+
+        let name : String = field(&json, "name").unwrap();
+        assert_eq!(name, "a \"b\"\n");
+        let values : Vec<f64> = field(&json, "values").unwrap();
+        assert_eq!(values, vec![1.5, -2000.0]);
+        let absent : Option<u32> = field(&json, "absent").unwrap();
+        assert_eq!(absent, None);
+        assert_eq!(field::<u32>(&json, "absent"), Err(Error::MissingField(String::from("absent"))));
+        assert_eq!(field::<u32>(&json, "ok"), Err(Error::Expected("a non-negative integer")));
+
+        assert_eq!(parse("[1, 2"), Err(Error::Syntax{position: 5}));
+        assert_eq!(parse("{} x"), Err(Error::Syntax{position: 3}));
+    }
+}
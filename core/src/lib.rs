@@ -23,10 +23,14 @@ pub mod benches;
 pub mod rpc;
 pub mod estimation;
 pub mod approximate_estimation;
+pub mod exact_estimation;
+pub mod stochastic;
+pub mod significance;
 pub mod model;
 pub mod precomputed;
 pub mod args;
 pub mod consistency;
+pub mod axioms;
 pub mod integer;
 pub mod simulation;
 pub mod experiment_stats;
@@ -38,3 +42,5 @@ pub mod integrity;
 pub mod csv;
 pub mod graph;
 pub mod instviz;
+pub mod json;
+pub mod handshake;
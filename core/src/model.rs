@@ -3,14 +3,18 @@ use preorder::Preorder;
 use alt_set::{AltSet,AltSetView};
 use std::result::Result;
 use linear_preorders;
+use exact_estimation;
+use fast_preorder::{self,FastPreorder};
 use precomputed::Precomputed;
 use precomputed::Error as PreorderError;
 use std::fmt;
-use std::cmp;
+use std::str::FromStr;
+use std::cell::Cell;
 use std::io::{Read,Write};
 use std::iter::FromIterator;
 use codec::{self,Encode,Decode};
-use rpc_common::{ChoiceRow};
+use json::{self,Json,ToJson,FromJson};
+use rpc_common::{ChoiceRow,CancelFlag};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct PreorderParams {
@@ -74,6 +78,18 @@ pub enum Model {
     TopTwo,
     SequentiallyRationalizableChoice,
     Swaps,
+
+    /// Masatlioglu-Nakajima-Ozbay limited attention: the decision maker
+    /// picks the best alternative (by a linear order) in a consideration set
+    /// that does not change when an overlooked alternative is removed from the menu.
+    LimitedAttention,
+
+    /// Manzini-Mariotti: every alternative is considered independently
+    /// with its own probability and the best considered alternative is chosen;
+    /// if nothing is considered, the choice is deferred.
+    /// The model deterministically predicts the most likely outcome,
+    /// with attention probabilities on a coarse grid; it is not a likelihood fit.
+    RandomConsideration,
 }
 
 impl Encode for Model {
@@ -88,6 +104,8 @@ impl Encode for Model {
             &Model::TopTwo => 6u8.encode(f),
             &Model::SequentiallyRationalizableChoice => 7u8.encode(f),
             &Model::Swaps => 8u8.encode(f),
+            &Model::LimitedAttention => 9u8.encode(f),
+            &Model::RandomConsideration => 10u8.encode(f),
         }
     }
 }
@@ -104,11 +122,215 @@ impl Decode for Model {
             6u8 => Ok(Model::TopTwo),
             7u8 => Ok(Model::SequentiallyRationalizableChoice),
             8u8 => Ok(Model::Swaps),
+            9u8 => Ok(Model::LimitedAttention),
+            10u8 => Ok(Model::RandomConsideration),
             _ => Err(codec::Error::BadEnumTag),
         }
     }
 }
 
+/* Model names for the command line: a kebab-case name,
+ * optionally followed by a colon and comma-separated flags,
+ * e.g. `preorder-maximization:strict,total` or `undominated-choice:nonstrict`.
+ *
+ * Preorder parameters that are not given are left unconstrained.
+ */
+
+const MODEL_NAMES : &[&str] = &[
+    "preorder-maximization",
+    "unattractiveness",
+    "undominated-choice",
+    "partially-dominant-choice",
+    "status-quo-undominated-choice",
+    "overload",
+    "top-two",
+    "sequentially-rationalizable-choice",
+    "swaps",
+    "limited-attention",
+    "random-consideration",
+];
+
+impl fmt::Display for Model {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        fn params(p : PreorderParams) -> Vec<&'static str> {
+            let mut flags = Vec::new();
+            match p.strict {
+                None => (),
+                Some(true) => flags.push("strict"),
+                Some(false) => flags.push("nonstrict"),
+            }
+            match p.total {
+                None => (),
+                Some(true) => flags.push("total"),
+                Some(false) => flags.push("partial"),
+            }
+            flags
+        }
+
+        let (name, flags) = match *self {
+            Model::PreorderMaximization(p) => (MODEL_NAMES[0], params(p)),
+            Model::Unattractiveness(p) => (MODEL_NAMES[1], params(p)),
+            Model::UndominatedChoice{strict} => (MODEL_NAMES[2], vec![if strict { "strict" } else { "nonstrict" }]),
+            Model::PartiallyDominantChoice{fc} => (MODEL_NAMES[3], vec![if fc { "fc" } else { "no-fc" }]),
+            Model::StatusQuoUndominatedChoice => (MODEL_NAMES[4], vec![]),
+            Model::Overload(p) => (MODEL_NAMES[5], params(p)),
+            Model::TopTwo => (MODEL_NAMES[6], vec![]),
+            Model::SequentiallyRationalizableChoice => (MODEL_NAMES[7], vec![]),
+            Model::Swaps => (MODEL_NAMES[8], vec![]),
+            Model::LimitedAttention => (MODEL_NAMES[9], vec![]),
+            Model::RandomConsideration => (MODEL_NAMES[10], vec![]),
+        };
+
+        if flags.is_empty() {
+            write!(f, "{}", name)
+        } else {
+            write!(f, "{}:{}", name, flags.join(","))
+        }
+    }
+}
+
+impl FromStr for Model {
+    type Err = String;
+
+    fn from_str(s : &str) -> Result<Model, String> {
+        let (name, flags) = match s.find(':') {
+            None => (s, Vec::new()),
+            Some(i) => (&s[..i], s[i+1..].split(',').map(str::trim).collect()),
+        };
+
+        let mut params = PreorderParams{strict: None, total: None};
+        let mut fc = None;
+        for &flag in &flags {
+            match flag {
+                "strict" => params.strict = Some(true),
+                "nonstrict" => params.strict = Some(false),
+                "total" => params.total = Some(true),
+                "partial" => params.total = Some(false),
+                "fc" => fc = Some(true),
+                "no-fc" => fc = Some(false),
+                _ => return Err(format!("unknown model flag \"{}\" in \"{}\"", flag, s)),
+            }
+        }
+
+        let no_flags = |model : Model| if flags.is_empty() {
+            Ok(model)
+        } else {
+            Err(format!("model \"{}\" takes no flags", name))
+        };
+
+        match name {
+            "preorder-maximization" => Ok(Model::PreorderMaximization(params)),
+            "unattractiveness" => Ok(Model::Unattractiveness(params)),
+            "overload" => Ok(Model::Overload(params)),
+            "undominated-choice" => match params.strict {
+                Some(strict) => Ok(Model::UndominatedChoice{strict}),
+                None => Err(String::from("undominated-choice needs the flag strict or nonstrict")),
+            },
+            "partially-dominant-choice" => match fc {
+                Some(fc) => Ok(Model::PartiallyDominantChoice{fc}),
+                None => Err(String::from("partially-dominant-choice needs the flag fc or no-fc")),
+            },
+            "status-quo-undominated-choice" => no_flags(Model::StatusQuoUndominatedChoice),
+            "top-two" => no_flags(Model::TopTwo),
+            "sequentially-rationalizable-choice" => no_flags(Model::SequentiallyRationalizableChoice),
+            "swaps" => no_flags(Model::Swaps),
+            "limited-attention" => no_flags(Model::LimitedAttention),
+            "random-consideration" => no_flags(Model::RandomConsideration),
+            _ => Err(format!("unknown model \"{}\"; known models: {}", name, MODEL_NAMES.join(", "))),
+        }
+    }
+}
+
+impl ToJson for Model {
+    fn to_json(&self) -> Json {
+        self.to_string().to_json()
+    }
+}
+
+impl FromJson for Model {
+    fn from_json(js : &Json) -> json::Result<Model> {
+        let name = String::from_json(js)?;
+        name.parse().map_err(|_| json::Error::BadTag(name))
+    }
+}
+
+// linear orders are enumerated by `linear_preorders`
+const MAX_ALTERNATIVES_LINEAR : u32 = 10;
+
+// unattractiveness enumerates preorders of all subsets of alternatives
+const MAX_ALTERNATIVES_UNATTRACTIVENESS : u32 = 7;
+
+// random consideration enumerates n! linear orders times 3^n attention vectors
+const MAX_ALTERNATIVES_RANDOM_CONSIDERATION : u32 = 6;
+
+impl Model {
+    /// All models, with every combination of preorder parameters.
+    pub fn all() -> Vec<Model> {
+        let bools = [None, Some(true), Some(false)];
+        let params : Vec<PreorderParams> = bools.iter().flat_map(
+            |&strict| bools.iter().map(move |&total| PreorderParams{strict, total})
+        ).collect();
+
+        let mut models = Vec::new();
+        models.extend(params.iter().map(|&p| Model::PreorderMaximization(p)));
+        models.extend(params.iter().map(|&p| Model::Unattractiveness(p)));
+        models.push(Model::UndominatedChoice{strict: true});
+        models.push(Model::UndominatedChoice{strict: false});
+        models.push(Model::PartiallyDominantChoice{fc: true});
+        models.push(Model::PartiallyDominantChoice{fc: false});
+        models.push(Model::StatusQuoUndominatedChoice);
+        models.extend(params.iter().map(|&p| Model::Overload(p)));
+        models.push(Model::TopTwo);
+        models.push(Model::SequentiallyRationalizableChoice);
+        models.push(Model::Swaps);
+        models.push(Model::LimitedAttention);
+        models.push(Model::RandomConsideration);
+        models
+    }
+
+    /// The largest number of alternatives that estimation accepts for this model.
+    pub fn max_alternatives(self) -> u32 {
+        fn preorders(p : PreorderParams) -> u32 {
+            if p == (PreorderParams{strict: Some(true), total: Some(true)}) {
+                MAX_ALTERNATIVES_LINEAR
+            } else {
+                fast_preorder::MAX_SIZE
+            }
+        }
+
+        let linear = PreorderParams{strict: Some(true), total: Some(true)};
+        match self {
+            // estimated by the exact search, see `estimation::evaluate_model`
+            Model::PreorderMaximization(p) if p == linear
+                => exact_estimation::MAX_ALTERNATIVES_STRICT_UM,
+
+            Model::PreorderMaximization(p) | Model::Overload(p)
+                => preorders(p),
+
+            Model::Unattractiveness(_)
+                => MAX_ALTERNATIVES_UNATTRACTIVENESS,
+
+            Model::UndominatedChoice{..}
+            | Model::PartiallyDominantChoice{..}
+            | Model::StatusQuoUndominatedChoice
+                => fast_preorder::MAX_SIZE,
+
+            // estimated by the exact search, see `estimation::evaluate_model`
+            Model::LimitedAttention
+                => exact_estimation::MAX_ALTERNATIVES_LIMITED_ATTENTION,
+
+            Model::TopTwo | Model::Swaps
+                => MAX_ALTERNATIVES_LINEAR,
+
+            Model::RandomConsideration
+                => MAX_ALTERNATIVES_RANDOM_CONSIDERATION,
+
+            Model::SequentiallyRationalizableChoice
+                => exact_estimation::MAX_ALTERNATIVES_SRC_PERFECT,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
 pub enum Instance {
     PreorderMaximization(Preorder),
@@ -129,8 +351,19 @@ pub enum Instance {
     TopTwo(Preorder),
     SequentiallyRationalizableChoice(Preorder, Preorder),
     Swaps(Preorder),
+    LimitedAttention {
+        p : Preorder,  // preference, linear
+        filter : Vec<(AltSet, Alt)>,  // explained observations, see `exact_estimation::limited_attention`
+    },
+    RandomConsideration {
+        p : Preorder,  // preference, linear
+        attention : Vec<u32>,  // attention probabilities, in multiples of 1/ATTENTION_DENOMINATOR
+    },
 }
 
+// attention probabilities of random consideration are 1/4, 2/4 or 3/4
+pub const ATTENTION_DENOMINATOR : u32 = 4;
+
 impl Encode for Instance {
     fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
         match self {
@@ -160,6 +393,12 @@ impl Encode for Instance {
 
             &Instance::Swaps(ref p)
                 => (8u8, p).encode(f),
+
+            &Instance::LimitedAttention{ref p, ref filter}
+                => (9u8, p, filter).encode(f),
+
+            &Instance::RandomConsideration{ref p, ref attention}
+                => (10u8, p, attention).encode(f),
         }
     }
 }
@@ -188,6 +427,14 @@ impl Decode for Instance {
                 Decode::decode(f)?,
             )),
             8u8 => Ok(Instance::Swaps(Decode::decode(f)?)),
+            9u8 => Ok(Instance::LimitedAttention{
+                p: Decode::decode(f)?,
+                filter: Decode::decode(f)?,
+            }),
+            10u8 => Ok(Instance::RandomConsideration{
+                p: Decode::decode(f)?,
+                attention: Decode::decode(f)?,
+            }),
             _ => Err(codec::Error::BadEnumTag),
         }
     }
@@ -201,7 +448,7 @@ pub fn preorder_maximization(p : &Preorder, menu : AltSetView) -> AltSet {
     result
 }
 
-fn undominated_choice(p : &Preorder, menu : AltSetView) -> AltSet {
+pub fn undominated_choice(p : &Preorder, menu : AltSetView) -> AltSet {
     // we can't use p.transpose().upset() because we don't have p.transpose()
     menu.iter().filter(
         // select elements i such that
@@ -277,6 +524,12 @@ impl Instance {
 
             &Instance::Swaps(_) =>
                 Model::Swaps,
+
+            &Instance::LimitedAttention{..} =>
+                Model::LimitedAttention,
+
+            &Instance::RandomConsideration{..} =>
+                Model::RandomConsideration,
         }
     }
 
@@ -368,57 +621,118 @@ impl Instance {
                 debug_assert!(p.is_strict());
                 debug_assert!(q.is_strict());
 
+                // the model requires a single answer;
+                // anything else is penalised in `row_penalty()`
                 let shortlist = undominated_choice(p, menu);
-                let answer = undominated_choice(q, shortlist.view());
+                undominated_choice(q, shortlist.view())
+            }
 
-                // requirement of the model
-                // fairly cheap to check so we do it
-                assert!(answer.size() == 1);
+            &Instance::LimitedAttention{ref p, ref filter} => {
+                debug_assert!(p.is_strict());
+                debug_assert!(p.is_total());
 
-                answer
-            }
-        }
-    }
+                // Between an explained menu and its consideration set (the choice and worse),
+                // the consideration set stays the same. Elsewhere, everything is considered.
+                let explained = filter.iter().find(|&&(ref observed, choice)|
+                    menu.is_subseteq_of(observed.view())
+                    && observed.view().iter().all(|a| menu.contains(a) || p.lt(choice, a))
+                );
 
-    pub fn penalty(&self, crs : &[ChoiceRow]) -> Penalty {
-        let upper_bound = crs.iter().map(|cr| {
-            // special case for SWAPS
-            if let &Instance::Swaps(ref p) = self {
-                if let Some(choice) = cr.choice.view().as_singleton() {
-                    // all strictly better options
-                    return p.upset(choice).iter().filter(
-                        |&c|
-                            cr.menu.view().contains(c)
-                            && c != choice
-                    ).count() as u32
-                } else {
-                    panic!("SWAPS model: choices must be exactly singletons");
+                match explained {
+                    Some(&(_, choice)) => AltSet::singleton(choice),
+                    None => preorder_maximization(p, menu),
                 }
             }
 
-            let standard_penalty =
-                if cr.choice == self.choice(cr.menu.view(), cr.default) { 0 } else { 1 };
+            &Instance::RandomConsideration{ref p, ref attention} => {
+                debug_assert!(p.is_strict());
+                debug_assert!(p.is_total());
+
+                // probabilities are kept as integers, scaled by powers of ATTENTION_DENOMINATOR:
+                // P(a) = attention(a) * prod_{b better than a} (1 - attention(b))
+                let denominator = ATTENTION_DENOMINATOR as u64;
+                let mut best = Vec::new();
+                let mut best_prob = 0u64;
+                let mut none_considered = 1u64;
+                for i in p.as_linear_order().into_iter().filter(|&i| menu.contains(i)) {
+                    let level = attention[i.index() as usize] as u64;
+                    let prob = none_considered * level;
+                    if prob > best_prob {
+                        best = vec![i];
+                        best_prob = prob;
+                    } else if prob == best_prob {
+                        best.push(i);
+                    }
+
+                    // move on to the next power of the denominator
+                    best_prob *= denominator;
+                    none_considered *= denominator - level;
+                }
 
-            if cr.menu.view().is_singleton() {
-                if let Instance::PartiallyDominantChoice{p:_,fc:_} = self {
-                    // PDC should not be penalised for deferring at singletons
-                    0
+                if none_considered * denominator > best_prob {
+                    // deferral is the most likely outcome
+                    AltSet::empty()
                 } else {
-                    standard_penalty
+                    // ties with deferral are resolved in favour of choosing
+                    best.into_iter().collect()
                 }
+            }
+        }
+    }
+
+    pub fn row_penalty(&self, cr : &ChoiceRow) -> u32 {
+        // special case for SWAPS
+        if let &Instance::Swaps(ref p) = self {
+            if let Some(choice) = cr.choice.view().as_singleton() {
+                // all strictly better options
+                return p.upset(choice).iter().filter(
+                    |&c|
+                        cr.menu.view().contains(c)
+                        && c != choice
+                ).count() as u32
+            } else {
+                panic!("SWAPS model: choices must be exactly singletons");
+            }
+        }
+
+        // SRC always chooses exactly one alternative
+        if let &Instance::SequentiallyRationalizableChoice(_,_) = self {
+            if !cr.choice.view().is_singleton() {
+                return 1;
+            }
+        }
+
+        let standard_penalty =
+            if cr.choice == self.choice(cr.menu.view(), cr.default) { 0 } else { 1 };
+
+        if cr.menu.view().is_singleton() {
+            if let Instance::PartiallyDominantChoice{p:_,fc:_} = self {
+                // PDC should not be penalised for deferring at singletons
+                0
             } else {
                 standard_penalty
             }
-        }).sum();
+        } else {
+            standard_penalty
+        }
+    }
 
-        let lower_bound = match self {
-            &Instance::SequentiallyRationalizableChoice(_,_)
-                => cmp::min(1, upper_bound),
-            _
-                => upper_bound,
-        };
+    pub fn penalty(&self, crs : &[ChoiceRow]) -> Penalty {
+        Penalty::exact(crs.iter().map(|cr| self.row_penalty(cr)).sum())
+    }
+
+    /// Same as `penalty()` but gives up (returning `None`)
+    /// as soon as the penalty exceeds `bound`.
+    pub fn bounded_penalty(&self, crs : &[ChoiceRow], bound : u32) -> Option<Penalty> {
+        let mut penalty = 0;
+        for cr in crs {
+            penalty += self.row_penalty(cr);
+            if penalty > bound {
+                return None;
+            }
+        }
 
-        Penalty{lower_bound, upper_bound}
+        Some(Penalty::exact(penalty))
     }
 }
 
@@ -428,7 +742,7 @@ pub enum InstanceError {
         model: Model,
         alt_count: u32,
     },
-    NeedPrecomputedPreorders,
+    Cancelled,
 }
 
 impl Encode for InstanceError {
@@ -437,14 +751,14 @@ impl Encode for InstanceError {
             &InstanceError::TooManyAlternatives{ref model, alt_count}
                 => (0u8, model, alt_count).encode(f),
 
-            &InstanceError::NeedPrecomputedPreorders
+            &InstanceError::Cancelled
                 => 1u8.encode(f),
         }
     }
 }
 
 impl PreorderError {
-    fn annotate(self, model : Model) -> InstanceError {
+    pub fn annotate(self, model : Model) -> InstanceError {
         match self {
             PreorderError::TooManyAlternatives(alt_count)
                 => InstanceError::TooManyAlternatives {
@@ -452,8 +766,8 @@ impl PreorderError {
                     alt_count,
                 },
 
-            PreorderError::NeedPrecomputedPreorders
-                => InstanceError::NeedPrecomputedPreorders,
+            PreorderError::Cancelled
+                => InstanceError::Cancelled,
         }
     }
 }
@@ -467,8 +781,8 @@ impl fmt::Display for InstanceError {
                 // in the error handler
                 write!(f, "Model \"{:?}\" does not support {} alternatives.", model, alt_count),
 
-            &InstanceError::NeedPrecomputedPreorders =>
-                PreorderError::NeedPrecomputedPreorders.fmt(f),
+            &InstanceError::Cancelled =>
+                PreorderError::Cancelled.fmt(f),
         }
     }
 }
@@ -478,10 +792,18 @@ fn traverse_preorders<F>(
     precomputed : &Precomputed,
     preorder_params : PreorderParams,
     alt_count : u32,
+    cancel : &CancelFlag,
     f : &mut F
 ) -> Result<(), PreorderError>
     where F : FnMut(Preorder)
 {
+    // checked once per preorder, which is cheap next to what `f` does with it
+    let check_cancel = || if cancel.is_cancelled() {
+        Err(PreorderError::Cancelled)
+    } else {
+        Ok(())
+    };
+
     #[inline]
     fn satisfies<F : Fn(&Preorder) -> bool>(p : &Preorder, property : F, requirement : Option<bool>) -> bool {
         match requirement {
@@ -493,44 +815,67 @@ fn traverse_preorders<F>(
     if let Some(true) = preorder_params.total {
         if let Some(true) = preorder_params.strict {
             // subset of linear orders
-            if alt_count > 10 {
+            if alt_count > MAX_ALTERNATIVES_LINEAR {
                 return Err(PreorderError::TooManyAlternatives(alt_count));
             }
 
             for p in linear_preorders::all(alt_count) {
+                check_cancel()?;
                 f(p);
             }
-        } else {
-            // subset of weak orders
-            if alt_count > 7 {
-                return Err(PreorderError::TooManyAlternatives(alt_count));
-            }
 
-            for p in &precomputed.get(alt_count)?.weak_orders {
-                if satisfies(&p, Preorder::is_strict, preorder_params.strict) {
-                    f(p.clone());
-                }
-            }
+            return Ok(());
         }
-    } else {
-        if let Some(true) = preorder_params.strict {
-            // subset of partial orders
-            if alt_count > 7 {
-                return Err(PreorderError::TooManyAlternatives(alt_count));
+    }
+
+    if !precomputed.is_available(alt_count) {
+        // enumerate on the fly, without materialising the whole vectors
+        if alt_count > fast_preorder::MAX_SIZE {
+            return Err(PreorderError::TooManyAlternatives(alt_count));
+        }
+
+        let stream = fast_preorder::Stream::new(
+            alt_count,
+            preorder_params.strict == Some(true),
+            preorder_params.total == Some(true),
+        );
+
+        for p in stream {
+            check_cancel()?;
+            let p = Preorder::from_fast_preorder(alt_count, p);
+            if satisfies(&p, Preorder::is_strict, preorder_params.strict)
+                && satisfies(&p, Preorder::is_total, preorder_params.total)
+            {
+                f(p);
             }
+        }
 
-            for p in &precomputed.get(alt_count)?.partial_orders {
-                if satisfies(&p, Preorder::is_total, preorder_params.total) {
-                    f(p.clone());
-                }
+        return Ok(());
+    }
+
+    if let Some(true) = preorder_params.total {
+        // subset of weak orders
+        for p in &precomputed.get(alt_count)?.weak_orders {
+            check_cancel()?;
+            if satisfies(&p, Preorder::is_strict, preorder_params.strict) {
+                f(p.clone());
             }
-        } else {
-            for p in &precomputed.get(alt_count)?.preorders {
-                if satisfies(&p, Preorder::is_strict, preorder_params.strict)
-                    && satisfies(&p, Preorder::is_total, preorder_params.total)
-                {
-                    f(p.clone());
-                }
+        }
+    } else if let Some(true) = preorder_params.strict {
+        // subset of partial orders
+        for p in &precomputed.get(alt_count)?.partial_orders {
+            check_cancel()?;
+            if satisfies(&p, Preorder::is_total, preorder_params.total) {
+                f(p.clone());
+            }
+        }
+    } else {
+        for p in &precomputed.get(alt_count)?.preorders {
+            check_cancel()?;
+            if satisfies(&p, Preorder::is_strict, preorder_params.strict)
+                && satisfies(&p, Preorder::is_total, preorder_params.total)
+            {
+                f(p.clone());
             }
         }
     }
@@ -542,18 +887,19 @@ fn traverse_unattractive<F>(
     precomputed : &Precomputed,
     preorder_params : PreorderParams,
     alt_count : u32,
+    cancel : &CancelFlag,
     f : &mut F
 ) -> Result<(), PreorderError>
     where F : FnMut(Preorder, AltSet)
 {
-    if alt_count > 7 {
+    if alt_count > MAX_ALTERNATIVES_UNATTRACTIVENESS {
         return Err(PreorderError::TooManyAlternatives(alt_count));
     }
 
     // we don't include 0b11111...111 because unattractive=Some(true)
     // it's sufficient to use u32 masks because alt_count is limited by other aspects of the implementation
     for mask_u32 in 0u32 .. (1 << alt_count)-1 {
-        traverse_preorders(precomputed, preorder_params, mask_u32.count_ones(),
+        traverse_preorders(precomputed, preorder_params, mask_u32.count_ones(), cancel,
             &mut |p| f(
                 p.stuff(alt_count, mask_u32),
                 AltSet::from_block(mask_u32),
@@ -569,6 +915,7 @@ pub fn traverse_all<F>(
     model : Model,
     alt_count : u32,
     choices : &[ChoiceRow],
+    cancel : &CancelFlag,
     f : &mut F,
 ) -> Result<(), InstanceError>
     where F : FnMut(Instance)
@@ -577,12 +924,12 @@ pub fn traverse_all<F>(
 
     match model {
         Model::PreorderMaximization(preorder_params)
-            => traverse_preorders(precomputed, preorder_params, alt_count,
+            => traverse_preorders(precomputed, preorder_params, alt_count, cancel,
                 &mut |p| f(Instance::PreorderMaximization(p))
             ).map_err(&ann)?,
 
         Model::Unattractiveness(preorder_params)
-            => traverse_unattractive(precomputed, preorder_params, alt_count,
+            => traverse_unattractive(precomputed, preorder_params, alt_count, cancel,
                 &mut |p, mask| f(Instance::Unattractiveness{p, mask})
             ).map_err(&ann)?,
 
@@ -591,6 +938,7 @@ pub fn traverse_all<F>(
                 precomputed,
                 PreorderParams{strict: Some(strict), total: Some(false)},
                 alt_count,
+                cancel,
                 &mut |p| f(Instance::UndominatedChoice(p))
             ).map_err(&ann)?,
 
@@ -599,6 +947,7 @@ pub fn traverse_all<F>(
                 precomputed,
                 PreorderParams{strict: Some(true), total: Some(false)},
                 alt_count,
+                cancel,
                 &mut |p| f(Instance::PartiallyDominantChoice{p, fc})
             ).map_err(&ann)?,
 
@@ -607,6 +956,7 @@ pub fn traverse_all<F>(
                 precomputed,
                 PreorderParams{strict: Some(true), total: Some(true)},
                 alt_count,
+                cancel,
                 &mut |p| f(Instance::Swaps(p))
             ).map_err(&ann)?,
 
@@ -615,11 +965,12 @@ pub fn traverse_all<F>(
                 precomputed,
                 PreorderParams{strict: Some(true), total: Some(false)},
                 alt_count,
+                cancel,
                 &mut |p| f(Instance::StatusQuoUndominatedChoice(p))
             ).map_err(&ann)?,
 
         Model::Overload(pp)
-            => traverse_preorders(precomputed, pp, alt_count, &mut |p| {
+            => traverse_preorders(precomputed, pp, alt_count, cancel, &mut |p| {
                 // we have to be overloaded in at least one case
                 // limit attains the maximum value of (alt_count-1)
                 // so for the full set of size alt_count, this model will defer
@@ -629,64 +980,158 @@ pub fn traverse_all<F>(
             }).map_err(&ann)?,
 
         Model::TopTwo
-            => traverse_preorders(precomputed, PreorderParams{strict: Some(true), total: Some(true)}, alt_count,
+            => traverse_preorders(precomputed, PreorderParams{strict: Some(true), total: Some(true)}, alt_count, cancel,
                 &mut |p| f(Instance::TopTwo(p))
             ).map_err(&ann)?,
 
-        Model::SequentiallyRationalizableChoice => {
+        Model::LimitedAttention => {
+            if alt_count > exact_estimation::MAX_ALTERNATIVES_LIMITED_ATTENTION {
+                return Err(ann(PreorderError::TooManyAlternatives(alt_count)));
+            }
+
+            // for a fixed preference, the best attention filter is found exactly
             traverse_preorders(precomputed,
-                PreorderParams{strict: Some(true), total: Some(false)},
-                alt_count, &mut |p|
+                PreorderParams{strict: Some(true), total: Some(true)},
+                alt_count, cancel, &mut |p|
             {
-                /* SRC is supported only experimentally. If there is a perfectly rationalising
-                 * instance, we will find it. Otherwise, we will probably give a sub-optimal
-                 * solution. Either way, we produce at least one instance, however bad, to avoid
-                 * the nasty corner case of choosing the best instance from an empty list later.
-                 *
-                 * How this works:
-                 *
-                 * SRC has two rationales, P and Q. P yields a shortlist from any given menu,
-                 * and Q makes the final choice from the shortlist.
-                 * We therefore know that any perfect instance will have a P such that
-                 * P(X) is a subseteq of C(X), and then finally Q(P(X)) = C(X).
-                 *
-                 * We therefore prune the candidates for P using the above criterion
-                 * to make the algorithm run faster.
-                 *
-                 * Finally, in case there is no perfect instance, we just invent one,
-                 * for the sake of producing /something/.
-                 */
-                let shortlist = {
-                    // we iterate explicitly to be able to return early
-                    let mut shortlist = Vec::with_capacity(choices.len());
-                    for cr in choices {
-                        let choice = undominated_choice(&p, cr.menu.view());
-                        if cr.choice.view().is_subseteq_of(choice.view()) {
-                            shortlist.push((&cr.choice, choice));
-                        } else {
-                            // preorder "p" is incompatible, bail out early
-                            return;
-                        }
-                    }
-                    shortlist
-                };
-
-                traverse_preorders(precomputed,
-                    PreorderParams{strict: Some(true), total: Some(false)},
-                    alt_count, &mut |q|
-                {
-                    for &(cr_choice, ref shortlist) in &shortlist {
-                        if undominated_choice(&q, shortlist.view()) != *cr_choice {
-                            // gives wrong answer in at least one case, bail out early
-                            return;
+                let (_penalty, filter) = exact_estimation::limited_attention_filter(&p, choices);
+                f(Instance::LimitedAttention{p, filter});
+            }).map_err(&ann)?
+        }
+
+        Model::RandomConsideration => {
+            if alt_count > MAX_ALTERNATIVES_RANDOM_CONSIDERATION {
+                return Err(ann(PreorderError::TooManyAlternatives(alt_count)));
+            }
+
+            traverse_preorders(precomputed,
+                PreorderParams{strict: Some(true), total: Some(true)},
+                alt_count, cancel, &mut |p|
+            {
+                // all vectors of attention levels 1 ..= ATTENTION_DENOMINATOR-1
+                let mut attention = vec![1; alt_count as usize];
+                loop {
+                    f(Instance::RandomConsideration{p: p.clone(), attention: attention.clone()});
+
+                    // increment the vector as a number
+                    match attention.iter().position(|&level| level < ATTENTION_DENOMINATOR-1) {
+                        Some(i) => {
+                            attention[i] += 1;
+                            for level in &mut attention[..i] {
+                                *level = 1;
+                            }
                         }
+                        None => break,
                     }
-
-                    // all answers are compatible, list this instance
-                    f(Instance::SequentiallyRationalizableChoice(p.clone(), q));
-                }).unwrap();
+                }
             }).map_err(&ann)?
         }
+
+        Model::SequentiallyRationalizableChoice => {
+            let best = exact_estimation::sequentially_rationalizable_choice(alt_count, choices, u32::MAX, cancel)
+                .map_err(&ann)?;
+
+            if let Some((_penalty, instances)) = best {
+                for (p, q) in instances {
+                    f(Instance::SequentiallyRationalizableChoice(p, q));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/* Like `traverse_all` but the caller maintains `bound`, the highest penalty
+ * that is still interesting, and we skip the instances that certainly exceed it.
+ *
+ * For models where the choice from a menu depends only on the restriction
+ * of the preorder to that menu, we generate the preorders alternative by alternative
+ * (see `fast_preorder::Stream`) and abandon the whole subtree as soon as
+ * the rows whose menus are already fully placed incur more than `bound`.
+ *
+ * The remaining models are traversed as usual and the caller
+ * should use `Instance::bounded_penalty` to abandon the instances early.
+ */
+pub fn traverse_all_pruned<F>(
+    precomputed : &Precomputed,
+    model : Model,
+    alt_count : u32,
+    choices : &[ChoiceRow],
+    bound : &Cell<u32>,
+    cancel : &CancelFlag,
+    f : &mut F,
+) -> Result<(), InstanceError>
+    where F : FnMut(Instance)
+{
+    let (preorder_params, instance) : (PreorderParams, fn(Preorder) -> Instance) = match model {
+        Model::PreorderMaximization(pp)
+            => (pp, Instance::PreorderMaximization),
+
+        Model::UndominatedChoice{strict}
+            => (PreorderParams{strict: Some(strict), total: Some(false)}, Instance::UndominatedChoice),
+
+        Model::PartiallyDominantChoice{fc: false}
+            => (PreorderParams{strict: Some(true), total: Some(false)},
+                |p| Instance::PartiallyDominantChoice{p, fc: false}),
+
+        Model::PartiallyDominantChoice{fc: true}
+            => (PreorderParams{strict: Some(true), total: Some(false)},
+                |p| Instance::PartiallyDominantChoice{p, fc: true}),
+
+        _ => return traverse_all(precomputed, model, alt_count, choices, cancel, f),
+    };
+
+    if alt_count > fast_preorder::MAX_SIZE
+        || preorder_params == (PreorderParams{strict: Some(true), total: Some(true)})
+    {
+        // linear orders are not streamed
+        return traverse_all(precomputed, model, alt_count, choices, cancel, f);
+    }
+
+    // rows_by_size[k] = rows whose menus fit in the first k alternatives
+    let mut rows_by_size : Vec<Vec<&ChoiceRow>> = (0..alt_count+1).map(|_| Vec::new()).collect();
+    for cr in choices {
+        let size = cr.menu.view().iter().map(|Alt(i)| i+1).max().unwrap_or(0);
+        for rows in &mut rows_by_size[size as usize ..] {
+            rows.push(cr);
+        }
+    }
+
+    let mut stream = fast_preorder::Stream::new(
+        alt_count,
+        preorder_params.strict == Some(true),
+        preorder_params.total == Some(true),
+    );
+
+    // once cancelled, everything is pruned
+    let mut prune = |size : u32, p : FastPreorder| {
+        if cancel.is_cancelled() {
+            return true;
+        }
+
+        let p = instance(Preorder::from_fast_preorder(size, p));
+        let mut penalty = 0;
+        for cr in &rows_by_size[size as usize] {
+            penalty += p.row_penalty(cr);
+            if penalty > bound.get() {
+                return true;
+            }
+        }
+        false
+    };
+
+    while let Some(p) = stream.next_pruned(&mut prune) {
+        let p = Preorder::from_fast_preorder(alt_count, p);
+        if preorder_params.strict.is_none_or(|strict| p.is_strict() == strict)
+            && preorder_params.total.is_none_or(|total| p.is_total() == total)
+        {
+            f(instance(p));
+        }
+    }
+
+    if cancel.is_cancelled() {
+        return Err(InstanceError::Cancelled);
     }
 
     Ok(())
@@ -695,6 +1140,7 @@ pub fn traverse_all<F>(
 #[cfg(test)]
 mod test {
     use precomputed::Precomputed;
+    use rpc_common::CancelFlag;
     use super::{AltSet,PreorderParams,Instance};
     use std::collections::HashSet;
     use preorder::Preorder;
@@ -771,6 +1217,7 @@ mod test {
                 super::Model::Unattractiveness(PreorderParams{strict:None, total:None}),
                 alt_count,
                 &[],
+                &CancelFlag::new(),
                 &mut |inst|
             {
                 // make sure that the preorders are well-formed
@@ -791,16 +1238,204 @@ mod test {
         }
 
         let mut precomp = Precomputed::new(None);
-        precomp.precompute(6).unwrap();
+        precomp.precompute(6);
         for size in 0..6 {
             case(&precomp, size);
         }
     }
 
+    #[test]
+    fn streamed_preorders() {
+        fn collect(precomp : &Precomputed, params : PreorderParams, alt_count : u32) -> HashSet<super::Instance> {
+            let mut result = HashSet::new();
+            super::traverse_all(
+                precomp,
+                super::Model::PreorderMaximization(params),
+                alt_count,
+                &[],
+                &CancelFlag::new(),
+                &mut |inst| assert!(result.insert(inst)),
+            ).unwrap();
+            result
+        }
+
+        let mut precomp = Precomputed::new(None);
+        precomp.precompute(5);
+        let empty = Precomputed::new(None);
+
+        for &strict in &[None, Some(true), Some(false)] {
+            for &total in &[None, Some(true), Some(false)] {
+                let params = PreorderParams{strict, total};
+                assert_eq!(collect(&precomp, params, 5), collect(&empty, params, 5));
+            }
+        }
+
+        // no precomputed file needed
+        let weak_orders = PreorderParams{strict: Some(false), total: Some(true)};
+        assert_eq!(collect(&empty, weak_orders, 7).len(), 47293 - 5040);
+    }
+
+    #[test]
+    fn pruned_traversal() {
+        use std::cell::Cell;
+        use super::Model;
+        use rpc_common::ChoiceRow;
+        use alt_set::AltSet;
+
+        let choices = choices![
+            [0,1,2,3,4] -> [2],
+            [0,1,2] -> [1],
+            [1,2,3] -> [3],
+            [0,3] -> [3],
+            [0,1] -> [0],
+            [2,4] -> [4],
+            [3,4] -> [],
+            [0,2,4] -> [0,2],
+            [1,3] -> [1],
+            [4] -> []
+        ];
+
+        let precomp = Precomputed::new(None);
+        let models = [
+            Model::PreorderMaximization(PreorderParams{strict: None, total: None}),
+            Model::PreorderMaximization(PreorderParams{strict: Some(false), total: Some(true)}),
+            Model::UndominatedChoice{strict: true},
+            Model::UndominatedChoice{strict: false},
+            Model::PartiallyDominantChoice{fc: true},
+            Model::PartiallyDominantChoice{fc: false},
+        ];
+
+        for &model in &models {
+            let mut all = Vec::new();
+            super::traverse_all(&precomp, model, 5, &choices, &CancelFlag::new(), &mut |inst| {
+                all.push((inst.penalty(&choices).upper_bound, inst))
+            }).unwrap();
+            let best_penalty = all.iter().map(|&(penalty, _)| penalty).min().unwrap();
+            let expected : HashSet<Instance> = all.into_iter().filter(
+                |&(penalty, _)| penalty == best_penalty
+            ).map(|(_, inst)| inst).collect();
+
+            let bound = Cell::new(u32::MAX);
+            let mut found = HashSet::new();
+            super::traverse_all_pruned(&precomp, model, 5, &choices, &bound, &CancelFlag::new(), &mut |inst| {
+                let penalty = inst.bounded_penalty(&choices, bound.get()).unwrap().upper_bound;
+                if penalty < bound.get() {
+                    bound.set(penalty);
+                    found.clear();
+                }
+                found.insert(inst);
+            }).unwrap();
+
+            assert_eq!(bound.get(), best_penalty);
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn random_consideration() {
+        use rpc_common::ChoiceRow;
+        use alt_set::AltSet;
+
+        // 0 > 1 > 2, attention 1/4, 3/4, 1/4
+        let inst = Instance::RandomConsideration{
+            p: Preorder::from_values(&[2, 1, 0]),
+            attention: vec![1, 3, 1],
+        };
+
+        // P(0) = 1/4, P(1) = 9/16, P(2) = 3/64, P(none) = 9/64
+        assert_eq!(inst.choice(alts![0,1,2].view(), None), alts![1]);
+        // P(0) = 1/4, P(2) = 3/16, P(none) = 9/16
+        assert_eq!(inst.choice(alts![0,2].view(), None), alts![]);
+        assert_eq!(inst.choice(alts![1].view(), None), alts![1]);
+        assert_eq!(inst.choice(alts![2].view(), None), alts![]);
+
+        let mut count = 0;
+        let choices : Vec<ChoiceRow> = Vec::new();
+        super::traverse_all(&Precomputed::new(None), super::Model::RandomConsideration, 3, &choices, &CancelFlag::new(), &mut |_| count += 1).unwrap();
+        assert_eq!(count, 6 * 27);
+
+        // 7! * 3^7 instances would be too many
+        match super::traverse_all(&Precomputed::new(None), super::Model::RandomConsideration, 7, &choices, &CancelFlag::new(), &mut |_| ()) {
+            Err(super::InstanceError::TooManyAlternatives{alt_count: 7, ..}) => (),
+            _ => panic!("7 alternatives should be rejected"),
+        }
+    }
+
+    #[test]
+    fn limited_attention() {
+        use rpc_common::ChoiceRow;
+        use alt_set::AltSet;
+        use super::Model;
+
+        // violates WARP but the decision maker might overlook 0 in the presence of 2
+        let choices = choices![
+            [0,1,2] -> [1],
+            [0,1] -> [0],
+            [1,2] -> [1],
+            [0,2] -> [2]
+        ];
+
+        let best_penalty = |model| {
+            let mut best = u32::MAX;
+            super::traverse_all(&Precomputed::new(None), model, 3, &choices, &CancelFlag::new(), &mut |inst| {
+                best = std::cmp::min(best, inst.penalty(&choices).upper_bound);
+            }).unwrap();
+            best
+        };
+
+        assert_eq!(best_penalty(Model::PreorderMaximization(PreorderParams{strict: Some(true), total: Some(true)})), 1);
+        assert_eq!(best_penalty(Model::LimitedAttention), 0);
+
+        // the perfect instance: 0 is best but overlooked in the presence of 2
+        // unless 1 is missing, too
+        let inst = Instance::LimitedAttention{
+            p: Preorder::from_values(&[2, 1, 0]),
+            filter: vec![(alts![0,1,2], Alt(1)), (alts![0,2], Alt(2))],
+        };
+        assert_eq!(inst.penalty(&choices), super::Penalty::exact(0));
+        assert_eq!(inst.choice(alts![0,1].view(), None), alts![0]);
+
+        let model = Model::LimitedAttention;
+        let too_many = model.max_alternatives() + 1;
+        match super::traverse_all(&Precomputed::new(None), model, too_many, &[], &CancelFlag::new(), &mut |_| ()) {
+            Err(super::InstanceError::TooManyAlternatives{alt_count, ..}) => assert_eq!(alt_count, too_many),
+            _ => panic!("{} alternatives should be rejected", too_many),
+        }
+    }
+
+    #[test]
+    fn cancelled() {
+        use rpc_common::ChoiceRow;
+        use super::Model;
+        use std::cell::Cell;
+
+        let cancel = CancelFlag::new();
+        cancel.cancel();
+
+        let choices = choices![[0,1] -> [0]];
+        for &model in &[
+            Model::PreorderMaximization(PreorderParams{strict: None, total: None}),
+            Model::UndominatedChoice{strict: true},
+            Model::Overload(PreorderParams{strict: Some(true), total: Some(true)}),
+            Model::LimitedAttention,
+        ] {
+            match super::traverse_all(&Precomputed::new(None), model, 3, &choices, &cancel, &mut |_| ()) {
+                Err(super::InstanceError::Cancelled) => (),
+                _ => panic!("traversal of {} not cancelled", model),
+            }
+
+            let bound = Cell::new(u32::MAX);
+            match super::traverse_all_pruned(&Precomputed::new(None), model, 3, &choices, &bound, &cancel, &mut |_| ()) {
+                Err(super::InstanceError::Cancelled) => (),
+                _ => panic!("pruned traversal of {} not cancelled", model),
+            }
+        }
+    }
+
     #[test]
     fn balance() {
         let mut precomp = Precomputed::new(None);
-        precomp.precompute(4).unwrap();
+        precomp.precompute(4);
         
         {
             let mut m = 0;
@@ -811,6 +1446,7 @@ mod test {
                 super::Model::PreorderMaximization(PreorderParams{strict:None,total:None}),
                 4,
                 &[],
+                &CancelFlag::new(),
                 &mut |inst|
             {
                 if inst.choice(alts![2].view(), None).view().contains(Alt(3)) {
@@ -825,4 +1461,33 @@ mod test {
             assert_eq!(m, n);
         }
     }
+
+    #[test]
+    fn model_names() {
+        use super::Model;
+
+        let models = [
+            Model::PreorderMaximization(PreorderParams{strict: None, total: None}),
+            Model::PreorderMaximization(PreorderParams{strict: Some(true), total: Some(false)}),
+            Model::Unattractiveness(PreorderParams{strict: Some(false), total: Some(true)}),
+            Model::UndominatedChoice{strict: false},
+            Model::PartiallyDominantChoice{fc: true},
+            Model::Overload(PreorderParams{strict: None, total: Some(true)}),
+            Model::TopTwo,
+            Model::LimitedAttention,
+            Model::RandomConsideration,
+        ];
+
+        for &model in &models {
+            assert_eq!(model.to_string().parse::<Model>(), Ok(model));
+        }
+
+        assert_eq!(
+            "preorder-maximization:total, strict".parse::<Model>(),
+            Ok(Model::PreorderMaximization(PreorderParams{strict: Some(true), total: Some(true)}))
+        );
+        assert!("undominated-choice".parse::<Model>().is_err());
+        assert!("swaps:strict".parse::<Model>().is_err());
+        assert!("utility-maximization".parse::<Model>().is_err());
+    }
 }
@@ -13,7 +13,7 @@ type Result<T> = result::Result<T, Error>;
 #[derive(Debug)]
 pub enum Error {
     TooManyAlternatives(u32),
-    NeedPrecomputedPreorders,
+    Cancelled,
 }
 
 impl fmt::Display for Error {
@@ -22,8 +22,8 @@ impl fmt::Display for Error {
             Error::TooManyAlternatives(alt_count) =>
                 write!(f, "too many alternatives: {}", alt_count),
 
-            Error::NeedPrecomputedPreorders =>
-                write!(f, "file with precomputed preorders is required"),
+            Error::Cancelled =>
+                write!(f, "cancelled"),
         }
     }
 }
@@ -32,7 +32,7 @@ impl Encode for Error {
     fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
         match *self {
             Error::TooManyAlternatives(alt_count) => (0u8, alt_count).encode(f),
-            Error::NeedPrecomputedPreorders => 1u8.encode(f),
+            Error::Cancelled => 1u8.encode(f),
         }
     }
 }
@@ -58,7 +58,9 @@ impl Precomputed {
         }
     }
 
-    pub fn precompute(&mut self, max_size : u32) -> Result<()> {
+    // Materialises the preorders that are cheap to build (or to load) in memory.
+    // Larger sizes are left to `fast_preorder::Stream`, see `is_available`.
+    pub fn precompute(&mut self, max_size : u32) {
         for size in self.preorders.len() as u32 .. max_size+1 {
             let preorders = if size < 7 {
                 fast_preorder::all(size).into_iter().map(
@@ -73,10 +75,12 @@ impl Precomputed {
                     }
                     result
                 } else {
-                    return Err(Error::NeedPrecomputedPreorders)
+                    // will be streamed
+                    break;
                 }
             } else {
-                return Err(Error::TooManyAlternatives(max_size))
+                // will be streamed
+                break;
             };
 
             let partial_orders = preorders.iter().cloned().filter(
@@ -91,10 +95,12 @@ impl Precomputed {
                 preorders, partial_orders, weak_orders,
             });
         }
-
-        Ok(())
     }
     
+    pub fn is_available(&self, size : u32) -> bool {
+        size < self.preorders.len() as u32
+    }
+
     pub fn get(&self, size : u32) -> Result<&Preorders> {
         if size >= self.preorders.len() as u32 {
             return Err(Error::TooManyAlternatives(size))
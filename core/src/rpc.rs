@@ -1,32 +1,91 @@
 use std;
-use std::io::{Read,Write,Stdout,Stdin,BufReader,BufWriter};
+use std::io::{Read,Write,Stdout,BufRead,BufReader,BufWriter};
 use std::result::Result;
+use std::str::FromStr;
 use std::fmt::Display;
+use std::sync::mpsc;
+use std::thread;
+use base64::prelude::BASE64_STANDARD;
+use base64::engine::Engine;
 use codec::{self,Encode,Decode};
+use json::{self,Json,ToJson,FromJson};
+use rpc_common::CancelFlag;
 
 use estimation;
+use stochastic;
+use significance;
 use consistency;
+use axioms;
 use simulation;
 use experiment_stats;
 use budgetary;
 use integrity;
 use instviz;
+use handshake;
+
+/// Bumped whenever the encoding of a request or a response changes.
+pub const PROTOCOL_VERSION : u32 = 7;
+
+/// The tags of all requests understood by the core.
+pub const TAGS : &[&str] = &[
+    "hello",
+    "instviz",
+    "budgetary-consistency",
+    "budgetary-utility",
+    "budgetary-support",
+    "budgetary-csv",
+    "summary",
+    "set-rng-seed",
+    "simulation",
+    "consistency",
+    "tuple-intrans-menus",
+    "tuple-intrans-alts",
+    "axioms",
+    "estimation",
+    "estimation-stream",
+    "stochastic-estimation",
+    "stochastic-axioms",
+    "significance",
+    "integrity-check",
+    "echo",
+    "crash",
+    "fail",
+    "cancel",
+    "quit",
+];
+
+fn unknown_request(tag : &str) -> String {
+    format!(
+        "unknown request \"{}\": this core speaks protocol version {} and supports: {}",
+        tag, PROTOCOL_VERSION, TAGS.join(", "),
+    )
+}
 
 #[derive(Debug)]
 pub enum ActionRequest {
+    Hello(handshake::Request),
     InstViz(instviz::Request),
     IntegrityCheck(integrity::Request),
     BudgetaryConsistency(budgetary::consistency::Request),
+    BudgetaryUtility(budgetary::afriat::Request),
+    BudgetarySupport(budgetary::support::Request),
+    BudgetaryCsv(budgetary::reader::Request),
     Summary(experiment_stats::Request),
     SetRngSeed(Vec<u8>),
     Simulation(simulation::Request),
     Consistency(consistency::Request),
     TupleIntransMenus(consistency::Request),
     TupleIntransAlts(consistency::Request),
+    Axioms(axioms::Request),
     Estimation(estimation::Request),
+    EstimationStream(estimation::Request),
+    StochasticEstimation(stochastic::Request),
+    StochasticAxioms(stochastic::axioms::Request),
+    Significance(significance::Request),
     Echo(String),
     Crash(String),
     Fail(String),
+    Cancel,
     Quit,
 }
 
@@ -36,21 +95,118 @@ impl Decode for ActionRequest {
 
         let tag : String = Decode::decode(f)?;
         match tag.as_str() {
+            "hello" => Ok(Hello(Decode::decode(f)?)),
             "instviz" => Ok(InstViz(Decode::decode(f)?)),
             "budgetary-consistency" => Ok(BudgetaryConsistency(Decode::decode(f)?)),
+            "budgetary-utility" => Ok(BudgetaryUtility(Decode::decode(f)?)),
+            "budgetary-support" => Ok(BudgetarySupport(Decode::decode(f)?)),
+            "budgetary-csv" => Ok(BudgetaryCsv(Decode::decode(f)?)),
             "summary" => Ok(Summary(Decode::decode(f)?)),
             "set-rng-seed" => Ok(SetRngSeed(Decode::decode(f)?)),
             "simulation" => Ok(Simulation(Decode::decode(f)?)),
             "consistency" => Ok(Consistency(Decode::decode(f)?)),
             "tuple-intrans-menus" => Ok(TupleIntransMenus(Decode::decode(f)?)),
             "tuple-intrans-alts" => Ok(TupleIntransAlts(Decode::decode(f)?)),
+            "axioms" => Ok(Axioms(Decode::decode(f)?)),
             "estimation" => Ok(Estimation(Decode::decode(f)?)),
+            "estimation-stream" => Ok(EstimationStream(Decode::decode(f)?)),
+            "stochastic-estimation" => Ok(StochasticEstimation(Decode::decode(f)?)),
+            "stochastic-axioms" => Ok(StochasticAxioms(Decode::decode(f)?)),
+            "significance" => Ok(Significance(Decode::decode(f)?)),
             "integrity-check" => Ok(IntegrityCheck(Decode::decode(f)?)),
             "echo" => Ok(Echo(Decode::decode(f)?)),
             "crash" => Ok(Crash(Decode::decode(f)?)),
             "fail" => Ok(Fail(Decode::decode(f)?)),
+            "cancel" => Ok(Cancel),
+            "quit" => Ok(Quit),
+            _ => Err(codec::Error::UnknownRequest(tag)),
+        }
+    }
+}
+
+/* The JSON transport (`prest-core --transport json`) carries the same requests
+ * and messages as the binary codec, one JSON document per line.
+ *
+ * Requests are objects {"tag": <tag>, "request": <payload>} with the tags
+ * in `TAGS`. Payloads mirror the request structs field by field
+ * (see the `FromJson` impls next to the `Decode` impls):
+ * alternatives are indices into the subject's alternatives,
+ * sets of alternatives are arrays of indices, matrices are arrays of rows,
+ * models are names like "preorder-maximization:strict,total",
+ * and model instances are their base64 codes. For example:
+ *
+ *   {"tag": "consistency", "request": {"subject": {"name": "s1", "alternatives": ["a", "b"],
+ *       "choices": [{"menu": [0, 1], "default": null, "choice": [0]}]}}}
+ *   {"tag": "hello", "request": {"protocol_version": 7}}
+ *   {"tag": "echo", "request": "hello"}
+ *   {"tag": "quit"}
+ *
+ * The core answers with one object per line:
+ *
+ *   {"type": "answer", "answer": <response>}
+ *   {"type": "error", "message": <string>}
+ *   {"type": "progress", "position": <number>}
+ *   {"type": "log", "level": "debug" | "info" | "warning" | "error", "message": <string>}
+ *   {"type": "partial", "data": <base64 of the binary encoding>}
+ *
+ * Malformed requests are answered with an error and the core keeps going.
+ * Partial results are only produced by "estimation-stream", which is meant
+ * for the GUI; other clients should use "estimation".
+ */
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    Binary,
+    Json,
+}
+
+impl FromStr for Transport {
+    type Err = String;
+    fn from_str(s : &str) -> Result<Transport, String> {
+        match s {
+            "binary" => Ok(Transport::Binary),
+            "json" => Ok(Transport::Json),
+            _ => Err(format!("unknown transport: {}", s)),
+        }
+    }
+}
+
+impl FromJson for ActionRequest {
+    fn from_json(js : &Json) -> json::Result<ActionRequest> {
+        use self::ActionRequest::*;
+
+        let tag : String = json::field(js, "tag")?;
+        let req = |js : &Json| match js.get("request") {
+            Some(req) => Ok(req.clone()),
+            None => Err(json::Error::MissingField(String::from("request"))),
+        };
+
+        match tag.as_str() {
+            "hello" => Ok(Hello(FromJson::from_json(&req(js)?)?)),
+            "instviz" => Ok(InstViz(FromJson::from_json(&req(js)?)?)),
+            "budgetary-consistency" => Ok(BudgetaryConsistency(FromJson::from_json(&req(js)?)?)),
+            "budgetary-utility" => Ok(BudgetaryUtility(FromJson::from_json(&req(js)?)?)),
+            "budgetary-support" => Ok(BudgetarySupport(FromJson::from_json(&req(js)?)?)),
+            "budgetary-csv" => Ok(BudgetaryCsv(FromJson::from_json(&req(js)?)?)),
+            "summary" => Ok(Summary(FromJson::from_json(&req(js)?)?)),
+            "set-rng-seed" => Ok(SetRngSeed(FromJson::from_json(&req(js)?)?)),
+            "simulation" => Ok(Simulation(FromJson::from_json(&req(js)?)?)),
+            "consistency" => Ok(Consistency(FromJson::from_json(&req(js)?)?)),
+            "tuple-intrans-menus" => Ok(TupleIntransMenus(FromJson::from_json(&req(js)?)?)),
+            "tuple-intrans-alts" => Ok(TupleIntransAlts(FromJson::from_json(&req(js)?)?)),
+            "axioms" => Ok(Axioms(FromJson::from_json(&req(js)?)?)),
+            "estimation" => Ok(Estimation(FromJson::from_json(&req(js)?)?)),
+            "estimation-stream" => Ok(EstimationStream(FromJson::from_json(&req(js)?)?)),
+            "stochastic-estimation" => Ok(StochasticEstimation(FromJson::from_json(&req(js)?)?)),
+            "stochastic-axioms" => Ok(StochasticAxioms(FromJson::from_json(&req(js)?)?)),
+            "significance" => Ok(Significance(FromJson::from_json(&req(js)?)?)),
+            "integrity-check" => Ok(IntegrityCheck(FromJson::from_json(&req(js)?)?)),
+            "echo" => Ok(Echo(FromJson::from_json(&req(js)?)?)),
+            "crash" => Ok(Crash(FromJson::from_json(&req(js)?)?)),
+            "fail" => Ok(Fail(FromJson::from_json(&req(js)?)?)),
+            "cancel" => Ok(Cancel),
             "quit" => Ok(Quit),
-            _ => Err(codec::Error::BadEnumTag),
+            _ => Err(json::Error::BadTag(tag)),
         }
     }
 }
@@ -96,6 +252,18 @@ impl Encode for LogLevel {
     }
 }
 
+impl ToJson for LogLevel {
+    fn to_json(&self) -> Json {
+        use self::LogLevel::*;
+        String::from(match *self {
+            Debug   => "debug",
+            Info    => "info",
+            Warning => "warning",
+            Error   => "error",
+        }).to_json()
+    }
+}
+
 pub struct LogMessage {
     pub level : LogLevel,
     pub message : String,
@@ -112,6 +280,7 @@ pub enum Message<Ans> {
     Answer(Ans),
     Error(Error),
     Log(LogMessage),
+    Partial(Vec<u8>),  // encoded partial result, to be decoded by the caller
 }
 
 impl<Ans : Encode> Encode for Message<Ans> {
@@ -122,42 +291,156 @@ impl<Ans : Encode> Encode for Message<Ans> {
             &Answer(ref answer) => (1u8, answer).encode(f),
             &Error(ref error)   => (2u8, error).encode(f),
             &Log(ref log)       => (3u8, log).encode(f),
+            &Partial(ref data)  => (4u8, data).encode(f),
+        }
+    }
+}
+
+impl<Ans : ToJson> ToJson for Message<Ans> {
+    fn to_json(&self) -> Json {
+        use self::Message::*;
+        let tagged = |tag : &str, mut fields : Vec<(&str, Json)>| {
+            fields.insert(0, ("type", String::from(tag).to_json()));
+            json::object(fields)
+        };
+
+        match *self {
+            Progress(position) => tagged("progress", vec![("position", position.to_json())]),
+            Answer(ref answer) => tagged("answer", vec![("answer", answer.to_json())]),
+            Error(ref error)   => tagged("error", vec![("message", error.error_message.to_json())]),
+            Log(ref log)       => tagged("log", vec![
+                ("level", log.level.to_json()),
+                ("message", log.message.to_json()),
+            ]),
+            Partial(ref data)  => tagged("partial", vec![("data", BASE64_STANDARD.encode(data).to_json())]),
         }
     }
 }
 
 pub struct IO {
-    stdin : BufReader<Stdin>,
+    requests : mpsc::Receiver<codec::Result<ActionRequest>>,
     stdout : BufWriter<Stdout>,
+    transport : Transport,
+    pub cancel : CancelFlag,
+}
+
+// one request per line; malformed lines become failures reported to the client
+fn read_json_request<R : BufRead>(f : &mut R) -> codec::Result<ActionRequest> {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if f.read_line(&mut line)? == 0 {
+            return Ok(ActionRequest::Quit);  // EOF
+        }
+
+        if !line.trim().is_empty() {
+            break;
+        }
+    }
+
+    let js = match json::parse(&line) {
+        Ok(js) => js,
+        Err(e) => return Ok(ActionRequest::Fail(format!("bad request: {}", e))),
+    };
+
+    match json::field::<String>(&js, "tag") {
+        Ok(ref tag) if !TAGS.contains(&tag.as_str()) => Ok(ActionRequest::Fail(unknown_request(tag))),
+        _ => Ok(ActionRequest::from_json(&js).unwrap_or_else(
+            |e| ActionRequest::Fail(format!("bad request: {}", e))
+        )),
+    }
 }
 
 impl IO {
-    pub fn from_stdio() -> Self {
+    pub fn from_stdio(transport : Transport) -> Self {
+        let cancel = CancelFlag::new();
+        let (tx, rx) = mpsc::channel();
+
+        // stdin is read on a separate thread so that we can receive
+        // cancellations while the main thread is busy with a request
+        let reader_cancel = cancel.clone();
+        thread::spawn(move || {
+            let mut stdin = BufReader::new(std::io::stdin());
+            loop {
+                let request = match transport {
+                    Transport::Binary => Decode::decode(&mut stdin),
+                    Transport::Json => read_json_request(&mut stdin),
+                };
+
+                match request {
+                    Ok(ActionRequest::Cancel) => {
+                        reader_cancel.cancel();
+                    }
+
+                    Ok(ActionRequest::Quit) => {
+                        let _ = tx.send(Ok(ActionRequest::Quit));
+                        break;
+                    }
+
+                    Ok(request) => {
+                        // a new request starts uncancelled
+                        reader_cancel.reset();
+                        if tx.send(Ok(request)).is_err() {
+                            break;
+                        }
+                    }
+
+                    Err(codec::Error::UnknownRequest(tag)) => {
+                        // the payload cannot be skipped without knowing its type
+                        // so we report the problem and stop
+                        let _ = tx.send(Ok(ActionRequest::Fail(unknown_request(&tag))));
+                        let _ = tx.send(Ok(ActionRequest::Quit));
+                        break;
+                    }
+
+                    Err(e) => {
+                        let _ = tx.send(Err(e));
+                        break;
+                    }
+                }
+            }
+        });
+
         IO {
-            stdin: BufReader::new(std::io::stdin()),
+            requests: rx,
             stdout: BufWriter::new(std::io::stdout()),
+            transport,
+            cancel,
         }
     }
 
-    pub fn read<T : Decode>(&mut self) -> codec::Result<T> {
-        Decode::decode(&mut self.stdin)
+    fn write_message<Ans : Encode + ToJson>(&mut self, message : Message<Ans>) -> codec::Result<()> {
+        match self.transport {
+            Transport::Binary => message.encode(&mut self.stdout)?,
+            Transport::Json => writeln!(self.stdout, "{}", message.to_json())?,
+        }
+        self.stdout.flush()?;
+        Ok(())
+    }
+
+    pub fn read_request(&mut self) -> codec::Result<ActionRequest> {
+        match self.requests.recv() {
+            Ok(request) => request,
+            Err(_) => Err(codec::Error::IO(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof, "request reader has terminated"
+            ))),
+        }
     }
 
-    pub fn write_result<T : Encode, E : Encode+Display>(&mut self, r : Result<T, E>)
+    pub fn write_result<T : Encode + ToJson, E : Encode+Display>(&mut self, r : Result<T, E>)
         -> codec::Result<()>
     {
         match r {
-            Ok(x) => Message::Answer(x).encode(&mut self.stdout)?,
-            Err(e) => Message::Error::<()>(Error::from(e)).encode(&mut self.stdout)?,
+            Ok(x) => self.write_message(Message::Answer(x)),
+            Err(e) => self.write_message(Message::Error::<()>(Error::from(e))),
         }
-        self.stdout.flush()?;
-        Ok(())
     }
 }
 
 pub trait Log {
     fn log(&mut self, level : LogLevel, message : String);
     fn progress(&mut self, position : u32);
+    fn partial(&mut self, data : Vec<u8>);
 
     fn debug(&mut self, msg : String) {
         self.log(LogLevel::Debug, msg)
@@ -188,13 +471,15 @@ impl<'a> Logger<'a> {
 
 impl<'a> Log for Logger<'a> {
     fn log(&mut self, level : LogLevel, message : String) {
-        Message::Log::<()>(LogMessage{ level, message }).encode(&mut self.io.stdout).unwrap();
-        self.io.stdout.flush().unwrap();
+        self.io.write_message(Message::Log::<()>(LogMessage{ level, message })).unwrap();
     }
 
     fn progress(&mut self, position : u32) {
-        Message::Progress::<()>(position).encode(&mut self.io.stdout).unwrap();
-        self.io.stdout.flush().unwrap();
+        self.io.write_message(Message::Progress::<()>(position)).unwrap();
+    }
+
+    fn partial(&mut self, data : Vec<u8>) {
+        self.io.write_message(Message::Partial::<()>(data)).unwrap();
     }
 }
 
@@ -203,4 +488,70 @@ pub struct DummyLogger;
 impl Log for DummyLogger {
     fn log(&mut self, _level : LogLevel, _message : String) {}
     fn progress(&mut self, _position : u32) {}
+    fn partial(&mut self, _data : Vec<u8>) {}
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn json_tags() {
+        for &tag in TAGS {
+            let js = json::object(vec![("tag", String::from(tag).to_json())]);
+            match ActionRequest::from_json(&js) {
+                Ok(_) => assert!(tag == "cancel" || tag == "quit"),
+                Err(e) => assert_eq!(e, json::Error::MissingField(String::from("request")), "{}", tag),
+            }
+        }
+
+        let js = json::parse(r#"{"tag": "hello", "request": {"protocol_version": 3}}"#).unwrap();
+        match ActionRequest::from_json(&js) {
+            Ok(ActionRequest::Hello(req)) => assert_eq!(req.protocol_version, 3),
+            other => panic!("unexpected request: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn binary_tags() {
+        for &tag in TAGS {
+            let mut buf = Vec::new();
+            String::from(tag).encode(&mut buf).unwrap();
+            match ActionRequest::decode(&mut buf.as_slice()) {
+                Ok(_) => assert!(tag == "cancel" || tag == "quit"),
+                // the tag is known and the payload is missing
+                Err(codec::Error::IO(_)) => assert!(tag != "cancel" && tag != "quit"),
+                Err(e) => panic!("{}: {:?}", tag, e),
+            }
+        }
+
+        let mut buf = Vec::new();
+        String::from("no-such-request").encode(&mut buf).unwrap();
+        match ActionRequest::decode(&mut buf.as_slice()) {
+            Err(codec::Error::UnknownRequest(tag)) => assert_eq!(tag, "no-such-request"),
+            other => panic!("unexpected request: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn json_unknown_alternatives() {
+        let request = |menu : &str, default : &str, choice : &str| format!(
+            r#"{{"tag": "consistency", "request": {{"subject": {{"name": "s", "alternatives": ["a", "b"],
+                "choices": [{{"menu": {}, "default": {}, "choice": {}}}]}}}}}}"#,
+            menu, default, choice,
+        ).replace('\n', " ") + "\n";
+        let read = |line : String| read_json_request(&mut line.as_bytes()).unwrap();
+
+        match read(request("[0,1]", "1", "[0]")) {
+            ActionRequest::Consistency(req) => assert_eq!(req.subject.unpack().choices.len(), 1),
+            other => panic!("unexpected request: {:?}", other),
+        }
+
+        for &(menu, default, choice) in &[("[0,5]", "null", "[0]"), ("[0,1]", "2", "[0]"), ("[0,1]", "null", "[3]")] {
+            match read(request(menu, default, choice)) {
+                ActionRequest::Fail(msg) => assert!(msg.starts_with("bad request"), "{}", msg),
+                other => panic!("unexpected request: {:?}", other),
+            }
+        }
+    }
 }
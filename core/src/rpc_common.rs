@@ -2,6 +2,32 @@ use alt::Alt;
 use alt_set::AltSet;
 use std::io::{Read,Write};
 use codec::{self,Decode,Encode};
+use json::{self,Json,ToJson,FromJson};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool,Ordering};
+
+/// Raised by the RPC reader thread when the client cancels the running request.
+/// Long computations check it between subjects, cycles, preorders, etc.
+#[derive(Clone, Debug, Default)]
+pub struct CancelFlag(Arc<AtomicBool>);
+
+impl CancelFlag {
+    pub fn new() -> CancelFlag {
+        CancelFlag(Arc::new(AtomicBool::new(false)))
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst)
+    }
+
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::SeqCst)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct ChoiceRow {
@@ -26,6 +52,26 @@ impl Decode for ChoiceRow {
     }
 }
 
+impl ToJson for ChoiceRow {
+    fn to_json(&self) -> Json {
+        json::object(vec![
+            ("menu", self.menu.to_json()),
+            ("default", self.default.to_json()),
+            ("choice", self.choice.to_json()),
+        ])
+    }
+}
+
+impl FromJson for ChoiceRow {
+    fn from_json(js : &Json) -> json::Result<ChoiceRow> {
+        Ok(ChoiceRow {
+            menu: json::field(js, "menu")?,
+            default: json::field(js, "default")?,
+            choice: json::field(js, "choice")?,
+        })
+    }
+}
+
 #[macro_export]
 macro_rules! choices {
     ($([$($x:expr),*] -> [$($y:expr),*]),*) => {vec![
@@ -75,3 +121,38 @@ impl Decode for Subject {
         })
     }
 }
+
+impl ToJson for Subject {
+    fn to_json(&self) -> Json {
+        json::object(vec![
+            ("name", self.name.to_json()),
+            ("alternatives", self.alternatives.to_json()),
+            ("choices", self.choices.to_json()),
+        ])
+    }
+}
+
+// JSON requests are written by hand so, unlike the binary ones coming from the GUI,
+// they are checked not to refer to alternatives that do not exist
+impl FromJson for Subject {
+    fn from_json(js : &Json) -> json::Result<Subject> {
+        let subject = Subject {
+            name: json::field(js, "name")?,
+            alternatives: json::field(js, "alternatives")?,
+            choices: json::field(js, "choices")?,
+        };
+
+        let alt_count = subject.alternatives.len() as u32;
+        let known = |alt : Alt| alt.index() < alt_count;
+        for cr in &subject.choices {
+            if !cr.menu.view().iter().all(known)
+                || !cr.default.is_none_or(known)
+                || !cr.choice.view().iter().all(known)
+            {
+                return Err(json::Error::Expected("alternatives given by their index in the list of alternatives"));
+            }
+        }
+
+        Ok(subject)
+    }
+}
@@ -34,3 +34,209 @@ pub fn greedy<T : Eq+Hash+Clone>(sets : &[HashSet<T>]) -> HashSet<usize> {
 
     selected_indices
 }
+
+/// Smallest choice of sets covering the union of all sets.
+/// Branch and bound with the greedy cover as the initial incumbent.
+pub fn exact<T : Eq+Hash+Clone>(sets : &[HashSet<T>]) -> HashSet<usize> {
+    let universe : Vec<T> = sets.iter().fold(
+        HashSet::new(),
+        |mut univ, set| { univ.extend(set.iter().cloned()); univ },
+    ).into_iter().collect();
+
+    // for each element, the sets containing it
+    let containing : Vec<Vec<usize>> = universe.iter().map(
+        |x| (0..sets.len()).filter(|&i| sets[i].contains(x)).collect()
+    ).collect();
+
+    let mut best = greedy(sets);
+    let mut selected = vec![false; sets.len()];
+    exact_branch(&containing, &mut selected, 0, &mut best);
+
+    best
+}
+
+// elements that share no set need distinct sets
+fn packing_bound(uncovered : &[&Vec<usize>], set_count : usize) -> usize {
+    let mut used = vec![false; set_count];
+    let mut bound = 0;
+
+    for idxs in uncovered {
+        if idxs.iter().all(|&i| !used[i]) {
+            for &i in idxs.iter() {
+                used[i] = true;
+            }
+            bound += 1;
+        }
+    }
+
+    bound
+}
+
+fn exact_branch(
+    containing : &[Vec<usize>],
+    selected : &mut [bool],
+    size : usize,
+    best : &mut HashSet<usize>,
+) {
+    let mut uncovered : Vec<&Vec<usize>> = containing.iter().filter(
+        |idxs| !idxs.iter().any(|&i| selected[i])
+    ).collect();
+
+    if uncovered.is_empty() {
+        if size < best.len() {
+            *best = (0..selected.len()).filter(|&i| selected[i]).collect();
+        }
+        return;
+    }
+
+    // rare elements first: they give both a better bound and fewer branches
+    uncovered.sort_by_key(|idxs| idxs.len());
+    if size + packing_bound(&uncovered, selected.len()) >= best.len() {
+        return;
+    }
+
+    for &i in uncovered[0].iter() {
+        selected[i] = true;
+        exact_branch(containing, selected, size + 1, best);
+        selected[i] = false;
+    }
+}
+
+/// Cheapest choice of sets covering the union of all sets, with its total cost.
+/// Exponential in the worst case; costs must be non-negative.
+pub fn weighted_exact<T : Eq+Hash+Clone>(sets : &[HashSet<T>], costs : &[f64]) -> (f64, HashSet<usize>) {
+    assert_eq!(sets.len(), costs.len());
+
+    let universe : Vec<T> = sets.iter().fold(
+        HashSet::new(),
+        |mut univ, set| { univ.extend(set.iter().cloned()); univ },
+    ).into_iter().collect();
+
+    // for each element, the sets containing it, cheapest first
+    let containing : Vec<Vec<usize>> = universe.iter().map(|x| {
+        let mut idxs : Vec<usize> = (0..sets.len()).filter(|&i| sets[i].contains(x)).collect();
+        idxs.sort_by(|&i, &j| costs[i].partial_cmp(&costs[j]).unwrap());
+        idxs
+    }).collect();
+
+    let mut best = weighted_greedy(&containing, costs);
+    let mut selected = vec![false; sets.len()];
+    weighted_branch(&containing, costs, &mut selected, 0.0, &mut best);
+
+    best
+}
+
+// the cheapest cost per newly covered element
+fn weighted_greedy(containing : &[Vec<usize>], costs : &[f64]) -> (f64, HashSet<usize>) {
+    let mut covered = vec![false; containing.len()];
+    let mut selected_indices = HashSet::new();
+    let mut total = 0.0;
+
+    while covered.iter().any(|&c| !c) {
+        let mut best : Option<(f64, usize)> = None;
+
+        for (i, &cost) in costs.iter().enumerate() {
+            if selected_indices.contains(&i) {
+                continue;
+            }
+
+            let score = containing.iter().enumerate().filter(
+                |&(x, idxs)| !covered[x] && idxs.contains(&i)
+            ).count();
+
+            if score > 0 {
+                let price = cost / score as f64;
+                if best.is_none_or(|(best_price, _)| price < best_price) {
+                    best = Some((price, i));
+                }
+            }
+        }
+
+        let (_, i) = best.unwrap();  // every uncovered element is in some set
+        for (x, idxs) in containing.iter().enumerate() {
+            if idxs.contains(&i) {
+                covered[x] = true;
+            }
+        }
+        selected_indices.insert(i);
+        total += costs[i];
+    }
+
+    (total, selected_indices)
+}
+
+fn weighted_branch(
+    containing : &[Vec<usize>],
+    costs : &[f64],
+    selected : &mut [bool],
+    cost : f64,
+    best : &mut (f64, HashSet<usize>),
+) {
+    // the uncovered element with the fewest options
+    let uncovered = containing.iter().filter(
+        |idxs| !idxs.iter().any(|&i| selected[i])
+    );
+
+    let idxs = match uncovered.clone().min_by_key(|idxs| idxs.len()) {
+        None => {
+            // everything covered
+            if cost < best.0 {
+                *best = (cost, (0..selected.len()).filter(|&i| selected[i]).collect());
+            }
+            return;
+        }
+        Some(idxs) => idxs,
+    };
+
+    // every uncovered element costs at least its cheapest set
+    let lower_bound = uncovered.map(|idxs| costs[idxs[0]]).fold(0.0, f64::max);
+    if cost + lower_bound >= best.0 {
+        return;
+    }
+
+    for &i in idxs {
+        if cost + costs[i] >= best.0 {
+            break;  // sorted by cost
+        }
+
+        selected[i] = true;
+        weighted_branch(containing, costs, selected, cost + costs[i], best);
+        selected[i] = false;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sets(xss : &[&[u32]]) -> Vec<HashSet<u32>> {
+        xss.iter().map(|xs| xs.iter().cloned().collect()).collect()
+    }
+
+    #[test]
+    fn unweighted() {
+        // greedy takes the biggest set first and then needs two more
+        let sets = sets(&[&[1,2,3,4], &[1,2,5], &[3,4,6]]);
+        assert_eq!(greedy(&sets).len(), 3);
+        assert_eq!(exact(&sets), [1,2].iter().cloned().collect());
+
+        assert_eq!(exact::<u32>(&[]), HashSet::new());
+    }
+
+    #[test]
+    fn weighted() {
+        // the two small sets together cover what the big set covers,
+        // so the cheapest cover takes them when the big set costs more than both
+        // and the big set when it costs less
+        let sets = sets(&[&[1,2,3,4], &[1,2], &[3,4], &[5]]);
+        let (cost, selected) = weighted_exact(&sets, &[3.0, 1.0, 1.0, 0.5]);
+        assert_eq!(cost, 2.5);
+        assert_eq!(selected, [1,2,3].iter().cloned().collect());
+
+        let (cost, selected) = weighted_exact(&sets, &[1.5, 1.0, 1.0, 0.0]);
+        assert_eq!(cost, 1.5);
+        assert_eq!(selected, [0,3].iter().cloned().collect());
+
+        assert_eq!(weighted_exact::<u32>(&[], &[]), (0.0, HashSet::new()));
+    }
+}
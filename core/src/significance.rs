@@ -0,0 +1,261 @@
+use std::io::{Read,Write};
+use std::sync::mpsc;
+use std::thread;
+use rand::{Rng,SeedableRng};
+use rand::rngs::SmallRng;
+use rayon::prelude::*;
+
+use model::{Model,Penalty};
+use precomputed::Precomputed;
+use rpc_common::{Subject,CancelFlag};
+use codec::{self,Encode,Decode,Packed};
+use json::{self,Json,ToJson,FromJson};
+use simulation::{self,GenChoices};
+use estimation::{self,EstimationError,Result};
+use rpc::Log;
+
+/* How significant is the score of a subject?
+ *
+ * We generate benchmark subjects that choose randomly on the same menus
+ * (copycat menus, see `simulation`), estimate them with the same models
+ * and compare their best scores to the score of the subject.
+ *
+ * Every benchmark subject gets its own RNG, seeded from the core RNG
+ * before the estimation starts, so the results depend only on the seed
+ * and not on the order in which the parallel workers finish.
+ */
+
+#[derive(Debug)]
+pub struct Request {
+    subject : Packed<Subject>,
+    models : Vec<Model>,
+    gen_choices : GenChoices,
+    preserve_deferrals : bool,
+    disregard_deferrals : bool,
+    sample_size : u32,  // number of benchmark subjects
+    disable_parallelism : bool,
+}
+
+impl Decode for Request {
+    fn decode<R : Read>(f : &mut R) -> codec::Result<Request> {
+        Ok(Request {
+            subject: Decode::decode(f)?,
+            models: Decode::decode(f)?,
+            gen_choices: Decode::decode(f)?,
+            preserve_deferrals: Decode::decode(f)?,
+            disregard_deferrals: Decode::decode(f)?,
+            sample_size: Decode::decode(f)?,
+            disable_parallelism: Decode::decode(f)?,
+        })
+    }
+}
+
+impl FromJson for Request {
+    fn from_json(js : &Json) -> json::Result<Request> {
+        Ok(Request {
+            subject: json::field(js, "subject")?,
+            models: json::field(js, "models")?,
+            gen_choices: json::field(js, "gen_choices")?,
+            preserve_deferrals: json::field(js, "preserve_deferrals")?,
+            disregard_deferrals: json::field(js, "disregard_deferrals")?,
+            sample_size: json::field(js, "sample_size")?,
+            disable_parallelism: json::field(js, "disable_parallelism")?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub subject_name : String,
+    pub score : Penalty,
+    pub benchmark_scores : Vec<u32>,  // upper bounds, in the order of generation
+    pub percentile : f64,  // fraction of benchmark subjects scoring at most `score`
+    pub power : f64,  // fraction of benchmark subjects that are not rationalised perfectly
+    pub predictive_success : f64,  // pass (0 or 1) minus the pass rate of the benchmark
+}
+
+impl Encode for Response {
+    fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+        (
+            &self.subject_name,
+            self.score.clone(),
+            &self.benchmark_scores,
+            self.percentile,
+            self.power,
+            self.predictive_success,
+        ).encode(f)
+    }
+}
+
+impl ToJson for Response {
+    fn to_json(&self) -> Json {
+        json::object(vec![
+            ("subject_name", self.subject_name.to_json()),
+            ("score", self.score.to_json()),
+            ("benchmark_scores", self.benchmark_scores.to_json()),
+            ("percentile", self.percentile.to_json()),
+            ("power", self.power.to_json()),
+            ("predictive_success", self.predictive_success.to_json()),
+        ])
+    }
+}
+
+fn best_score(precomputed : &Precomputed, subject : &Subject, request : &Request, cancel : &CancelFlag)
+    -> Result<Penalty>
+{
+    if cancel.is_cancelled() {
+        return Err(EstimationError::Cancelled);
+    }
+
+    let subject = subject.drop_deferrals(request.disregard_deferrals);
+    Ok(estimation::run_one(precomputed, &subject, &request.models, cancel)?.score)
+}
+
+fn benchmark_score(precomputed : &Precomputed, request : &Request, seed : [u8; 32], cancel : &CancelFlag)
+    -> Result<u32>
+{
+    let mut rng = SmallRng::from_seed(seed);
+    let subject = request.subject.unpack();
+    let benchmark = Subject {
+        name: subject.name.clone(),
+        alternatives: subject.alternatives.clone(),
+        choices: simulation::copycat_choices(
+            &mut rng, subject, &request.gen_choices, request.preserve_deferrals
+        ),
+    };
+
+    Ok(best_score(precomputed, &benchmark, request, cancel)?.upper_bound)
+}
+
+/// Progress is reported as the number of benchmark subjects estimated so far.
+pub fn run<L : Log, R : Rng>(
+    mut log : L,
+    rng : &mut R,
+    precomputed : &mut Precomputed,
+    request : &Request,
+    cancel : &CancelFlag,
+) -> Result<Response> {
+    let subject = request.subject.unpack();
+    estimation::precompute_for(precomputed, subject.alternatives.len() as u32, &request.models);
+    let precomputed : &Precomputed = precomputed;
+
+    let score = best_score(precomputed, subject, request, cancel)?;
+
+    let seeds : Vec<[u8; 32]> = (0..request.sample_size).map(|_| rng.gen()).collect();
+    let mut benchmark_scores = vec![0; seeds.len()];
+    let mut finished = 0;
+    let mut first_error = None;
+    let mut report = |index : usize, result : Result<u32>| {
+        match result {
+            Ok(benchmark_score) => benchmark_scores[index] = benchmark_score,
+            Err(e) => if first_error.is_none() {
+                first_error = Some(e);
+            },
+        }
+
+        finished += 1;
+        log.progress(finished);
+    };
+
+    if request.disable_parallelism {
+        for (i, &seed) in seeds.iter().enumerate() {
+            report(i, benchmark_score(precomputed, request, seed, cancel));
+        }
+    } else {
+        // the workers send the results to this thread, which owns the logger
+        let (tx, rx) = mpsc::channel();
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                seeds.par_iter().enumerate().for_each_with(tx,
+                    |tx, (i, &seed)| tx.send((i, benchmark_score(precomputed, request, seed, cancel))).unwrap()
+                );
+            });
+
+            for (i, result) in rx {
+                report(i, result);
+            }
+        });
+    }
+
+    if let Some(e) = first_error {
+        return Err(e);
+    }
+
+    let sample_size = benchmark_scores.len() as f64;
+    let fraction = |pred : &dyn Fn(u32) -> bool|
+        if benchmark_scores.is_empty() {
+            0.0
+        } else {
+            benchmark_scores.iter().filter(|&&s| pred(s)).count() as f64 / sample_size
+        };
+
+    let pass_rate = fraction(&|s| s == 0);
+    let pass = if score.upper_bound == 0 { 1.0 } else { 0.0 };
+
+    Ok(Response {
+        subject_name: subject.name.clone(),
+        percentile: fraction(&|s| s <= score.upper_bound),
+        power: 1.0 - pass_rate,
+        predictive_success: pass - pass_rate,
+        benchmark_scores,
+        score,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use model::PreorderParams;
+    use rpc::DummyLogger;
+    use rpc_common::ChoiceRow;
+    use alt_set::AltSet;
+    use alt::Alt;
+    use std::iter::FromIterator;
+
+    fn request(disable_parallelism : bool) -> Request {
+        Request {
+            subject: Packed(Subject {
+                name: String::from("subject"),
+                alternatives: (0..3).map(|i| i.to_string()).collect(),
+                choices: choices![
+                    [0,1] -> [0],
+                    [1,2] -> [1],
+                    [0,2] -> [0]
+                ],
+            }),
+            models: vec![Model::PreorderMaximization(PreorderParams{strict: Some(true), total: Some(true)})],
+            gen_choices: GenChoices::Uniform{forced_choice: true, multiple_choice: false},
+            preserve_deferrals: false,
+            disregard_deferrals: false,
+            sample_size: 400,
+            disable_parallelism,
+        }
+    }
+
+    #[test]
+    fn binary_menus() {
+        let mut rng = SmallRng::from_seed([7; 32]);
+        let mut precomputed = Precomputed::new(None);
+        let response = run(DummyLogger, &mut rng, &mut precomputed, &request(false), &CancelFlag::new()).unwrap();
+
+        // two out of eight choice patterns on three binary menus are cyclic
+        assert_eq!(response.score, Penalty::exact(0));
+        assert_eq!(response.benchmark_scores.len(), 400);
+        assert!(response.benchmark_scores.iter().all(|&s| s <= 1));
+        assert!(response.power > 0.15 && response.power < 0.35, "power: {}", response.power);
+        assert_eq!(response.predictive_success, response.power);
+        assert_eq!(response.percentile, 1.0 - response.power);
+    }
+
+    #[test]
+    fn reproducible() {
+        let scores = |disable_parallelism| {
+            let mut rng = SmallRng::from_seed([42; 32]);
+            let mut precomputed = Precomputed::new(None);
+            run(DummyLogger, &mut rng, &mut precomputed, &request(disable_parallelism), &CancelFlag::new())
+                .unwrap().benchmark_scores
+        };
+
+        assert_eq!(scores(true), scores(false));
+    }
+}
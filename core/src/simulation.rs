@@ -8,6 +8,9 @@ use rand::seq::SliceRandom;
 use model;
 use rpc_common::{ChoiceRow,Subject};
 use codec::{Encode,Decode,Packed,self};
+use json::{self,Json,ToJson,FromJson};
+use base64::prelude::BASE64_STANDARD;
+use base64::engine::Engine;
 use alt_set::{AltSet,AltSetView};
 use alt::Alt;
 
@@ -35,6 +38,20 @@ impl Decode for MenuGenerator {
     }
 }
 
+impl FromJson for MenuGenerator {
+    fn from_json(js : &Json) -> json::Result<MenuGenerator> {
+        use self::MenuGenerator::*;
+
+        match json::tag(js)?.as_str() {
+            "exhaustive" => Ok(Exhaustive),
+            "sample-with-replacement" => Ok(SampleWithReplacement(json::field(js, "count")?)),
+            "copycat" => Ok(Copycat(json::field(js, "subject")?)),
+            "binary" => Ok(Binary),
+            tag => Err(json::Error::BadTag(String::from(tag))),
+        }
+    }
+}
+
 impl MenuGenerator {
     fn gen<R : Rng>(&self, rng : &mut R, alt_count : u32) -> Vec<(AltSet, Option<Alt>)> {
         use self::MenuGenerator::*;
@@ -99,6 +116,15 @@ impl Decode for GenMenus {
     }
 }
 
+impl FromJson for GenMenus {
+    fn from_json(js : &Json) -> json::Result<GenMenus> {
+        Ok(GenMenus {
+            generator: json::field(js, "generator")?,
+            defaults: json::field(js, "defaults")?,
+        })
+    }
+}
+
 #[derive(Debug)]
 pub enum GenChoices {
     Instance(model::Instance),
@@ -124,8 +150,30 @@ impl Decode for GenChoices {
     }
 }
 
+// the instance is given by its base64 code, like in instviz
+impl FromJson for GenChoices {
+    fn from_json(js : &Json) -> json::Result<GenChoices> {
+        match json::tag(js)?.as_str() {
+            "instance" => {
+                let code : String = json::field(js, "instance")?;
+                let bytes = BASE64_STANDARD.decode(&code).map_err(
+                    |_| json::Error::Expected("a base64 instance code")
+                )?;
+                codec::decode_from_memory(&bytes).map(GenChoices::Instance).map_err(
+                    |_| json::Error::Expected("a valid instance code")
+                )
+            }
+            "uniform" => Ok(GenChoices::Uniform {
+                forced_choice: json::field(js, "forced_choice")?,
+                multiple_choice: json::field(js, "multiple_choice")?,
+            }),
+            tag => Err(json::Error::BadTag(String::from(tag))),
+        }
+    }
+}
+
 impl GenChoices {
-    fn gen<R : Rng>(&self, rng : &mut R, alt_count : u32, menu : AltSetView, default : Option<Alt>) -> AltSet {
+    pub fn gen<R : Rng>(&self, rng : &mut R, alt_count : u32, menu : AltSetView, default : Option<Alt>) -> AltSet {
         assert!(menu.is_nonempty());
         use self::GenChoices::*;
 
@@ -169,11 +217,11 @@ impl GenChoices {
 
 #[derive(Debug)]
 pub struct Request {
-    name : String,
-    alternatives : Vec<String>,
-    gen_menus : GenMenus,
-    gen_choices : GenChoices,
-    preserve_deferrals : bool,
+    pub name : String,
+    pub alternatives : Vec<String>,
+    pub gen_menus : GenMenus,
+    pub gen_choices : GenChoices,
+    pub preserve_deferrals : bool,
 }
 
 impl Decode for Request {
@@ -188,9 +236,21 @@ impl Decode for Request {
     }
 }
 
+impl FromJson for Request {
+    fn from_json(js : &Json) -> json::Result<Request> {
+        Ok(Request {
+            name: json::field(js, "name")?,
+            alternatives: json::field(js, "alternatives")?,
+            gen_menus: json::field(js, "gen_menus")?,
+            gen_choices: json::field(js, "gen_choices")?,
+            preserve_deferrals: json::field(js, "preserve_deferrals")?,
+        })
+    }
+}
+
 pub struct Response {
-    subject : Packed<Subject>,
-    observation_count : u32,
+    pub subject : Packed<Subject>,
+    pub observation_count : u32,
 }
 
 impl Encode for Response {
@@ -199,6 +259,15 @@ impl Encode for Response {
     }
 }
 
+impl ToJson for Response {
+    fn to_json(&self) -> Json {
+        json::object(vec![
+            ("subject", self.subject.to_json()),
+            ("observation_count", self.observation_count.to_json()),
+        ])
+    }
+}
+
 pub enum Error {
 }
 
@@ -216,24 +285,37 @@ impl fmt::Display for Error {
 
 pub type Result<T> = result::Result<T, Error>;
 
+/// New choices on the menus of `subject`.
+pub fn copycat_choices<R : Rng>(
+    rng : &mut R,
+    subject : &Subject,
+    gen_choices : &GenChoices,
+    preserve_deferrals : bool,
+) -> Vec<ChoiceRow> {
+    let alt_count = subject.alternatives.len() as u32;
+
+    subject.choices.iter().map(
+        |cr| ChoiceRow {
+            menu: cr.menu.clone(),
+            default: cr.default.clone(),
+            choice: if preserve_deferrals
+                && cr.choice.view().is_empty() {
+                    AltSet::empty()
+                } else {
+                    gen_choices.gen(
+                        rng, alt_count, cr.menu.view(), cr.default
+                    )
+                }
+        }
+    ).collect()
+}
+
 pub fn run<R : Rng>(rng : &mut R, request : Request) -> Result<Response> {
     let alt_count = request.alternatives.len() as u32;
 
     let choices : Vec<ChoiceRow> = match request.gen_menus.generator {
-        MenuGenerator::Copycat(Packed(ref subj)) => subj.choices.iter().map(
-            |cr| ChoiceRow {
-                menu: cr.menu.clone(),
-                default: cr.default.clone(),
-                choice: if request.preserve_deferrals
-                    && cr.choice.view().is_empty() {
-                        AltSet::empty()
-                    } else {
-                        request.gen_choices.gen(
-                            rng, alt_count, cr.menu.view(), cr.default
-                        )
-                    }
-            }
-        ).collect(),
+        MenuGenerator::Copycat(Packed(ref subj)) =>
+            copycat_choices(rng, subj, &request.gen_choices, request.preserve_deferrals),
 
         _ => request.gen_menus.gen(rng, alt_count).into_iter().map(
             // we use this order of ChoiceRow fields
@@ -0,0 +1,956 @@
+use std::fmt;
+use std::result;
+use std::str::FromStr;
+use std::io::{Read,Write};
+use std::collections::BTreeMap;
+use alt::Alt;
+use alt_set::AltSet;
+use linear_preorders;
+use rpc_common::Subject;
+use codec::{self,Encode,Decode,Packed};
+use json::{self,Json,ToJson,FromJson};
+use rayon::prelude::*;
+
+// we enumerate all linear orders for random preferences
+pub const MAX_ALTERNATIVES_RANDOM_PREFERENCE : u32 = 7;
+
+const MAX_ITERATIONS : u32 = 10_000;
+const TOLERANCE : f64 = 1e-10;
+
+// orders with lower probabilities are not reported
+const MIN_REPORTED_PROBABILITY : f64 = 1e-6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StochasticModel {
+    Luce,
+    RandomPreference,
+}
+
+impl Encode for StochasticModel {
+    fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+        match *self {
+            StochasticModel::Luce => 0u8.encode(f),
+            StochasticModel::RandomPreference => 1u8.encode(f),
+        }
+    }
+}
+
+impl Decode for StochasticModel {
+    fn decode<R : Read>(f : &mut R) -> codec::Result<StochasticModel> {
+        match Decode::decode(f)? {
+            0u8 => Ok(StochasticModel::Luce),
+            1u8 => Ok(StochasticModel::RandomPreference),
+            _ => Err(codec::Error::BadEnumTag),
+        }
+    }
+}
+
+impl ToJson for StochasticModel {
+    fn to_json(&self) -> Json {
+        String::from(match *self {
+            StochasticModel::Luce => "luce",
+            StochasticModel::RandomPreference => "random-preference",
+        }).to_json()
+    }
+}
+
+impl StochasticModel {
+    pub fn all() -> Vec<StochasticModel> {
+        vec![StochasticModel::Luce, StochasticModel::RandomPreference]
+    }
+
+    /// The largest number of alternatives accepted by the estimation, if any.
+    pub fn max_alternatives(self) -> Option<u32> {
+        match self {
+            StochasticModel::Luce => None,
+            StochasticModel::RandomPreference => Some(MAX_ALTERNATIVES_RANDOM_PREFERENCE),
+        }
+    }
+}
+
+impl FromStr for StochasticModel {
+    type Err = String;
+
+    fn from_str(s : &str) -> result::Result<StochasticModel, String> {
+        match s {
+            "luce" => Ok(StochasticModel::Luce),
+            "random-preference" => Ok(StochasticModel::RandomPreference),
+            _ => Err(format!("unknown stochastic model \"{}\"", s)),
+        }
+    }
+}
+
+impl FromJson for StochasticModel {
+    fn from_json(js : &Json) -> json::Result<StochasticModel> {
+        match String::from_json(js)?.as_str() {
+            "luce" => Ok(StochasticModel::Luce),
+            "random-preference" => Ok(StochasticModel::RandomPreference),
+            tag => Err(json::Error::BadTag(String::from(tag))),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    TooManyAlternatives {
+        model : StochasticModel,
+        alt_count : u32,
+    },
+}
+
+impl Encode for Error {
+    fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+        match *self {
+            Error::TooManyAlternatives{model, alt_count}
+                => (0u8, model, alt_count).encode(f),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::TooManyAlternatives{model, alt_count}
+                => write!(f, "too many alternatives for {:?}: {}", model, alt_count),
+        }
+    }
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+/* Maximum-likelihood fits of stochastic choice models.
+ *
+ * The GUI gets the same fits from `estimation::Request::stochastic_models`;
+ * this request fits the stochastic models alone.
+ */
+#[derive(Debug, Clone)]
+pub struct Request {
+    subjects : Vec<Packed<Subject>>,
+    models : Vec<StochasticModel>,
+    disable_parallelism : bool,
+}
+
+impl Decode for Request {
+    fn decode<R : Read>(f : &mut R) -> codec::Result<Request> {
+        Ok(Request {
+            subjects: Decode::decode(f)?,
+            models: Decode::decode(f)?,
+            disable_parallelism: Decode::decode(f)?,
+        })
+    }
+}
+
+impl FromJson for Request {
+    fn from_json(js : &Json) -> json::Result<Request> {
+        Ok(Request {
+            subjects: json::field(js, "subjects")?,
+            models: json::field(js, "models")?,
+            disable_parallelism: json::field(js, "disable_parallelism")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Estimate {
+    // indexed by alternatives, sums up to 1
+    Luce(Vec<f64>),
+
+    // linear orders (best first) with their probabilities, most likely first
+    RandomPreference(Vec<(Vec<Alt>, f64)>),
+}
+
+impl Encode for Estimate {
+    fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+        match *self {
+            Estimate::Luce(ref weights) => (0u8, weights).encode(f),
+            Estimate::RandomPreference(ref orders) => (1u8, orders).encode(f),
+        }
+    }
+}
+
+impl ToJson for Estimate {
+    fn to_json(&self) -> Json {
+        match *self {
+            Estimate::Luce(ref weights) => json::object(vec![
+                ("type", String::from("luce").to_json()),
+                ("weights", weights.to_json()),
+            ]),
+            Estimate::RandomPreference(ref orders) => json::object(vec![
+                ("type", String::from("random-preference").to_json()),
+                ("orders", orders.to_json()),
+            ]),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Fit {
+    pub estimate : Estimate,
+    pub log_likelihood : f64,
+    pub iterations : u32,
+}
+
+impl Encode for Fit {
+    fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+        (&self.estimate, self.log_likelihood, self.iterations).encode(f)
+    }
+}
+
+impl ToJson for Fit {
+    fn to_json(&self) -> Json {
+        json::object(vec![
+            ("estimate", self.estimate.to_json()),
+            ("log_likelihood", self.log_likelihood.to_json()),
+            ("iterations", self.iterations.to_json()),
+        ])
+    }
+}
+
+pub struct Response {
+    pub subject_name : String,
+    pub observations : u32,  // rows with a single chosen alternative
+    pub ignored_rows : u32,  // deferrals and multiple choices
+    pub fits : Vec<Fit>,
+}
+
+impl Encode for Response {
+    fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+        (&self.subject_name, self.observations, self.ignored_rows, &self.fits).encode(f)
+    }
+}
+
+impl ToJson for Response {
+    fn to_json(&self) -> Json {
+        json::object(vec![
+            ("subject_name", self.subject_name.to_json()),
+            ("observations", self.observations.to_json()),
+            ("ignored_rows", self.ignored_rows.to_json()),
+            ("fits", self.fits.to_json()),
+        ])
+    }
+}
+
+// identical rows are merged
+struct Observation {
+    menu : AltSet,
+    choice : Alt,
+    count : u32,
+}
+
+fn observations(subject : &Subject) -> (Vec<Observation>, u32) {
+    let mut counts = BTreeMap::new();
+    let mut ignored_rows = 0;
+
+    for cr in &subject.choices {
+        match cr.choice.view().as_singleton() {
+            Some(choice) => *counts.entry((cr.menu.clone(), choice)).or_insert(0) += 1,
+            None => ignored_rows += 1,
+        }
+    }
+
+    let observations = counts.into_iter().map(
+        |((menu, choice), count)| Observation{menu, choice, count}
+    ).collect();
+
+    (observations, ignored_rows)
+}
+
+/* Luce (multinomial logit) model: P(a | A) = w_a / sum_{b in A} w_b
+ *
+ * Maximum likelihood by the MM algorithm of Hunter (2004):
+ * w_a <- (times a was chosen) / sum_{observations where a in A} 1 / w(A)
+ */
+fn luce(alt_count : u32, observations : &[Observation]) -> Fit {
+    let menu_weight = |weights : &[f64], menu : &AltSet|
+        menu.view().iter().map(|Alt(i)| weights[i as usize]).sum::<f64>();
+
+    let mut wins = vec![0.0; alt_count as usize];
+    for obs in observations {
+        wins[obs.choice.index() as usize] += obs.count as f64;
+    }
+
+    let mut weights = vec![1.0 / alt_count as f64; alt_count as usize];
+    let mut iterations = 0;
+
+    if !observations.is_empty() {
+        while iterations < MAX_ITERATIONS {
+            iterations += 1;
+
+            let mut denominators = vec![0.0; alt_count as usize];
+            for obs in observations {
+                let w = menu_weight(&weights, &obs.menu);
+                for Alt(i) in obs.menu.view().iter() {
+                    denominators[i as usize] += obs.count as f64 / w;
+                }
+            }
+
+            let mut new_weights : Vec<f64> = wins.iter().zip(&denominators).map(
+                |(&win, &denom)| if denom > 0.0 { win / denom } else { 0.0 }
+            ).collect();
+
+            let total : f64 = new_weights.iter().sum();
+            for w in &mut new_weights {
+                *w /= total;
+            }
+
+            let change = weights.iter().zip(&new_weights).map(
+                |(w, nw)| (w - nw).abs()
+            ).fold(0.0, f64::max);
+
+            weights = new_weights;
+            if change < TOLERANCE {
+                break;
+            }
+        }
+    }
+
+    let log_likelihood = observations.iter().map(
+        |obs| obs.count as f64 * (
+            weights[obs.choice.index() as usize] / menu_weight(&weights, &obs.menu)
+        ).ln()
+    ).sum();
+
+    Fit {
+        estimate: Estimate::Luce(weights),
+        log_likelihood,
+        iterations,
+    }
+}
+
+/* Random preference model: a probability distribution over linear orders,
+ * where P(a | A) is the probability of the orders that rank a top in A.
+ *
+ * Maximum likelihood by EM, starting from the uniform distribution.
+ * The distribution is generally not identified by the data
+ * so we report the fixpoint reached from the uniform start;
+ * the log-likelihood is the maximum nevertheless (the likelihood is concave).
+ */
+fn random_preference(alt_count : u32, observations : &[Observation]) -> Result<Fit> {
+    if alt_count > MAX_ALTERNATIVES_RANDOM_PREFERENCE {
+        return Err(Error::TooManyAlternatives{
+            model: StochasticModel::RandomPreference,
+            alt_count,
+        });
+    }
+
+    let orders : Vec<Vec<Alt>> = linear_preorders::all(alt_count).map(
+        |p| p.as_linear_order()
+    ).collect();
+
+    // consistent[k] = orders that choose the observed alternative in observation k
+    let consistent : Vec<Vec<usize>> = observations.iter().map(|obs|
+        orders.iter().enumerate().filter(
+            |&(_, order)| order.iter().find(|&&a| obs.menu.view().contains(a)) == Some(&obs.choice)
+        ).map(|(o, _)| o).collect()
+    ).collect();
+
+    let total_count : f64 = observations.iter().map(|obs| obs.count as f64).sum();
+    let mut probs = vec![1.0 / orders.len() as f64; orders.len()];
+    let log_likelihood = |probs : &[f64]| -> f64 {
+        observations.iter().zip(&consistent).map(
+            |(obs, os)| obs.count as f64 * os.iter().map(|&o| probs[o]).sum::<f64>().ln()
+        ).sum()
+    };
+
+    let mut iterations = 0;
+    let mut last_ll = log_likelihood(&probs);
+
+    if !observations.is_empty() {
+        while iterations < MAX_ITERATIONS {
+            iterations += 1;
+
+            // E-step and M-step at once
+            let mut new_probs = vec![0.0; orders.len()];
+            for (obs, os) in observations.iter().zip(&consistent) {
+                let p : f64 = os.iter().map(|&o| probs[o]).sum();
+                for &o in os {
+                    new_probs[o] += obs.count as f64 * probs[o] / p / total_count;
+                }
+            }
+
+            probs = new_probs;
+            let ll = log_likelihood(&probs);
+            let converged = (ll - last_ll).abs() < TOLERANCE;
+            last_ll = ll;
+
+            if converged {
+                break;
+            }
+        }
+    }
+
+    let mut reported : Vec<(Vec<Alt>, f64)> = orders.into_iter().zip(probs).filter(
+        |&(_, p)| p >= MIN_REPORTED_PROBABILITY
+    ).collect();
+    reported.sort_by(|(_, p), (_, q)| q.partial_cmp(p).unwrap());
+
+    Ok(Fit {
+        estimate: Estimate::RandomPreference(reported),
+        log_likelihood: last_ll,
+        iterations,
+    })
+}
+
+pub fn run_one(subject : &Subject, models : &[StochasticModel]) -> Result<Response> {
+    let alt_count = subject.alternatives.len() as u32;
+    let (observations, ignored_rows) = observations(subject);
+
+    let mut fits = Vec::with_capacity(models.len());
+    for &model in models {
+        fits.push(match model {
+            StochasticModel::Luce => luce(alt_count, &observations),
+            StochasticModel::RandomPreference => random_preference(alt_count, &observations)?,
+        });
+    }
+
+    Ok(Response {
+        subject_name: subject.name.clone(),
+        observations: observations.iter().map(|obs| obs.count).sum(),
+        ignored_rows,
+        fits,
+    })
+}
+
+pub fn run(request : &Request) -> Result<Vec<Packed<Response>>> {
+    let results : Vec<Result<Response>> = if request.disable_parallelism {
+        request.subjects.iter().map(
+            |subj| run_one(subj.unpack(), &request.models)
+        ).collect()
+    } else {
+        let mut results = Vec::new();
+        request.subjects.par_iter().map(
+            |subj| run_one(subj.unpack(), &request.models)
+        ).collect_into_vec(&mut results);
+        results
+    };
+
+    let mut responses = Vec::with_capacity(results.len());
+    for result in results.into_iter() {
+        responses.push(Packed(result?));
+    }
+
+    Ok(responses)
+}
+
+/* Axioms of stochastic choice, tested on the choice frequencies at every menu,
+ * with the rows of the same menu aggregated like for the estimation:
+ *
+ *  - regularity: P(a | B) ≤ P(a | A) whenever a ∈ A ⊊ B
+ *
+ * and for binary choice probabilities p(a,b) = P(a | {a,b}),
+ * whenever p(a,b) ≥ ½ and p(b,c) ≥ ½:
+ *
+ *  - weak stochastic transitivity: p(a,c) ≥ ½
+ *  - moderate stochastic transitivity: p(a,c) ≥ min(p(a,b), p(b,c))
+ *  - strong stochastic transitivity: p(a,c) ≥ max(p(a,b), p(b,c))
+ *
+ * and finally the triangle inequality p(a,b) + p(b,c) - p(a,c) ≤ 1,
+ * i.e. p(a,b) + p(b,c) + p(c,a) ≤ 2, tested once for every cyclic order (a,b,c).
+ *
+ * The tests are run for every subject and for all subjects pooled together.
+ */
+pub mod axioms {
+    use super::{Observation,observations};
+    use std::fmt;
+    use std::result;
+    use std::io::{Read,Write};
+    use std::collections::BTreeMap;
+    use std::iter::FromIterator;
+    use alt::Alt;
+    use alt_set::AltSet;
+    use simulation::Menu;
+    use rpc_common::Subject;
+    use codec::{self,Encode,Decode,Packed};
+    use json::{self,Json,ToJson,FromJson};
+
+    // frequencies are ratios of counts so only rounding errors need forgiving
+    const EPSILON : f64 = 1e-9;
+
+    #[derive(Debug)]
+    pub struct Request {
+        pub subjects : Vec<Packed<Subject>>,
+    }
+
+    impl Decode for Request {
+        fn decode<R : Read>(f : &mut R) -> codec::Result<Request> {
+            Ok(Request {
+                subjects: Decode::decode(f)?,
+            })
+        }
+    }
+
+    impl FromJson for Request {
+        fn from_json(js : &Json) -> json::Result<Request> {
+            Ok(Request {
+                subjects: json::field(js, "subjects")?,
+            })
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct RegularityViolation {
+        alt : Alt,
+        smaller : Menu,
+        larger : Menu,
+        p_smaller : f64,  // P(alt | smaller)
+        p_larger : f64,  // P(alt | larger)
+    }
+
+    impl Encode for RegularityViolation {
+        fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+            (self.alt, &self.smaller, &self.larger, self.p_smaller, self.p_larger).encode(f)
+        }
+    }
+
+    impl RegularityViolation {
+        fn json_with(&self, alternatives : Option<&[String]>) -> Json {
+            json::object(vec![
+                ("alternative", json::alt(self.alt, alternatives)),
+                ("smaller", json::alt_set(&self.smaller, alternatives)),
+                ("larger", json::alt_set(&self.larger, alternatives)),
+                ("p_smaller", self.p_smaller.to_json()),
+                ("p_larger", self.p_larger.to_json()),
+            ])
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct TripleViolation {
+        alts : (Alt, Alt, Alt),  // (a, b, c)
+        p_ab : f64,
+        p_bc : f64,
+        p_ac : f64,
+    }
+
+    impl Encode for TripleViolation {
+        fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+            (self.alts, self.p_ab, self.p_bc, self.p_ac).encode(f)
+        }
+    }
+
+    impl TripleViolation {
+        fn json_with(&self, alternatives : Option<&[String]>) -> Json {
+            let (a, b, c) = self.alts;
+            json::object(vec![
+                ("a", json::alt(a, alternatives)),
+                ("b", json::alt(b, alternatives)),
+                ("c", json::alt(c, alternatives)),
+                ("p_ab", self.p_ab.to_json()),
+                ("p_bc", self.p_bc.to_json()),
+                ("p_ac", self.p_ac.to_json()),
+            ])
+        }
+    }
+
+    pub struct Report {
+        subject_name : Option<String>,  // none when pooled
+        observations : u32,  // rows with a single chosen alternative
+        ignored_rows : u32,  // deferrals and multiple choices
+        regularity : Vec<RegularityViolation>,
+        weak : Vec<TripleViolation>,
+        moderate : Vec<TripleViolation>,
+        strong : Vec<TripleViolation>,
+        triangle : Vec<TripleViolation>,
+    }
+
+    impl Encode for Report {
+        fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+            (
+                &self.subject_name,
+                self.observations,
+                self.ignored_rows,
+                &self.regularity,
+                &self.weak,
+                &self.moderate,
+                &self.strong,
+                &self.triangle,
+            ).encode(f)
+        }
+    }
+
+    impl Report {
+        /// With the alternatives by name rather than by index.
+        pub fn to_named_json(&self, alternatives : &[String]) -> Json {
+            self.json_with(Some(alternatives))
+        }
+
+        fn json_with(&self, alternatives : Option<&[String]>) -> Json {
+            let triples = |violations : &[TripleViolation]| json::object(vec![
+                ("violations", violations.len().to_json()),
+                ("cases", Json::Array(violations.iter().map(|v| v.json_with(alternatives)).collect())),
+            ]);
+
+            json::object(vec![
+                ("subject", self.subject_name.to_json()),
+                ("observations", self.observations.to_json()),
+                ("ignored_rows", self.ignored_rows.to_json()),
+                ("regularity", json::object(vec![
+                    ("violations", self.regularity.len().to_json()),
+                    ("cases", Json::Array(self.regularity.iter().map(|v| v.json_with(alternatives)).collect())),
+                ])),
+                ("weak_stochastic_transitivity", triples(&self.weak)),
+                ("moderate_stochastic_transitivity", triples(&self.moderate)),
+                ("strong_stochastic_transitivity", triples(&self.strong)),
+                ("triangle_inequality", triples(&self.triangle)),
+            ])
+        }
+    }
+
+    impl ToJson for Report {
+        fn to_json(&self) -> Json {
+            self.json_with(None)
+        }
+    }
+
+    pub struct Response {
+        pub subjects : Vec<Report>,
+        pub pooled : Report,
+    }
+
+    impl Encode for Response {
+        fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+            (&self.subjects, &self.pooled).encode(f)
+        }
+    }
+
+    impl ToJson for Response {
+        fn to_json(&self) -> Json {
+            json::object(vec![
+                ("subjects", Json::Array(self.subjects.iter().map(ToJson::to_json).collect())),
+                ("pooled", self.pooled.to_json()),
+            ])
+        }
+    }
+
+    #[derive(Debug)]
+    pub enum Error {
+        // subjects are pooled by alternative indices
+        DifferentAlternatives(String),
+    }
+
+    impl Encode for Error {
+        fn encode<W : Write>(&self, f : &mut W) -> codec::Result<()> {
+            match *self {
+                Error::DifferentAlternatives(ref name) => (0u8, name).encode(f),
+            }
+        }
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+            match *self {
+                Error::DifferentAlternatives(ref name) => write!(f,
+                    "cannot pool subject {} because its alternatives differ from the other subjects'", name
+                ),
+            }
+        }
+    }
+
+    pub type Result<T> = result::Result<T, Error>;
+
+    // choice probabilities at every observed menu, indexed by alternatives
+    fn probabilities(alt_count : u32, observations : &[Observation]) -> BTreeMap<Menu, Vec<f64>> {
+        let mut counts = BTreeMap::new();
+        for obs in observations {
+            counts.entry(obs.menu.clone()).or_insert_with(
+                || vec![0; alt_count as usize]
+            )[obs.choice.index() as usize] += obs.count;
+        }
+
+        counts.into_iter().map(|(menu, counts)| {
+            let total : u32 = counts.iter().sum();
+            (menu, counts.into_iter().map(|count| count as f64 / total as f64).collect())
+        }).collect()
+    }
+
+    fn report(subject : &Subject) -> Report {
+        let alt_count = subject.alternatives.len() as u32;
+        let (observations, ignored_rows) = observations(subject);
+        let probs = probabilities(alt_count, &observations);
+
+        let mut regularity = Vec::new();
+        for (smaller, p_smaller) in &probs {
+            for (larger, p_larger) in &probs {
+                if !smaller.view().is_strict_subset_of(larger.view()) {
+                    continue;
+                }
+
+                for alt in smaller.view().iter() {
+                    let i = alt.index() as usize;
+                    if p_larger[i] > p_smaller[i] + EPSILON {
+                        regularity.push(RegularityViolation {
+                            alt,
+                            smaller: smaller.clone(),
+                            larger: larger.clone(),
+                            p_smaller: p_smaller[i],
+                            p_larger: p_larger[i],
+                        });
+                    }
+                }
+            }
+        }
+
+        let binary = |a : Alt, b : Alt| probs.get(
+            &AltSet::from_iter(&[a, b])
+        ).map(|p| p[a.index() as usize]);
+
+        let mut weak = Vec::new();
+        let mut moderate = Vec::new();
+        let mut strong = Vec::new();
+        let mut triangle = Vec::new();
+        for a in Alt::all(alt_count) {
+            for b in Alt::all(alt_count) {
+                for c in Alt::all(alt_count) {
+                    if a == b || b == c || a == c {
+                        continue;
+                    }
+
+                    let (p_ab, p_bc, p_ac) = match (binary(a, b), binary(b, c), binary(a, c)) {
+                        (Some(p_ab), Some(p_bc), Some(p_ac)) => (p_ab, p_bc, p_ac),
+                        _ => continue,
+                    };
+                    let violation = TripleViolation{alts: (a, b, c), p_ab, p_bc, p_ac};
+
+                    if p_ab >= 0.5 - EPSILON && p_bc >= 0.5 - EPSILON {
+                        if p_ac < 0.5 - EPSILON {
+                            weak.push(violation.clone());
+                        }
+
+                        if p_ac < p_ab.min(p_bc) - EPSILON {
+                            moderate.push(violation.clone());
+                        }
+
+                        if p_ac < p_ab.max(p_bc) - EPSILON {
+                            strong.push(violation.clone());
+                        }
+                    }
+
+                    // every cyclic order once, starting from its smallest alternative
+                    if a < b && a < c && p_ab + p_bc - p_ac > 1.0 + EPSILON {
+                        triangle.push(violation);
+                    }
+                }
+            }
+        }
+
+        Report {
+            subject_name: Some(subject.name.clone()),
+            observations: observations.iter().map(|obs| obs.count).sum(),
+            ignored_rows,
+            regularity,
+            weak,
+            moderate,
+            strong,
+            triangle,
+        }
+    }
+
+    pub fn run(request : &Request) -> Result<Response> {
+        let subjects : Vec<&Subject> = request.subjects.iter().map(|s| s.unpack()).collect();
+
+        // alternatives only ever get added as subjects are read,
+        // so the longest list must extend all others
+        let mut pooled = Subject {
+            name: String::new(),
+            alternatives: subjects.iter().map(|s| &s.alternatives).max_by_key(
+                |alternatives| alternatives.len()
+            ).cloned().unwrap_or_default(),
+            choices: Vec::new(),
+        };
+
+        for subject in &subjects {
+            if !pooled.alternatives.starts_with(&subject.alternatives) {
+                return Err(Error::DifferentAlternatives(subject.name.clone()));
+            }
+            pooled.choices.extend(subject.choices.iter().cloned());
+        }
+
+        Ok(Response {
+            subjects: subjects.into_iter().map(report).collect(),
+            pooled: Report {
+                subject_name: None,
+                ..report(&pooled)
+            },
+        })
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+        use rpc_common::ChoiceRow;
+
+        fn testsubj(name : &str, choices : Vec<ChoiceRow>) -> Packed<Subject> {
+            Packed(Subject{
+                name: String::from(name),
+                alternatives: (0..3).map(|s| s.to_string()).collect(),
+                choices,
+            })
+        }
+
+        #[test]
+        fn stochastic_axioms() {
+            // 0 is chosen more often from {0,1,2} than from {0,1} or {0,2}
+            // and the binary choices cycle 0 > 1 > 2 > 0
+            let mut choices = Vec::new();
+            for _ in 0..3 {
+                choices.extend(choices![[0,1] -> [0], [1,2] -> [1], [0,2] -> [2]]);
+            }
+            choices.extend(choices![
+                [0,1] -> [1], [1,2] -> [2], [0,2] -> [0],
+                [0,1,2] -> [0], [0,1,2] -> [], [0,1,2] -> [0,1]
+            ]);
+
+            // the same menus but consistent
+            let mut consistent = Vec::new();
+            for _ in 0..3 {
+                consistent.extend(choices![[0,1] -> [0], [1,2] -> [1], [0,2] -> [0]]);
+            }
+            consistent.extend(choices![[0,1] -> [1]]);
+
+            let response = run(&Request{subjects: vec![
+                testsubj("cyclic", choices),
+                testsubj("consistent", consistent),
+            ]}).unwrap();
+
+            let report = &response.subjects[0];
+            assert_eq!((report.observations, report.ignored_rows), (13, 2));
+            assert_eq!(report.regularity, vec![
+                RegularityViolation{
+                    alt: Alt(0),
+                    smaller: alts![0, 1],
+                    larger: alts![0, 1, 2],
+                    p_smaller: 0.75,
+                    p_larger: 1.0,
+                },
+                RegularityViolation{
+                    alt: Alt(0),
+                    smaller: alts![0, 2],
+                    larger: alts![0, 1, 2],
+                    p_smaller: 0.25,
+                    p_larger: 1.0,
+                },
+            ]);
+
+            // every rotation of the cycle violates all three transitivities
+            let rotations : Vec<(Alt, Alt, Alt)> = vec![
+                (Alt(0), Alt(1), Alt(2)), (Alt(1), Alt(2), Alt(0)), (Alt(2), Alt(0), Alt(1)),
+            ];
+            for violations in &[&report.weak, &report.moderate, &report.strong] {
+                assert_eq!(violations.iter().map(|v| v.alts).collect::<Vec<_>>(), rotations);
+            }
+
+            // 0.75 + 0.75 + 0.75 > 2
+            assert_eq!(report.triangle.iter().map(|v| v.alts).collect::<Vec<_>>(), vec![(Alt(0), Alt(1), Alt(2))]);
+
+            let report = &response.subjects[1];
+            assert!(report.regularity.is_empty() && report.weak.is_empty() && report.triangle.is_empty());
+            assert!(report.moderate.is_empty() && report.strong.is_empty());
+
+            // pooled, p(0,1) = 3/4, p(1,2) = 6/7 and p(0,2) = 4/7:
+            // weakly but not moderately transitive, and 3/4 + 6/7 - 4/7 > 1
+            let pooled = &response.pooled;
+            assert_eq!(pooled.subject_name, None);
+            assert_eq!(pooled.observations, 13 + 10);
+            assert_eq!(pooled.regularity.len(), 2);
+            assert!(pooled.weak.is_empty());
+            for violations in &[&pooled.moderate, &pooled.strong, &pooled.triangle] {
+                assert_eq!(violations.iter().map(|v| v.alts).collect::<Vec<_>>(), vec![(Alt(0), Alt(1), Alt(2))]);
+            }
+        }
+
+        #[test]
+        fn different_alternatives() {
+            let other = Packed(Subject{
+                name: String::from("other"),
+                alternatives: vec![String::from("x")],
+                choices: Vec::new(),
+            });
+
+            assert!(run(&Request{subjects: vec![testsubj("s", Vec::new()), other]}).is_err());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rpc_common::ChoiceRow;
+    use std::iter::FromIterator;
+
+    fn testsubj(alt_count : u32, choices : Vec<ChoiceRow>) -> Subject {
+        Subject{
+            name: String::from("subject"),
+            alternatives: (0..alt_count).map(|s| s.to_string()).collect(),
+            choices,
+        }
+    }
+
+    #[test]
+    fn luce_binary() {
+        // 0 beats 1 three times out of four, 1 beats 2 three times out of four
+        let mut choices = Vec::new();
+        for _ in 0..3 {
+            choices.extend(choices![[0,1] -> [0], [1,2] -> [1]]);
+        }
+        choices.extend(choices![[0,1] -> [1], [1,2] -> [2], [0,1] -> []]);
+
+        let response = run_one(&testsubj(3, choices), &[StochasticModel::Luce]).unwrap();
+        assert_eq!(response.observations, 8);
+        assert_eq!(response.ignored_rows, 1);
+
+        // the MLE is w0 : w1 : w2 = 9 : 3 : 1
+        let fit = &response.fits[0];
+        match fit.estimate {
+            Estimate::Luce(ref weights) => {
+                for (w, expected) in weights.iter().zip(&[9.0/13.0, 3.0/13.0, 1.0/13.0]) {
+                    assert!((w - expected).abs() < 1e-6, "{:?}", weights);
+                }
+            }
+            _ => panic!("wrong estimate"),
+        }
+
+        let expected_ll = 4.0 * (3.0 * (0.75f64).ln() + (0.25f64).ln()) / 2.0;
+        assert!((fit.log_likelihood - expected_ll).abs() < 1e-6);
+    }
+
+    #[test]
+    fn random_preference_mixture() {
+        // half of the choices follow 0 > 1 > 2, the other half 2 > 1 > 0
+        let mut choices = Vec::new();
+        for _ in 0..2 {
+            choices.extend(choices![
+                [0,1,2] -> [0], [0,1] -> [0], [1,2] -> [1], [0,2] -> [0],
+                [0,1,2] -> [2], [0,1] -> [1], [1,2] -> [2], [0,2] -> [2]
+            ]);
+        }
+
+        let response = run_one(&testsubj(3, choices), &[
+            StochasticModel::RandomPreference,
+            StochasticModel::Luce,
+        ]).unwrap();
+
+        // every observed choice has probability 1/2
+        let rp = &response.fits[0];
+        assert!((rp.log_likelihood - 16.0 * (0.5f64).ln()).abs() < 1e-6);
+        match rp.estimate {
+            Estimate::RandomPreference(ref orders) => {
+                let total : f64 = orders.iter().map(|&(_, p)| p).sum();
+                assert!((total - 1.0).abs() < 1e-6);
+
+                // the mixture is not identified; other orders may share the mass
+                assert!(orders.iter().all(|(o, _)| o.len() == 3));
+            }
+            _ => panic!("wrong estimate"),
+        }
+
+        // Luce can't do better than random preferences here
+        assert!(response.fits[1].log_likelihood <= rp.log_likelihood + 1e-6);
+    }
+
+    #[test]
+    fn too_many_alternatives() {
+        let subject = testsubj(MAX_ALTERNATIVES_RANDOM_PREFERENCE + 1, choices![[0,1] -> [0]]);
+        assert!(run_one(&subject, &[StochasticModel::Luce]).is_ok());
+        assert!(run_one(&subject, &[StochasticModel::RandomPreference]).is_err());
+    }
+}
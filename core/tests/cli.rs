@@ -0,0 +1,51 @@
+extern crate base64;
+
+use std::io::Write;
+use std::process::{Command,Stdio};
+use base64::prelude::BASE64_STANDARD;
+use base64::engine::Engine;
+
+// run the prest binary with the given arguments and CSV on stdin
+fn prest(args : &[&str], input : &str) -> (bool, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_prest"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    (output.status.success(), String::from_utf8(output.stdout).unwrap())
+}
+
+// w is introduced only after the first subject has been read
+const LATE_ALTERNATIVE : &str = "subject,menu,choice\nA,\"x,y\",x\nB,\"x,z\",z\nB,\"x,w\",w\n";
+
+#[test]
+fn copycat_with_late_alternative() {
+    let (success, output) = prest(&["simulate", "--menus", "copycat", "--format", "json"], LATE_ALTERNATIVE);
+    assert!(success);
+    assert_eq!(output.lines().count(), 3);
+    assert!(output.contains("\"menu\":\"x,w\""));
+}
+
+#[test]
+fn late_alternative() {
+    let (success, output) = prest(&["estimate", "-m", "preorder-maximization:strict,total", "--format", "json"], LATE_ALTERNATIVE);
+    assert!(success);
+
+    // the instances of the first subject cover all four alternatives,
+    // so that instance codes are comparable across subjects
+    let first = output.lines().next().unwrap();
+    let code = first.split("\"instance\":\"").nth(1).unwrap().split('"').next().unwrap();
+    let bytes = BASE64_STANDARD.decode(code).unwrap();
+    assert_eq!(bytes[1], 4);  // after the model tag comes the size of the preorder
+
+    for command in &["consistency", "axioms", "integrity", "summary"] {
+        let (success, output) = prest(&[command, "--format", "json"], LATE_ALTERNATIVE);
+        assert!(success, "prest {} failed", command);
+        assert_eq!(output.lines().count(), 2, "prest {}", command);
+    }
+}
@@ -91,6 +91,11 @@ def estimate(args):
         models=models,
         disable_parallelism=args.sequential,
         disregard_deferrals=args.disregard_deferrals,
+        diagnostics=args.diagnostics,
+        stochastic_models=[
+            {'luce': Luce(), 'random-preference': RandomPreference()}[name]
+            for name in args.stochastic_models
+        ],
     ))
     variant = dsm._get_export_variant(args.export_variant)
     dsm.export(args.fname_out, '*.csv', variant, MockWorker())
@@ -120,6 +125,9 @@ if __name__ == '__main__':
     apE.add_argument('-s', '--sequential', default=False, action='store_true', help='disable paralellism')
     apE.add_argument('-m', dest='models', metavar='MODEL', nargs='+', help='model(s)')
     apE.add_argument('--disregard-deferrals', default=False, action='store_true')
+    apE.add_argument('--diagnostics', default=False, action='store_true', help='report the observations missed by each instance')
+    apE.add_argument('--stochastic', dest='stochastic_models', metavar='MODEL', nargs='+', default=[],
+        choices=('luce', 'random-preference'), help='stochastic model(s) to fit as well')
 
     apC = sub.add_parser('consistency', help='general consistency')
     apC.add_argument('fname_in', metavar='input.csv')
@@ -7,19 +7,21 @@ import threading
 import subprocess
 import collections
 import typing
-from typing import Sequence, Any, Type, Optional, NamedTuple, Union, BinaryIO, cast, TypeVar
+from typing import Sequence, Any, Type, Optional, NamedTuple, Union, BinaryIO, cast, TypeVar, \
+    Callable, Tuple
 
 import model
 import platform_specific
 from util.tee import Tee
 
 from util.codec import Codec, CodecError, EOF, FileIn, FileOut, namedtupleC, \
-    strC, intC, frozensetC, listC, bytesC, tupleC, enumC
+    strC, intC, frozensetC, listC, bytesC, tupleC, enumC, maybe
 
 log = logging.getLogger(__name__)
 
 Req = TypeVar('Req')
 Resp = TypeVar('Resp')
+Part = TypeVar('Part')
 
 class CoreError(Exception):
     pass
@@ -47,11 +49,16 @@ class Log(NamedTuple):
     message : str
     tag : int = 3
 
+class Partial(NamedTuple):
+    data : bytes
+    tag : int = 4
+
 Message = Union[
     Progress,
     AnswerFollows,
     Error,
     Log,
+    Partial,
 ]
 
 MessageC = enumC('Message', {
@@ -59,8 +66,27 @@ MessageC = enumC('Message', {
     AnswerFollows: (),
     Error: (strC, bytesC),
     Log: (intC, strC),
+    Partial: (bytesC,),
 })
 
+# must match rpc::PROTOCOL_VERSION in the core
+PROTOCOL_VERSION = 7
+
+class Capabilities(NamedTuple):
+    protocol_version : int
+    core_version : str
+    tags : Sequence[str]
+    models : Sequence[Tuple[model.Model, int]]  # (model, max alternatives)
+    stochastic_models : Sequence[Tuple[int, Optional[int]]]  # (model tag, max alternatives)
+
+CapabilitiesC = namedtupleC(Capabilities,
+    intC,
+    strC,
+    listC(strC),
+    listC(tupleC(model.ModelC, intC)),
+    listC(tupleC(intC, maybe(intC))),
+)
+
 class Failure(CoreError):
     def __init__(self, message : str, error : bytes) -> None:
         CoreError.__init__(self, message)
@@ -131,6 +157,10 @@ class Core:
                     self.stdout = typing.cast(FileIn, self.core.stdout)
 
             self.stderr : FileIn = typing.cast(FileIn, self.core.stderr)
+
+            # the worker thread writes requests while the GUI thread
+            # may write cancellations and quits
+            self.stdin_lock = threading.Lock()
         except OSError:
             raise CoreError('could not run core')
 
@@ -139,6 +169,15 @@ class Core:
 
         log.debug('the core is running')
 
+        # recorded responses do not contain the handshake
+        self.capabilities : Optional[Capabilities] = None
+        if not f_mock:
+            try:
+                self.capabilities = self.hello()
+            except CoreError:
+                self.shutdown()
+                raise
+
     def __enter__(self) -> 'Core':
         return self
 
@@ -146,9 +185,21 @@ class Core:
         self.shutdown()
 
     def call(self, name : str, codec_req : Codec[Req], codec_resp : Codec[Resp], request : Req) -> Resp:
-        strC.encode(self.stdin, name)
-        codec_req.encode(self.stdin, request)
-        self.stdin.flush()
+        return self.call_streaming(name, codec_req, None, codec_resp, request)
+
+    def call_streaming(
+        self,
+        name : str,
+        codec_req : Codec[Req],
+        on_partial : Optional[tuple[Codec[Part], Callable[[Part], None]]],
+        codec_resp : Codec[Resp],
+        request : Req,
+        on_progress : Optional[Callable[[int], None]] = None,
+    ) -> Resp:
+        with self.stdin_lock:
+            strC.encode(self.stdin, name)
+            codec_req.encode(self.stdin, request)
+            self.stdin.flush()
 
         try:
             while True:
@@ -157,6 +208,15 @@ class Core:
 
                 if isinstance(msg, Progress):
                     log.debug('progress: %d' % msg.position)
+                    if on_progress:
+                        on_progress(msg.position)
+
+                elif isinstance(msg, Partial):
+                    if not on_partial:
+                        raise MalformedResponse('unexpected partial result')
+
+                    codec_part, callback = on_partial
+                    callback(codec_part.decode(cast(FileIn, io.BytesIO(msg.data))))
 
                 elif isinstance(msg, Log):
                     level = ['DEBUG', 'INFO', 'WARN', 'ERROR'][msg.level]
@@ -177,6 +237,30 @@ class Core:
         except CodecError as e:
             raise MalformedResponse('malformed response from core') from e
 
+    def cancel(self) -> None:
+        # may be called from another thread while call() is waiting for the answer;
+        # the running request then fails with a "cancelled" error
+        # if it checks for cancellation
+        with self.stdin_lock:
+            strC.encode(self.stdin, 'cancel')
+            self.stdin.flush()
+
+    def hello(self) -> Capabilities:
+        try:
+            capabilities = self.call('hello', intC, CapabilitiesC, PROTOCOL_VERSION)
+        except Failure as e:
+            raise CoreError(str(e)) from e
+        except CoreDeath as e:
+            # cores older than the handshake die on unknown requests
+            raise CoreError(
+                'the core does not speak protocol version %d; please install matching versions'
+                % PROTOCOL_VERSION
+            ) from e
+
+        log.debug('core version %s, protocol version %d'
+            % (capabilities.core_version, capabilities.protocol_version))
+        return capabilities
+
     def crash(self) -> None:
         self.call('crash', strC, strC, 'Crash test')
 
@@ -187,8 +271,13 @@ class Core:
         log.debug('core shutdown')
 
         try:
-            strC.encode(self.stdin, 'quit')
-            self.stdin.flush()
+            # stop the running request, if it checks for cancellation,
+            # so that the core reads the quit and exits without being killed
+            self.cancel()
+
+            with self.stdin_lock:
+                strC.encode(self.stdin, 'quit')
+                self.stdin.flush()
         except (OSError, BrokenPipeError):  # windows throws OSError
             log.debug('could not send quit, the core is probably dead already')
 
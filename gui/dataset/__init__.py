@@ -41,6 +41,13 @@ class ShowMessageBox(NamedTuple):
 
 AnalysisResult = Union[None, ShowMessageBox, 'Dataset']
 
+class PartialFailure(Exception):
+    # the analysis failed for some subjects
+    # but the result for the others is still worth showing
+    def __init__(self, message : str, result : 'Dataset') -> None:
+        Exception.__init__(self, message)
+        self.result = result
+
 class Analysis(NamedTuple):
     name : str
     config : Optional[Callable[[], Optional[Any]]]  # display config dialog, return config | can be None
@@ -152,6 +159,9 @@ class Dataset:
         except Cancelled:
             log.debug('analysis cancelled: {0}'.format(analysis.name))
             return None
+        except PartialFailure as e:
+            QMessageBox.warning(main_win, analysis.name, str(e))
+            return e.result
 
         if isinstance(result, ShowMessageBox):
             if result.type is MessageBoxType.INFORMATION:
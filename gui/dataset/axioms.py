@@ -0,0 +1,184 @@
+import logging
+from typing import NamedTuple, List, Sequence, Iterator, Tuple, Optional, Set, FrozenSet
+from PyQt5.QtWidgets import QDialog, QHeaderView
+
+import uic.view_dataset
+import util.tree_model
+from gui.progress import Worker
+from dataset import Dataset, DatasetHeaderC, Analysis, ExportVariant, PackedSubject, PackedSubjectC
+from util.codec import FileIn, FileOut, listC, strC, intC, \
+    tupleC, namedtupleC, setC, frozensetC, newtypeC
+from util.codec_progress import CodecProgress, listCP, oneCP
+
+log = logging.getLogger(__name__)
+
+# in the order of axioms::Axiom in the core
+AXIOMS = ('alpha', 'beta', 'congruence', 'nash_iia', 'desirability', 'deferral_monotonicity')
+AXIOM_NAMES = {
+    'alpha': "Sen's α",
+    'beta': "Sen's β",
+    'congruence': 'Congruence',
+    'nash_iia': "Nash's IIA",
+    'desirability': 'Desirability',
+    'deferral_monotonicity': 'Deferral monotonicity',
+}
+
+class Request(NamedTuple):
+    subject: PackedSubject
+
+RequestC = namedtupleC(Request, PackedSubjectC)
+
+MenuPair = Tuple[FrozenSet[int], FrozenSet[int]]  # (smaller, larger)
+
+class Row(NamedTuple):
+    axiom : str
+    menu_pairs : Set[MenuPair]  # violating the axiom
+
+AxiomC = newtypeC(intC, AXIOMS.__getitem__, AXIOMS.index)
+
+RowC = namedtupleC(Row, AxiomC, setC(tupleC(frozensetC(intC), frozensetC(intC))))
+
+class Subject(NamedTuple):
+    name : str
+    rows : List[Row]  # one for every axiom
+
+SubjectC = namedtupleC(Subject, strC, listC(RowC))
+
+def menu_str(alternatives : Sequence[str], menu : FrozenSet[int]) -> str:
+    return '{' + ','.join(alternatives[i] for i in sorted(menu)) + '}'
+
+class MenuPairNode(util.tree_model.Node):
+    def __init__(self, parent_node, row: int, alternatives : Sequence[str], pair : MenuPair) -> None:
+        smaller, larger = pair
+        util.tree_model.Node.__init__(
+            self, parent_node, row,
+            fields=('', '', '', menu_str(alternatives, smaller), menu_str(alternatives, larger)),
+        )
+
+class RowNode(util.tree_model.Node):
+    def __init__(self, parent_node, row: int, alternatives : Sequence[str], r: Row) -> None:
+        self.pairs = sorted(r.menu_pairs, key=lambda p: (sorted(p[0]), sorted(p[1])))
+        self.alternatives = alternatives
+
+        util.tree_model.Node.__init__(
+            self, parent_node, row,
+            fields=('', AXIOM_NAMES[r.axiom], len(self.pairs), '', ''),
+            child_count=len(self.pairs),
+        )
+
+    def create_child(self, row : int) -> MenuPairNode:
+        return MenuPairNode(self, row, self.alternatives, self.pairs[row])
+
+class SubjectNode(util.tree_model.Node):
+    def __init__(self, parent_node, row: int, alternatives : Sequence[str], subject: Subject) -> None:
+        self.subject = subject
+        self.alternatives = alternatives
+
+        util.tree_model.Node.__init__(
+            self, parent_node, row,
+            fields=(subject.name, '', sum(len(r.menu_pairs) for r in subject.rows), '', ''),
+            child_count=len(subject.rows),
+        )
+
+    def create_child(self, row: int) -> RowNode:
+        return RowNode(self, row, self.alternatives, self.subject.rows[row])
+
+class RootNode(util.tree_model.RootNode):
+    def __init__(self, alternatives : Sequence[str], subjects : List[Subject]) -> None:
+        util.tree_model.RootNode.__init__(self, len(subjects))
+        self.subjects = subjects
+        self.alternatives = alternatives
+
+    def create_child(self, row: int) -> SubjectNode:
+        return SubjectNode(self, row, self.alternatives, self.subjects[row])
+
+class AxiomViolations(Dataset):
+    class ViewDialog(QDialog, uic.view_dataset.Ui_ViewDataset):
+        def __init__(self, ds : 'AxiomViolations') -> None:
+            QDialog.__init__(self)
+            self.setupUi(self)
+
+            self.ds = ds
+            self.model = util.tree_model.TreeModel(
+                RootNode(ds.alternatives, ds.subjects),
+                headers=(
+                    'Subject',
+                    'Axiom',
+                    'Violations',
+                    'Smaller menu',
+                    'Larger menu',
+                ),
+            )
+            self.twRows.setModel(self.model)
+
+            self.twRows.header().setSectionResizeMode(QHeaderView.ResizeToContents)
+            self.twRows.header().setStretchLastSection(False)
+
+    def __init__(self, name : str, alternatives : Sequence[str] = ()) -> None:
+        Dataset.__init__(self, name, alternatives)
+        self.subjects : List[Subject] = []
+
+    def label_size(self) -> str:
+        return '%d subjects' % len(self.subjects)
+
+    def get_analyses(self) -> Sequence[Analysis]:
+        return ()
+
+    def get_export_variants(self) -> Sequence[ExportVariant]:
+        return (
+            ExportVariant(
+                name='Summary',
+                column_names=('subject',) + AXIOMS,
+                get_rows=self.export_summary,
+                size=len(self.subjects),
+            ),
+            ExportVariant(
+                name='Detailed',
+                column_names=(
+                    'subject',
+                    'axiom',
+                    'smaller_menu',
+                    'larger_menu',
+                ),
+                get_rows=self.export_detailed,
+                size=len(self.subjects),
+            ),
+        )
+
+    def export_summary(self) -> Iterator[Optional[Tuple[object, ...]]]:
+        for subject in self.subjects:
+            counts = {row.axiom: len(row.menu_pairs) for row in subject.rows}
+            yield (subject.name, *(counts[axiom] for axiom in AXIOMS))
+            yield None  # bump progress
+
+    def export_detailed(self) -> Iterator[Optional[Tuple[str,str,str,str]]]:
+        for subject in self.subjects:
+            for row in subject.rows:
+                for smaller, larger in sorted(row.menu_pairs, key=lambda p: (sorted(p[0]), sorted(p[1]))):
+                    yield (
+                        subject.name,
+                        row.axiom,
+                        menu_str(self.alternatives, smaller),
+                        menu_str(self.alternatives, larger),
+                    )
+
+            yield None  # bump progress
+
+    @classmethod
+    def get_codec_progress(_cls) -> CodecProgress['AxiomViolations']:
+        DatasetHeaderC_encode, DatasetHeaderC_decode = DatasetHeaderC.enc_dec()
+        subjects_size, subjects_encode, subjects_decode = listCP(oneCP(SubjectC)).enc_dec()
+
+        def get_size(x : 'AxiomViolations') -> int:
+            return subjects_size(x.subjects)
+
+        def encode(worker : Worker, f : FileOut, x : 'AxiomViolations') -> None:
+            DatasetHeaderC_encode(f, (x.name, x.alternatives))
+            subjects_encode(worker, f, x.subjects)
+
+        def decode(worker : Worker, f : FileIn) -> 'AxiomViolations':
+            ds = AxiomViolations(*DatasetHeaderC_decode(f))
+            ds.subjects = subjects_decode(worker, f)
+            return ds
+
+        return CodecProgress(get_size, encode, decode)
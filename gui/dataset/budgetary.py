@@ -10,7 +10,7 @@ from dataset import Dataset, Analysis, ExportVariant, DatasetHeaderC
 from dataset.budgetary_consistency import BudgetaryConsistency
 from typing import Sequence, NamedTuple, List, Dict, Tuple, Iterator, Union, Optional, cast
 from gui.progress import Worker, Cancelled
-from util.codec import FileOut, FileIn, namedtupleC, strC, numpyC, listC
+from util.codec import FileOut, FileIn, namedtupleC, strC, numpyC, listC, intC, maybe
 from util.codec_progress import CodecProgress, listCP, oneCP
 from PyQt5.QtWidgets import QDialog, QTreeWidgetItem, QHeaderView
 
@@ -21,6 +21,12 @@ class Subject(NamedTuple):
 
 SubjectC = namedtupleC(Subject, strC, numpyC(np.float32), numpyC(np.float32))
 
+class ConsistencyRequest(NamedTuple):
+    subject : Subject
+    cycle_limit : Optional[int]  # count HARP/quasilinear violations by cycle length, at most this many cycles
+
+ConsistencyRequestC = namedtupleC(ConsistencyRequest, SubjectC, maybe(intC))
+
 class RowNode(util.tree_model.Node):
     def __init__(self, parent_node, row: int, prices: np.ndarray, amounts: np.ndarray) -> None:
         util.tree_model.Node.__init__(
@@ -81,7 +87,7 @@ class Budgetary(Dataset):
 
     def analysis_consistency(self, worker : Worker, _config : None) -> BudgetaryConsistency:
         with Core() as core:
-            worker.interrupt = lambda: core.shutdown()  # interrupt hook
+            worker.interrupt = lambda: core.cancel()  # interrupt hook
 
             rows = []
 
@@ -89,9 +95,10 @@ class Budgetary(Dataset):
             for i, subject in enumerate(self.subjects):
                 response = core.call(
                     'budgetary-consistency',
-                    SubjectC,
+                    ConsistencyRequestC,
                     dataset.budgetary_consistency.SubjectC,
-                    subject
+                    # enumerating the cycles is exponential in the number of observations
+                    ConsistencyRequest(subject, cycle_limit=None),
                 )
                 rows.append(response)
 
@@ -6,23 +6,20 @@ import platform_specific
 from dataset import Dataset, Analysis, ExportVariant, DatasetHeaderC
 from typing import Sequence, NamedTuple, List, Iterator, Tuple, cast
 from gui.progress import Worker, Cancelled
-from util.codec import namedtupleC, strC, intC, listC, tupleC, FileIn, FileOut
+from util.codec import namedtupleC, strC, intC, boolC, listC, tupleC, doubleC, FileIn, FileOut
 from util.codec_progress import CodecProgress, listCP, oneCP
 
 from PyQt5.QtGui import QIcon
 from PyQt5.QtWidgets import QDialog, QTreeWidgetItem, QHeaderView
 
-class BoundEstimate(NamedTuple):
-    lower : int
-    upper : int
+class HoutmanMaks(NamedTuple):
+    count : int
+    removed : List[int]  # observations, zero-based
 
-    def to_str(self) -> str:
-        if self.lower == self.upper:
-            return str(self.lower)
-        else:
-            return f'{self.lower} ≤ x ≤ {self.upper}'
+    def removed_str(self) -> str:
+        return ' '.join(str(i+1) for i in self.removed)
 
-BoundEstimateC = namedtupleC(BoundEstimate, intC, intC)
+HoutmanMaksC = namedtupleC(HoutmanMaks, intC, listC(intC))
 
 class Violations(NamedTuple):
     garp : int
@@ -30,19 +27,39 @@ class Violations(NamedTuple):
 
 ViolationsC = namedtupleC(Violations, intC, intC)
 
+class CyclicalTest(NamedTuple):
+    passed : bool
+    violations : List[Tuple[int, int]]  # cycle length, count; only with a cycle limit
+    truncated : bool
+
+    def verdict(self) -> str:
+        return 'pass' if self.passed else 'fail'
+
+CyclicalTestC = namedtupleC(CyclicalTest, boolC, listC(tupleC(intC, intC)), boolC)
+
 class Subject(NamedTuple):
     subject_name : str
     violations : List[Tuple[int, Violations]]  # cycle length, counts
     warp_strict : int
     warp_nonstrict : int
-    hm_garp : BoundEstimate
-    hm_sarp : BoundEstimate
-    hm_warp_strict : BoundEstimate
-    hm_warp_nonstrict : BoundEstimate
+    hm_garp : HoutmanMaks
+    hm_sarp : HoutmanMaks
+    hm_warp_strict : HoutmanMaks
+    hm_warp_nonstrict : HoutmanMaks
+    ccei : float
+    varian : List[float]  # for each observation
+    mpi_mean : float
+    mpi_median : float
+    mci : float
+    harp : CyclicalTest
+    quasilinear : CyclicalTest
 
 SubjectC = namedtupleC(Subject, strC, listC(tupleC(intC, ViolationsC)), intC, intC,
-    BoundEstimateC, BoundEstimateC,
-    BoundEstimateC, BoundEstimateC,
+    HoutmanMaksC, HoutmanMaksC,
+    HoutmanMaksC, HoutmanMaksC,
+    doubleC, listC(doubleC),
+    doubleC, doubleC, doubleC,
+    CyclicalTestC, CyclicalTestC,
 )
 
 class ViolationsNode(util.tree_model.Node):
@@ -61,6 +78,12 @@ class ViolationsNode(util.tree_model.Node):
                 '-',
                 '-',
                 '-',
+                '-',
+                '-',
+                '-',
+                '-',
+                '-',
+                '-',
             ),
         )
 
@@ -76,10 +99,16 @@ class SubjectNode(util.tree_model.Node):
                 sum(v.sarp for _,v in subject.violations),
                 subject.warp_strict,
                 subject.warp_nonstrict,
-                subject.hm_garp.to_str(),
-                subject.hm_sarp.to_str(),
-                subject.hm_warp_strict.to_str(),
-                subject.hm_warp_nonstrict.to_str(),
+                subject.hm_garp.count,
+                subject.hm_sarp.count,
+                subject.hm_warp_strict.count,
+                subject.hm_warp_nonstrict.count,
+                '%.4f' % subject.ccei,
+                '%.4f' % subject.mpi_mean,
+                '%.4f' % subject.mpi_median,
+                '%.4f' % subject.mci,
+                subject.harp.verdict(),
+                subject.quasilinear.verdict(),
             ),
             child_count=len(subject.violations),
         )
@@ -129,6 +158,18 @@ class BudgetaryConsistency(Dataset):
                         'consistency/cons_budgetary.html#houtman-maks-index-hm'),
                     F('HM (WARP non-strict)', help_icon,
                         'consistency/cons_budgetary.html#houtman-maks-index-hm'),
+                    F('CCEI', help_icon,
+                        'consistency/cons_budgetary.html#critical-cost-efficiency-index-ccei'),
+                    F('MPI (mean)', help_icon,
+                        'consistency/cons_budgetary.html#money-pump-index-mpi'),
+                    F('MPI (median)', help_icon,
+                        'consistency/cons_budgetary.html#money-pump-index-mpi'),
+                    F('MCI', help_icon,
+                        'consistency/cons_budgetary.html#minimum-cost-index-mci'),
+                    F('HARP', help_icon,
+                        'consistency/cons_budgetary.html#homothetic-axiom-of-revealed-preference-harp'),
+                    F('Quasilinear', help_icon,
+                        'consistency/cons_budgetary.html#quasilinear-rationalisability'),
                 ],
             )
 
@@ -186,9 +227,8 @@ class BudgetaryConsistency(Dataset):
             ExportVariant(
                 name='Summary',
                 column_names=('subject', 'garp', 'sarp', 'warp_strict', 'warp_nonstrict',
-                    'hm_garp_lower', 'hm_garp_upper', 'hm_sarp_lower', 'hm_sarp_upper',
-                    'hm_warp_strict_lower', 'hm_warp_strict_upper',
-                    'hm_warp_nonstrict_lower', 'hm_warp_nonstrict_upper',
+                    'hm_garp', 'hm_sarp', 'hm_warp_strict', 'hm_warp_nonstrict',
+                    'ccei', 'mpi_mean', 'mpi_median', 'mci', 'harp', 'quasilinear',
                 ),
                 get_rows=self.export_summary,
                 size=len(self.subjects),
@@ -199,9 +239,21 @@ class BudgetaryConsistency(Dataset):
                 get_rows=self.export_breakdown,
                 size=len(self.subjects),
             ),
+            ExportVariant(
+                name='Houtman-Maks removed observations',
+                column_names=('subject', 'hm_garp', 'hm_sarp', 'hm_warp_strict', 'hm_warp_nonstrict'),
+                get_rows=self.export_hm_removed,
+                size=len(self.subjects),
+            ),
+            ExportVariant(
+                name='Varian efficiency by observation',
+                column_names=('subject', 'observation', 'efficiency'),
+                get_rows=self.export_varian,
+                size=len(self.subjects),
+            ),
         ]
 
-    def export_summary(self) -> Iterator[Tuple[str, int, int, int, int, int, int, int, int, int, int, int, int]]:
+    def export_summary(self) -> Iterator[Tuple[str, int, int, int, int, int, int, int, int, float, float, float, float, str, str]]:
         for subject in self.subjects:
             yield (
                 subject.subject_name,
@@ -209,14 +261,16 @@ class BudgetaryConsistency(Dataset):
                 sum(v.sarp for _l, v in subject.violations),
                 subject.warp_strict,
                 subject.warp_nonstrict,
-                subject.hm_garp.lower,
-                subject.hm_garp.upper,
-                subject.hm_sarp.lower,
-                subject.hm_sarp.upper,
-                subject.hm_warp_strict.lower,
-                subject.hm_warp_strict.upper,
-                subject.hm_warp_nonstrict.lower,
-                subject.hm_warp_nonstrict.upper,
+                subject.hm_garp.count,
+                subject.hm_sarp.count,
+                subject.hm_warp_strict.count,
+                subject.hm_warp_nonstrict.count,
+                subject.ccei,
+                subject.mpi_mean,
+                subject.mpi_median,
+                subject.mci,
+                subject.harp.verdict(),
+                subject.quasilinear.verdict(),
             )
 
     def export_breakdown(self) -> Iterator[Tuple[str, int, int, int]]:
@@ -228,3 +282,22 @@ class BudgetaryConsistency(Dataset):
                     violation.garp,
                     violation.sarp,
                 )
+
+    def export_hm_removed(self) -> Iterator[Tuple[str, str, str, str, str]]:
+        for subject in self.subjects:
+            yield (
+                subject.subject_name,
+                subject.hm_garp.removed_str(),
+                subject.hm_sarp.removed_str(),
+                subject.hm_warp_strict.removed_str(),
+                subject.hm_warp_nonstrict.removed_str(),
+            )
+
+    def export_varian(self) -> Iterator[Tuple[str, int, float]]:
+        for subject in self.subjects:
+            for observation, efficiency in enumerate(subject.varian, start=1):
+                yield (
+                    subject.subject_name,
+                    observation,
+                    efficiency,
+                )
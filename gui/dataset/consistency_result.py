@@ -13,13 +13,47 @@ import uic.view_dataset
 import util.tree_model
 import platform_specific
 from gui.progress import Worker
-from dataset import Dataset, DatasetHeaderC, ExportVariant, Analysis
+from dataset import Dataset, DatasetHeaderC, ExportVariant, Analysis, PackedSubject, PackedSubjectC
 from util.codec import Codec, FileIn, FileOut, namedtupleC, strC, intC, \
-    frozensetC, listC, bytesC, tupleC, dictC, setC
+    frozensetC, listC, bytesC, tupleC, dictC, setC, boolC, maybe
 from util.codec_progress import CodecProgress, listCP, oneCP
 
 log = logging.getLogger(__name__)
 
+# cycles are enumerated up to this many per subject and graph;
+# the pass/fail verdicts do not depend on it
+CYCLE_LIMIT = 100000
+
+class Request(NamedTuple):
+    subject: PackedSubject
+    cycle_limit: Optional[int]
+
+RequestC = namedtupleC(Request, PackedSubjectC, maybe(intC))
+
+class Verdict(NamedTuple):
+    garp: bool
+    sarp: bool
+    garp_binary_menus: bool
+    sarp_binary_menus: bool
+
+VerdictC = namedtupleC(Verdict, boolC, boolC, boolC, boolC)
+
+class HoutmanMaks(NamedTuple):
+    count: int
+    removed: List[int]  # choice rows, zero-based
+    exact: bool  # otherwise count is an upper bound
+
+    def removed_str(self) -> str:
+        return ' '.join(str(i+1) for i in self.removed)
+
+HoutmanMaksC = namedtupleC(HoutmanMaks, intC, listC(intC), boolC)
+
+class Component(NamedTuple):
+    alternatives: FrozenSet[int]
+    strict: bool
+
+ComponentC = namedtupleC(Component, frozensetC(intC), boolC)
+
 class Row(NamedTuple):
     cycle_length: int
     garp: int
@@ -31,11 +65,17 @@ RowC = namedtupleC(Row, intC, intC, intC, intC, intC)
 
 class SubjectRaw(NamedTuple):
     name: str
-    rows: List[Row]
+    verdict: Verdict
+    hm_garp: HoutmanMaks
+    hm_sarp: HoutmanMaks
+    components: List[Component]
     warp_pairs: int
     warp_all: int
+    rows: List[Row]
+    truncated: bool
 
-SubjectRawC = namedtupleC(SubjectRaw, strC, listC(RowC), intC, intC)
+SubjectRawC = namedtupleC(SubjectRaw, strC, VerdictC, HoutmanMaksC, HoutmanMaksC,
+    listC(ComponentC), intC, intC, listC(RowC), boolC)
 
 class Subject(NamedTuple):
     raw: SubjectRaw
@@ -77,7 +117,8 @@ class SubjectNode(util.tree_model.Node):
                     '',
                     subject.raw.warp_pairs,
                     subject.raw.warp_all,
-                    0, 0, 0, 0),
+                    0, 0, 0, 0,
+                    'yes' if subject.raw.truncated else ''),
             )
         elif len(subject.raw.rows) == 1:
             util.tree_model.Node.__init__(
@@ -92,6 +133,7 @@ class SubjectNode(util.tree_model.Node):
                     subject.total_sarp,
                     subject.total_garp_binary_menus,
                     subject.total_sarp_binary_menus,
+                    'yes' if subject.raw.truncated else '',
                 )
             )
         else:
@@ -107,6 +149,7 @@ class SubjectNode(util.tree_model.Node):
                     subject.total_sarp,
                     subject.total_garp_binary_menus,
                     subject.total_sarp_binary_menus,
+                    'yes' if subject.raw.truncated else '',
                 ),
                 child_count = len(subject.raw.rows),
             )
@@ -127,6 +170,7 @@ class RowNode(util.tree_model.Node):
                 row.sarp,
                 row.garp_binary_menus,
                 row.sarp_binary_menus,
+                '',
             )
         )
 
@@ -162,6 +206,7 @@ class ConsistencyResult(Dataset):
                         'consistency/cons_general.html#binary-choice-consistency'),
                     F('Strict binary cycles', help_icon,
                         'consistency/cons_general.html#strict-binary-choice-consistency'),  # SARP-binary
+                    'Cycles truncated',
                 ),
             )
             self.twRows.setModel(self.model)
@@ -211,6 +256,34 @@ class ConsistencyResult(Dataset):
                 get_rows=self.export_summary,
                 size=len(self.subjects),
             ),
+            ExportVariant(
+                name='Verdicts',
+                column_names=(
+                    'subject',
+                    'congruence',
+                    'strict_general_cycles',
+                    'binary_cycles',
+                    'strict_binary_cycles',
+                    'inconsistent_components',
+                    'cycles_truncated',
+                ),
+                get_rows=self.export_verdicts,
+                size=len(self.subjects),
+            ),
+            ExportVariant(
+                name='Houtman-Maks removed choice rows',
+                column_names=(
+                    'subject',
+                    'hm_congruence',
+                    'hm_congruence_exact',
+                    'hm_congruence_removed',
+                    'hm_strict_general_cycles',
+                    'hm_strict_general_cycles_exact',
+                    'hm_strict_general_cycles_removed',
+                ),
+                get_rows=self.export_hm,
+                size=len(self.subjects),
+            ),
             ExportVariant(
                 name='WARP violations',
                 column_names=['subject', 'warp_pairs', 'warp_all'],
@@ -246,6 +319,36 @@ class ConsistencyResult(Dataset):
     def label_size(self) -> str:
         return '%d subjects' % len(self.subjects)
 
+    def export_verdicts(self) -> Iterator[Optional[Tuple[str,str,str,str,str,int,str]]]:
+        def verdict(consistent : bool) -> str:
+            return 'pass' if consistent else 'fail'
+
+        for subject in self.subjects:
+            v = subject.raw.verdict
+            yield (
+                subject.raw.name,
+                verdict(v.garp),
+                verdict(v.sarp),
+                verdict(v.garp_binary_menus),
+                verdict(v.sarp_binary_menus),
+                sum(1 for c in subject.raw.components if c.strict),
+                'yes' if subject.raw.truncated else 'no',
+            )
+            yield None  # bump progress
+
+    def export_hm(self) -> Iterator[Optional[Tuple[str,int,str,str,int,str,str]]]:
+        def exact(hm : HoutmanMaks) -> str:
+            return 'yes' if hm.exact else 'upper bound'
+
+        for subject in self.subjects:
+            hm_garp, hm_sarp = subject.raw.hm_garp, subject.raw.hm_sarp
+            yield (
+                subject.raw.name,
+                hm_garp.count, exact(hm_garp), hm_garp.removed_str(),
+                hm_sarp.count, exact(hm_sarp), hm_sarp.removed_str(),
+            )
+            yield None  # bump progress
+
     def export_warp(self) -> Iterator[Optional[Tuple[str, int, int]]]:
         for subject in self.subjects:
             yield (subject.raw.name, subject.raw.warp_pairs, subject.raw.warp_all)
@@ -1,9 +1,10 @@
 import json
+import functools
 import collections
 import hashlib
 import base64
 from typing import NamedTuple, Sequence, List, Iterator, Tuple, Dict, \
-    Optional, Any, Union, NewType, cast, Callable
+    Optional, Any, Union, NewType, cast, Callable, FrozenSet
 
 from PyQt5.QtGui import QIcon, QCursor
 from PyQt5.QtCore import Qt
@@ -20,12 +21,13 @@ from core import Core
 from gui.progress import Worker
 from model import get_name as model_get_name
 from model import Model as ModelRepr
-from model import ModelC
+from model import ModelC, StochasticModelC
+from model import StochasticModel, STOCHASTIC_NAMES
 from model import get_ordering_key as model_get_ordering_key
 from dataset import Dataset, DatasetHeaderC, ExportVariant, Analysis
 from util.tree_model import Node, TreeModel, Field, PackedRootNode
 from util.codec import Codec, FileIn, FileOut, namedtupleC, strC, intC, \
-    frozensetC, listC, bytesC, tupleC, boolC
+    frozensetC, listC, bytesC, tupleC, boolC, dictC, doubleC, enumC, EOF
 from util.codec_progress import CodecProgress, listCP, oneCP
 import uic.view_estimated
 
@@ -50,8 +52,11 @@ class Request(NamedTuple):
     models : Sequence[model.Model]
     disable_parallelism : bool
     disregard_deferrals : bool
+    diagnostics : bool
+    stochastic_models : Sequence[StochasticModel]
 
-RequestC = namedtupleC(Request, listC(dataset.PackedSubjectC), listC(ModelC), boolC, boolC)
+RequestC = namedtupleC(Request, listC(dataset.PackedSubjectC), listC(ModelC), boolC, boolC, boolC,
+    listC(StochasticModelC))
 
 InstanceRepr = NewType('InstanceRepr', bytes)
 InstanceReprC = bytesC
@@ -63,18 +68,76 @@ class InstanceInfo(NamedTuple):
 
 InstanceInfoC = namedtupleC(InstanceInfo, ModelC, PenaltyC, InstanceReprC)
 
+class MissedRow(NamedTuple):
+    row : int  # index of the observation in the subject
+    predicted : FrozenSet[int]
+
+MissedRowC = namedtupleC(MissedRow, intC, frozensetC(intC))
+
+class LuceEstimate(NamedTuple):
+    weights : List[float]  # indexed by alternatives
+    tag : int = 0
+
+class RandomPreferenceEstimate(NamedTuple):
+    orders : List[Tuple[List[int], float]]  # best first, with probabilities
+    tag : int = 1
+
+StochasticEstimate = Union[LuceEstimate, RandomPreferenceEstimate]
+
+StochasticEstimateC = enumC('StochasticEstimate', {
+    LuceEstimate: (listC(doubleC),),
+    RandomPreferenceEstimate: (listC(tupleC(listC(intC), doubleC)),),
+})
+
+class StochasticFit(NamedTuple):
+    estimate : StochasticEstimate
+    log_likelihood : float
+    iterations : int
+
+    @property
+    def model(self) -> StochasticModel:
+        if isinstance(self.estimate, LuceEstimate):
+            return model.Luce()
+        else:
+            return model.RandomPreference()
+
+StochasticFitC = namedtupleC(StochasticFit, StochasticEstimateC, doubleC, intC)
+
 class Response(NamedTuple):
     subject_name : str
     penalty : Penalty
     best_instances : List[InstanceInfo]
+    diagnostics : List[List[MissedRow]]  # for each best instance, if requested
+    stochastic_fits : List[StochasticFit]  # one for each requested stochastic model
+
+ResponseC = namedtupleC(Response, strC, PenaltyC, listC(InstanceInfoC), listC(listC(MissedRowC)),
+    listC(StochasticFitC))
+
+# responses saved before the stochastic fits were introduced
+DiagnosticsResponseC = tupleC(strC, PenaltyC, listC(InstanceInfoC), listC(listC(MissedRowC)))
+
+# responses saved before the diagnostics were introduced
+LegacyResponseC = tupleC(strC, PenaltyC, listC(InstanceInfoC))
 
-ResponseC = namedtupleC(Response, strC, PenaltyC, listC(InstanceInfoC))
 ResponsesC = listC(ResponseC)
 
 PackedResponse = NewType('PackedResponse', bytes)
 PackedResponseC = cast(Codec[PackedResponse], bytesC)
 PackedResponsesC = listC(PackedResponseC)
 
+def decode_response(response_bytes : PackedResponse) -> Response:
+    try:
+        return ResponseC.decode_from_memory(response_bytes)
+    except EOF:
+        pass
+
+    try:
+        subject_name, penalty, best_instances, diagnostics = DiagnosticsResponseC.decode_from_memory(response_bytes)
+        return Response(subject_name, penalty, best_instances, diagnostics, stochastic_fits=[])
+    except EOF:
+        subject_name, penalty, best_instances = LegacyResponseC.decode_from_memory(response_bytes)
+        return Response(subject_name, penalty, best_instances, diagnostics=[], stochastic_fits=[])
+
 class InstVizRequest(NamedTuple):
     instance_code : str
 
@@ -102,8 +165,14 @@ class Subject(NamedTuple):
     name: str
     penalty: Penalty
     best_models: List[Tuple[model.Model, Penalty, List[InstanceRepr]]]
+    missed: Dict[InstanceRepr, List[MissedRow]]  # empty if diagnostics were not requested
+    stochastic_fits: List[StochasticFit]
 
-SubjectC = namedtupleC(Subject, strC, PenaltyC, listC(tupleC(ModelC, PenaltyC, listC(InstanceReprC))))
+SubjectC = namedtupleC(Subject, strC, PenaltyC,
+    listC(tupleC(ModelC, PenaltyC, listC(InstanceReprC))),
+    dictC(InstanceReprC, listC(MissedRowC)),
+    listC(StochasticFitC),
+)
 
 PackedSubject = NewType('PackedSubject', bytes)
 PackedSubjectC = cast(Codec[PackedSubject], bytesC)
@@ -118,7 +187,7 @@ def subject_from_response_bytes(response_bytes : PackedResponse) -> Subject:
             len(model_get_name(model)),
         )
 
-    subject_name, subject_penalty, best_instances = ResponseC.decode_from_memory(response_bytes)
+    subject_name, subject_penalty, best_instances, diagnostics, stochastic_fits = decode_response(response_bytes)
 
     by_model: Dict[model.Model, Tuple[Penalty, List[InstanceRepr]]] = {}
     for model, inst_penalty, instance in best_instances:
@@ -138,6 +207,11 @@ def subject_from_response_bytes(response_bytes : PackedResponse) -> Subject:
             for model, (penalty, instances)
             in sorted(by_model.items(), key=model_sort_criterion)
         ],
+        missed={
+            info.instance: missed
+            for info, missed in zip(best_instances, diagnostics)
+        },
+        stochastic_fits=stochastic_fits,
     )
 
 @dataclass
@@ -156,19 +230,55 @@ class RenderedInstance:
     graphs : list[RenderedGraph]
     extra_info : list[tuple[str, str]]
 
+def format_order(alternatives : Sequence[str], order : List[int]) -> str:
+    return ' > '.join(alternatives[i] for i in order)
+
 class EstimationResult(Dataset):
     class Subject(Node):
-        def __init__(self, parent_node, row: int, subject: Subject) -> None:
+        def __init__(self, parent_node, row: int, subject: Subject, alternatives: Sequence[str]) -> None:
             Node.__init__(
                 self, parent_node, row,
                 fields=(subject.name, str(subject.penalty), '%d models' % len(subject.best_models)),
-                child_count=len(subject.best_models),
+                child_count=len(subject.best_models) + len(subject.stochastic_fits),
             )
             self.subject = subject
+            self.alternatives = alternatives
+
+        def create_child(self, row: int) -> Node:
+            if row < len(self.subject.best_models):
+                model, penalty, instances = self.subject.best_models[row]
+                return EstimationResult.Model(self, row, model, penalty, instances)
+
+            # the stochastic fits come after the deterministic models
+            fit = self.subject.stochastic_fits[row - len(self.subject.best_models)]
+            return EstimationResult.StochasticFit(self, row, fit)
+
+    class StochasticFit(Node):
+        def __init__(self, parent_node: 'EstimationResult.Subject', row: int, fit: StochasticFit) -> None:
+            alternatives = parent_node.alternatives
+            if isinstance(fit.estimate, LuceEstimate):
+                rows = [
+                    (alternatives[i], '%.4f' % weight)
+                    for i, weight in enumerate(fit.estimate.weights)
+                ]
+                size = '%d weights' % len(rows)
+            else:
+                rows = [
+                    (format_order(alternatives, order), '%.4f' % probability)
+                    for order, probability in fit.estimate.orders
+                ]
+                size = '%d orders' % len(rows)
 
-        def create_child(self, row: int) -> 'EstimationResult.Model':
-            model, penalty, instances = self.subject.best_models[row]
-            return EstimationResult.Model(self, row, model, penalty, instances)
+            Node.__init__(
+                self, parent_node, row,
+                fields=(STOCHASTIC_NAMES[fit.model], 'log-likelihood %.4f' % fit.log_likelihood, size),
+                child_count=len(rows),
+            )
+            self.rows = rows
+
+        def create_child(self, row: int) -> Node:
+            label, value = self.rows[row]
+            return Node(self, row, fields=(label, value, ''))
 
     class Model(Node):
         def __init__(self, parent_node: 'EstimationResult.Subject', row: int,
@@ -191,9 +301,16 @@ class EstimationResult(Dataset):
             code = base64.b64encode(instance).decode('ascii')
             subject = parent_node.subject
             help_icon = QIcon(platform_specific.get_embedded_file_path('images/qm-16.png'))
+
+            # observations are numbered from 1 within the subject
+            missed = subject.missed.get(instance)
+            missed_str = '' if missed is None else 'missed: ' + (
+                ', '.join(str(m.row + 1) for m in missed) or 'none'
+            )
+
             Node.__init__(
                 self, parent_node, row,
-                fields=(code, Field(icon=help_icon, user_data=code), ''),
+                fields=(code, Field(icon=help_icon, user_data=code), missed_str),
                 #fields=(code, '', ''),
             )
 
@@ -205,7 +322,7 @@ class EstimationResult(Dataset):
             self.alternatives = ds.alternatives
             self.model = TreeModel(
                 PackedRootNode(
-                    EstimationResult.Subject,
+                    cast(type, functools.partial(EstimationResult.Subject, alternatives=ds.alternatives)),
                     cast(Callable[[bytes], Any], subject_from_response_bytes),
                     'Subject',
                     ds.subjects
@@ -362,8 +479,61 @@ class EstimationResult(Dataset):
                 get_rows=self.export_detailed,
                 size=len(self.subjects),
             ),
+            ExportVariant(
+                name='Missed observations (machine-friendly)',
+                column_names=('subject', 'model', 'instance', 'observation', 'predicted_choice'),
+                get_rows=self.export_missed,
+                size=len(self.subjects),
+            ),
+            ExportVariant(
+                name='Stochastic fits (machine-friendly)',
+                column_names=('subject', 'model', 'log_likelihood', 'parameter', 'value'),
+                get_rows=self.export_stochastic,
+                size=len(self.subjects),
+            ),
         )
 
+    def export_stochastic(self) -> Iterator[Optional[Tuple[str,str,float,str,float]]]:
+        for subject in map(subject_from_response_bytes, self.subjects):
+            for fit in subject.stochastic_fits:
+                # Luce weights by alternative, random preferences by order
+                if isinstance(fit.estimate, LuceEstimate):
+                    parameters = [
+                        (self.alternatives[i], weight)
+                        for i, weight in enumerate(fit.estimate.weights)
+                    ]
+                else:
+                    parameters = [
+                        (format_order(self.alternatives, order), probability)
+                        for order, probability in fit.estimate.orders
+                    ]
+
+                for parameter, value in parameters:
+                    yield (
+                        subject.name,
+                        STOCHASTIC_NAMES[fit.model],
+                        fit.log_likelihood,
+                        parameter,
+                        value,
+                    )
+
+            yield None  # bump progress
+
+    def export_missed(self) -> Iterator[Optional[Tuple[str,str,str,int,str]]]:
+        for subject in map(subject_from_response_bytes, self.subjects):
+            for model, _penalty, instances in subject.best_models:
+                for instance in sorted(instances):
+                    for missed in subject.missed.get(instance, []):
+                        yield (
+                            subject.name,
+                            model_get_name(model),
+                            base64.b64encode(instance).decode('ascii'),
+                            missed.row + 1,  # numbered from 1
+                            ','.join(self.alternatives[i] for i in sorted(missed.predicted)),
+                        )
+
+            yield None  # bump progress
+
     def export_detailed(self) -> Iterator[Optional[Tuple[str,Optional[int],int,str,str]]]:
         for subject in map(subject_from_response_bytes, self.subjects):
             for model, penalty, instances in subject.best_models:
@@ -11,7 +11,7 @@ import dataset
 import gui.copycat_simulation
 import gui.estimation
 import simulation
-from core import Core
+from core import Core, Failure
 from dataset import Dataset, DatasetHeaderC, ChoiceRow, \
     Subject, SubjectC, ExportVariant, Analysis, PackedSubject, PackedSubjectC
 from gui.progress import Worker
@@ -20,16 +20,20 @@ from dataset.consistency_result import ConsistencyResult
 from dataset.experiment_stats import ExperimentStats
 from dataset.tuple_intrans_alts import TupleIntransAlts
 from dataset.tuple_intrans_menus import TupleIntransMenus
+from dataset.axioms import AxiomViolations
+from dataset.stochastic_axioms import StochasticAxioms
 import dataset.consistency_result
 import dataset.experiment_stats
 import dataset.tuple_intrans_alts
 import dataset.tuple_intrans_menus
+import dataset.axioms
+import dataset.stochastic_axioms
 import dataset.integrity_check
 import dataset.estimation_result as estimation_result
 import uic.view_dataset
 import util.tree_model
 from util.codec import FileIn, FileOut, namedtupleC, strC, intC, \
-    frozensetC, maybe
+    frozensetC, maybe, tupleC
 from util.codec_progress import CodecProgress, listCP, oneCP
 
 log = logging.getLogger(__name__)
@@ -175,7 +179,7 @@ class ExperimentalData(Dataset):
         subjects : list[PackedSubject] = []
 
         with Core() as core:
-            worker.interrupt = lambda: core.shutdown()  # register interrupt hook
+            worker.interrupt = lambda: core.cancel()  # register interrupt hook
 
             worker.set_work_size(len(self.subjects) * options.multiplicity)
             position = 0
@@ -270,43 +274,75 @@ class ExperimentalData(Dataset):
         return ds
 
     def analysis_estimation(self, worker : Worker, options : gui.estimation.Options) -> EstimationResult:
+        ds = EstimationResult(
+            self.name + ' (model est.)',
+            self.alternatives,
+        )
 
-        CHUNK_SIZE = 64
         with Core() as core:
-            worker.interrupt = lambda: core.shutdown()  # register interrupt hook
+            worker.interrupt = lambda: core.cancel()  # register interrupt hook
+
+            # responses arrive as soon as they are finished, in arbitrary order,
+            # and go straight to the result so that a failure does not lose them
+            indices : list[int] = []
+            def on_partial(part : tuple[int, estimation_result.PackedResponse]) -> None:
+                index, response = part
+                indices.append(index)
+                ds.subjects.append(response)
 
-            rows : list[estimation_result.PackedResponse] = []
             worker.set_work_size(len(self.subjects))
-            for i in range(0, len(self.subjects), CHUNK_SIZE):
-                request = estimation_result.Request(
-                    subjects=self.subjects[i:i+CHUNK_SIZE],
-                    models=options.models,
-                    disable_parallelism=options.disable_parallelism,
-                    disregard_deferrals=options.disregard_deferrals,
-                )
+            request = estimation_result.Request(
+                subjects=self.subjects,
+                models=options.models,
+                disable_parallelism=options.disable_parallelism,
+                disregard_deferrals=options.disregard_deferrals,
+                diagnostics=options.diagnostics,
+                stochastic_models=options.stochastic_models,
+            )
 
-                responses = core.call(
-                    'estimation',
+            failure : Optional[Failure] = None
+            try:
+                core.call_streaming(
+                    'estimation-stream',
                     estimation_result.RequestC,
-                    estimation_result.PackedResponsesC,
-                    request
+                    (tupleC(intC, estimation_result.PackedResponseC), on_partial),
+                    intC,
+                    request,
+                    on_progress=worker.set_progress,
                 )
-                rows.extend(responses)
-
-                worker.set_progress(len(rows))
-
-            ds = EstimationResult(
-                self.name + ' (model est.)',
-                self.alternatives,
+            except Failure as e:
+                failure = e
+
+        # back to the order of the subjects
+        ds.subjects = [response for _index, response in sorted(zip(indices, ds.subjects))]
+
+        if failure is not None:
+            if not ds.subjects:
+                raise failure
+
+            arrived = set(indices)
+            failed = [
+                Subject.unpack(subject).name
+                for i, subject in enumerate(self.subjects)
+                if i not in arrived
+            ]
+            raise dataset.PartialFailure(
+                'Estimation failed for %d of %d subjects: %s%s\n\n%s' % (
+                    len(failed),
+                    len(self.subjects),
+                    ', '.join(failed[:10]),
+                    ', ...' if len(failed) > 10 else '',
+                    failure,
+                ),
+                ds,
             )
-            ds.subjects = rows
 
         return ds
 
     # detailed consistency
     def analysis_consistency(self, worker : Worker, _config : None) -> ConsistencyResult:
         with Core() as core:
-            worker.interrupt = lambda: core.shutdown()  # interrupt hook
+            worker.interrupt = lambda: core.cancel()  # interrupt hook
 
             rows = []
 
@@ -314,9 +350,11 @@ class ExperimentalData(Dataset):
             for i, subject in enumerate(self.subjects):
                 response = core.call(
                     'consistency',
-                    PackedSubjectC,
+                    dataset.consistency_result.RequestC,
                     dataset.consistency_result.SubjectRawC,
-                    subject
+                    dataset.consistency_result.Request(
+                        subject, dataset.consistency_result.CYCLE_LIMIT,
+                    ),
                 )
                 rows.append(response)
 
@@ -334,7 +372,7 @@ class ExperimentalData(Dataset):
         worker.set_work_size(len(self.subjects))
 
         with Core() as core:
-            worker.interrupt = lambda: core.shutdown()
+            worker.interrupt = lambda: core.cancel()
 
             for i, subject in enumerate(self.subjects):
                 subjects.append(core.call(
@@ -379,15 +417,17 @@ class ExperimentalData(Dataset):
         worker.set_work_size(len(self.subjects))
 
         with Core() as core:
-            worker.interrupt = lambda: core.shutdown()
+            worker.interrupt = lambda: core.cancel()
 
             for i, subject in enumerate(self.subjects):
                 subjects.append(
                     core.call(
                         'tuple-intrans-menus',
-                        PackedSubjectC,
+                        dataset.consistency_result.RequestC,
                         dataset.tuple_intrans_menus.SubjectC,
-                        subject,
+                        dataset.consistency_result.Request(
+                            subject, dataset.consistency_result.CYCLE_LIMIT,
+                        ),
                     )
                 )
                 worker.set_progress(i+1)
@@ -401,15 +441,17 @@ class ExperimentalData(Dataset):
         worker.set_work_size(len(self.subjects))
 
         with Core() as core:
-            worker.interrupt = lambda: core.shutdown()
+            worker.interrupt = lambda: core.cancel()
 
             for i, subject in enumerate(self.subjects):
                 subjects.append(
                     core.call(
                         'tuple-intrans-alts',
-                        PackedSubjectC,
+                        dataset.consistency_result.RequestC,
                         dataset.tuple_intrans_alts.SubjectC,
-                        subject,
+                        dataset.consistency_result.Request(
+                            subject, dataset.consistency_result.CYCLE_LIMIT,
+                        ),
                     )
                 )
                 worker.set_progress(i+1)
@@ -418,13 +460,55 @@ class ExperimentalData(Dataset):
         ds.subjects = subjects
         return ds
 
+    def analysis_axioms(self, worker : Worker, _config : None) -> AxiomViolations:
+        subjects = []
+        worker.set_work_size(len(self.subjects))
+
+        with Core() as core:
+            worker.interrupt = lambda: core.cancel()
+
+            for i, subject in enumerate(self.subjects):
+                subjects.append(
+                    core.call(
+                        'axioms',
+                        dataset.axioms.RequestC,
+                        dataset.axioms.SubjectC,
+                        dataset.axioms.Request(subject),
+                    )
+                )
+                worker.set_progress(i+1)
+
+        ds = AxiomViolations(self.name + ' (axioms)', self.alternatives)
+        ds.subjects = subjects
+        return ds
+
+    def analysis_stochastic_axioms(self, worker : Worker, _config : None) -> StochasticAxioms:
+        # pooling needs all subjects at once, hence a single call
+        worker.set_work_size(1)
+
+        with Core() as core:
+            worker.interrupt = lambda: core.cancel()
+
+            response = core.call(
+                'stochastic-axioms',
+                dataset.stochastic_axioms.RequestC,
+                dataset.stochastic_axioms.ResponseC,
+                dataset.stochastic_axioms.Request(self.subjects),
+            )
+            worker.set_progress(1)
+
+        ds = StochasticAxioms(self.name + ' (stochastic axioms)', self.alternatives)
+        ds.subjects = response.subjects
+        ds.pooled = response.pooled
+        return ds
+
     def analysis_integrity_check(self, worker : Worker, _config : None) -> dataset.AnalysisResult:
         worker.set_work_size(len(self.subjects))
 
         subjects : list[dataset.integrity_check.Subject] = []
 
         with Core() as core:
-            worker.interrupt = lambda: core.shutdown()
+            worker.interrupt = lambda: core.cancel()
 
             for i, subject in enumerate(self.subjects):
                 subj_issues = core.call(
@@ -477,6 +561,16 @@ class ExperimentalData(Dataset):
                 config=None,
                 run=self.analysis_tuple_intrans_alts,
             ),
+            Analysis(
+                name='Contraction, expansion and deferral axioms',
+                config=None,
+                run=self.analysis_axioms,
+            ),
+            Analysis(
+                name='Stochastic choice axioms',
+                config=None,
+                run=self.analysis_stochastic_axioms,
+            ),
             Analysis(
                 name='Model estimation',
                 config=self.config_estimation,
@@ -0,0 +1,213 @@
+import logging
+from typing import NamedTuple, List, Sequence, Iterator, Tuple, Optional, FrozenSet
+from PyQt5.QtWidgets import QDialog, QHeaderView
+
+import uic.view_dataset
+import util.tree_model
+from gui.progress import Worker
+from dataset import Dataset, DatasetHeaderC, Analysis, ExportVariant, PackedSubject, PackedSubjectC
+from util.codec import FileIn, FileOut, listC, strC, intC, doubleC, \
+    tupleC, namedtupleC, frozensetC, maybe
+from util.codec_progress import CodecProgress, listCP, oneCP
+
+log = logging.getLogger(__name__)
+
+class Request(NamedTuple):
+    subjects: List[PackedSubject]
+
+RequestC = namedtupleC(Request, listC(PackedSubjectC))
+
+class RegularityViolation(NamedTuple):
+    alt : int
+    smaller : FrozenSet[int]
+    larger : FrozenSet[int]
+    p_smaller : float  # P(alt | smaller)
+    p_larger : float  # P(alt | larger)
+
+RegularityViolationC = namedtupleC(RegularityViolation,
+    intC, frozensetC(intC), frozensetC(intC), doubleC, doubleC)
+
+class TripleViolation(NamedTuple):
+    alts : Tuple[int, int, int]  # (a, b, c)
+    p_ab : float
+    p_bc : float
+    p_ac : float
+
+TripleViolationC = namedtupleC(TripleViolation, tupleC(intC, intC, intC), doubleC, doubleC, doubleC)
+
+class Report(NamedTuple):
+    name : Optional[str]  # None when pooled
+    observations : int
+    ignored_rows : int
+    regularity : List[RegularityViolation]
+    weak : List[TripleViolation]
+    moderate : List[TripleViolation]
+    strong : List[TripleViolation]
+    triangle : List[TripleViolation]
+
+    def label(self) -> str:
+        return '(pooled)' if self.name is None else self.name
+
+ReportC = namedtupleC(Report, maybe(strC), intC, intC, listC(RegularityViolationC),
+    listC(TripleViolationC), listC(TripleViolationC), listC(TripleViolationC), listC(TripleViolationC))
+
+class Response(NamedTuple):
+    subjects : List[Report]
+    pooled : Report
+
+ResponseC = namedtupleC(Response, listC(ReportC), ReportC)
+
+# (export name, view header, Report field)
+TRANSITIVITY_TESTS = (
+    ('weak', 'Weak stochastic transitivity', 'weak'),
+    ('moderate', 'Moderate stochastic transitivity', 'moderate'),
+    ('strong', 'Strong stochastic transitivity', 'strong'),
+    ('triangle', 'Triangle inequality', 'triangle'),
+)
+
+def menu_str(alternatives : Sequence[str], menu : FrozenSet[int]) -> str:
+    return '{' + ','.join(alternatives[i] for i in sorted(menu)) + '}'
+
+class ReportNode(util.tree_model.Node):
+    def __init__(self, parent_node, row: int, report: Report) -> None:
+        util.tree_model.Node.__init__(
+            self, parent_node, row,
+            fields=(
+                report.label(),
+                report.observations,
+                len(report.regularity),
+                len(report.weak),
+                len(report.moderate),
+                len(report.strong),
+                len(report.triangle),
+            ),
+        )
+
+class RootNode(util.tree_model.RootNode):
+    def __init__(self, reports : List[Report]) -> None:
+        util.tree_model.RootNode.__init__(self, len(reports))
+        self.reports = reports
+
+    def create_child(self, row: int) -> ReportNode:
+        return ReportNode(self, row, self.reports[row])
+
+class StochasticAxioms(Dataset):
+    class ViewDialog(QDialog, uic.view_dataset.Ui_ViewDataset):
+        def __init__(self, ds : 'StochasticAxioms') -> None:
+            QDialog.__init__(self)
+            self.setupUi(self)
+
+            self.ds = ds
+            self.model = util.tree_model.TreeModel(
+                RootNode(ds.reports()),
+                headers=(
+                    'Subject',
+                    'Observations',
+                    'Regularity',
+                    *(header for _name, header, _field in TRANSITIVITY_TESTS),
+                ),
+            )
+            self.twRows.setModel(self.model)
+
+            self.twRows.header().setSectionResizeMode(QHeaderView.ResizeToContents)
+            self.twRows.header().setStretchLastSection(False)
+
+    def __init__(self, name : str, alternatives : Sequence[str] = ()) -> None:
+        Dataset.__init__(self, name, alternatives)
+        self.subjects : List[Report] = []
+        self.pooled : Optional[Report] = None
+
+    def reports(self) -> List[Report]:
+        return self.subjects + ([self.pooled] if self.pooled else [])
+
+    def label_size(self) -> str:
+        return '%d subjects' % len(self.subjects)
+
+    def get_analyses(self) -> Sequence[Analysis]:
+        return ()
+
+    def get_export_variants(self) -> Sequence[ExportVariant]:
+        return (
+            ExportVariant(
+                name='Summary',
+                column_names=('subject', 'observations', 'ignored_rows', 'regularity')
+                    + tuple(name for name, _header, _field in TRANSITIVITY_TESTS),
+                get_rows=self.export_summary,
+                size=len(self.subjects) + 1,
+            ),
+            ExportVariant(
+                name='Regularity violations',
+                column_names=('subject', 'alternative', 'smaller_menu', 'larger_menu', 'p_smaller', 'p_larger'),
+                get_rows=self.export_regularity,
+                size=len(self.subjects) + 1,
+            ),
+            ExportVariant(
+                name='Transitivity violations',
+                column_names=('subject', 'test', 'a', 'b', 'c', 'p_ab', 'p_bc', 'p_ac'),
+                get_rows=self.export_transitivity,
+                size=len(self.subjects) + 1,
+            ),
+        )
+
+    def export_summary(self) -> Iterator[Optional[Tuple[object, ...]]]:
+        for report in self.reports():
+            yield (
+                report.label(),
+                report.observations,
+                report.ignored_rows,
+                len(report.regularity),
+                *(len(getattr(report, field)) for _name, _header, field in TRANSITIVITY_TESTS),
+            )
+            yield None  # bump progress
+
+    def export_regularity(self) -> Iterator[Optional[Tuple[str,str,str,str,float,float]]]:
+        for report in self.reports():
+            for v in report.regularity:
+                yield (
+                    report.label(),
+                    self.alternatives[v.alt],
+                    menu_str(self.alternatives, v.smaller),
+                    menu_str(self.alternatives, v.larger),
+                    v.p_smaller,
+                    v.p_larger,
+                )
+            yield None  # bump progress
+
+    def export_transitivity(self) -> Iterator[Optional[Tuple[str,str,str,str,str,float,float,float]]]:
+        for report in self.reports():
+            for name, _header, field in TRANSITIVITY_TESTS:
+                for v in getattr(report, field):
+                    a, b, c = v.alts
+                    yield (
+                        report.label(),
+                        name,
+                        self.alternatives[a],
+                        self.alternatives[b],
+                        self.alternatives[c],
+                        v.p_ab,
+                        v.p_bc,
+                        v.p_ac,
+                    )
+            yield None  # bump progress
+
+    @classmethod
+    def get_codec_progress(_cls) -> CodecProgress['StochasticAxioms']:
+        DatasetHeaderC_encode, DatasetHeaderC_decode = DatasetHeaderC.enc_dec()
+        subjects_size, subjects_encode, subjects_decode = listCP(oneCP(ReportC)).enc_dec()
+        pooled_encode, pooled_decode = maybe(ReportC).enc_dec()
+
+        def get_size(x : 'StochasticAxioms') -> int:
+            return subjects_size(x.subjects)
+
+        def encode(worker : Worker, f : FileOut, x : 'StochasticAxioms') -> None:
+            DatasetHeaderC_encode(f, (x.name, x.alternatives))
+            subjects_encode(worker, f, x.subjects)
+            pooled_encode(f, x.pooled)
+
+        def decode(worker : Worker, f : FileIn) -> 'StochasticAxioms':
+            ds = StochasticAxioms(*DatasetHeaderC_decode(f))
+            ds.subjects = subjects_decode(worker, f)
+            ds.pooled = pooled_decode(f)
+            return ds
+
+        return CodecProgress(get_size, encode, decode)
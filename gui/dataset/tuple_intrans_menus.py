@@ -7,7 +7,7 @@ import util.tree_model
 from gui.progress import Worker
 from dataset import Dataset, DatasetHeaderC, Analysis, ExportVariant
 from util.codec import Codec, FileIn, FileOut, listC, strC, intC, \
-    tupleC, namedtupleC, setC, frozensetC
+    tupleC, namedtupleC, setC, frozensetC, boolC
 from util.codec_progress import CodecProgress, listCP, oneCP
 
 log = logging.getLogger(__name__)
@@ -21,8 +21,9 @@ RowC = namedtupleC(Row, intC, setC(frozensetC(frozensetC(intC))))
 class Subject(NamedTuple):
     name : str
     rows : List[Row]
+    truncated : bool  # some cycles were left out
 
-SubjectC = namedtupleC(Subject, strC, listC(RowC))
+SubjectC = namedtupleC(Subject, strC, listC(RowC), boolC)
 
 class MenuRowNode(util.tree_model.Node):
     def __init__(self, parent_node, row: int, alternatives : List[str], xs : FrozenSet[FrozenSet[int]]) -> None:
@@ -15,8 +15,8 @@ import uic.estimation
 import platform_specific
 from core import Core
 from gui.progress import Worker, Cancelled
-from model import Model
-from dataclasses import dataclass
+from model import Model, StochasticModel
+from dataclasses import dataclass, field
 
 log = logging.getLogger(__name__)
 
@@ -25,6 +25,8 @@ class Options:
     models : List[Model]
     disable_parallelism : bool
     disregard_deferrals : bool
+    diagnostics : bool = False
+    stochastic_models : List[StochasticModel] = field(default_factory=list)
 
 class Estimation(uic.estimation.Ui_Estimation, gui.ExceptionDialog):
     def __init__(self):
@@ -111,6 +113,13 @@ class Estimation(uic.estimation.Ui_Estimation, gui.ExceptionDialog):
             models=[model for cb, model in self.checkboxes if cb.isChecked()],
             disable_parallelism=self.cbDisableParallelism.isChecked(),
             disregard_deferrals=self.cbDisregardDeferrals.isChecked(),
+            diagnostics=self.cbDiagnostics.isChecked(),
+            stochastic_models=[
+                stochastic_model for cb, stochastic_model in (
+                    (self.cbLuce, model.Luce()),
+                    (self.cbRandomPreference, model.RandomPreference()),
+                ) if cb.isChecked()
+            ],
         )
 
     # override from QDialog
@@ -123,13 +132,5 @@ class Estimation(uic.estimation.Ui_Estimation, gui.ExceptionDialog):
                 'Please select at least one model for estimation',
             )
 
-        elif models == [model.SequentiallyRationalizableChoice()]:
-            QMessageBox.warning(
-                self,
-                'Please select additional models',
-                'Sequentially Rationalizable Choice is an experimental model '
-                'and cannot be selected alone. Please select at least one another model.'
-            )
-
         else:
             QDialog.accept(self)
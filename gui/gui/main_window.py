@@ -388,7 +388,7 @@ class MainWindow(QMainWindow, uic.main_window.Ui_MainWindow, gui.ExceptionDialog
                 ds.observ_count = 0
 
                 with Core() as core:
-                    self.interrupt = lambda: core.shutdown()
+                    self.interrupt = lambda: core.cancel()
 
                     for subj_nr in range(1, options.subject_count+1):
                         response = simulation.run(core, simulation.Request(
@@ -53,6 +53,12 @@ class SequentiallyRationalizableChoice(NamedTuple):
 class Swaps(NamedTuple):
     tag : int = 8
 
+class LimitedAttention(NamedTuple):
+    tag : int = 9
+
+class RandomConsideration(NamedTuple):
+    tag : int = 10
+
 Model = Union[
     PreorderMaximization,
     Unattractiveness,
@@ -63,6 +69,8 @@ Model = Union[
     TopTwo,
     SequentiallyRationalizableChoice,
     Swaps,
+    LimitedAttention,
+    RandomConsideration,
 ]
 
 ModelC = enumC('Model', {
@@ -75,6 +83,8 @@ ModelC = enumC('Model', {
     TopTwo: (),
     SequentiallyRationalizableChoice: (),
     Swaps: (),
+    LimitedAttention: (),
+    RandomConsideration: (),
 })
 
 # dicts are ordered from python 3.5 onwards
@@ -111,6 +121,10 @@ SPECIAL_NAMES = {
         'Sequentially Rationalizable Choice',
     Swaps():
         'Utility Maximization - Swaps',
+    LimitedAttention():
+        'Choice with Limited Attention',
+    RandomConsideration():
+        'Random Consideration',
 }
 
 ORDERING_INDICES = (
@@ -122,6 +136,7 @@ ORDERING_INDICES = (
 
 UPPER_BOUND_MODELS = {
     SequentiallyRationalizableChoice(),
+    LimitedAttention(),
 }
 
 # returns something comparable
@@ -187,15 +202,16 @@ MODELS = [
                 ('Strict', PartiallyDominantChoice(fc=True)),
                 None,
             ),
-            mgroup(sublabel(
-                    'Sequentially Rationalizable Choice',
-                    '(experimental/partial functionality)',
-                ),
+            mgroup('Sequentially Rationalizable Choice',
                 'models/fc.html#sequentially-rationalizable-choice',
                 ('Strict', SequentiallyRationalizableChoice()),
                 None,
             ),
-            # mgroup('Choice with limited attention'),
+            mgroup('Choice with Limited Attention',
+                'models/fc.html#choice-with-limited-attention',
+                ('Strict', LimitedAttention()),
+                None,
+            ),
         )),
         Category('Non-Forced Choice', (
             mgroup('Utility Maximization with an Outside Option',
@@ -218,6 +234,11 @@ MODELS = [
                 ('Strict', PartiallyDominantChoice(fc=False)),
                 None,
             ),
+            mgroup('Random Consideration',
+                'models/nfc.html#random-consideration',
+                ('Strict', RandomConsideration()),
+                None,
+            ),
         )),
     )),
     Category('Choice with a Default Alternative', (
@@ -240,3 +261,22 @@ def names_in_order() -> Iterator[str]:
 
     for item in MODELS:
         yield from traverse(item)
+
+# stochastic models, fitted by maximum likelihood alongside the models above
+class Luce(NamedTuple):
+    tag : int = 0
+
+class RandomPreference(NamedTuple):
+    tag : int = 1
+
+StochasticModel = Union[Luce, RandomPreference]
+
+StochasticModelC = enumC('StochasticModel', {
+    Luce: (),
+    RandomPreference: (),
+})
+
+STOCHASTIC_NAMES = {
+    Luce(): 'Luce (multinomial logit)',
+    RandomPreference(): 'Random preferences',
+}